@@ -1,25 +1,41 @@
 use std::env;
 use std::str::FromStr;
+use std::time::Duration;
 
 use alloy_primitives::{Address, Bytes, FixedBytes, B256};
 use alloy_sol_types::{sol, SolType};
 use anyhow::Result;
+use async_trait::async_trait;
+use blobstreamx::input::{DataCommitmentInputFetcher, HeaderHashSource};
+use blobstreamx::logging::init_logger;
+use blobstreamx::operator::{
+    await_commitment_event, expected_commitment_event, run_until_shutdown, serve_health,
+    spawn_shutdown_listener, HealthState, OperatorTask,
+};
 use ethers::abi::AbiEncode;
 use ethers::contract::abigen;
-use ethers::providers::{Http, Provider};
+use ethers::providers::{Http, Middleware, Provider};
 use ethers::signers::LocalWallet;
-use log::{error, info};
+use ethers::types::H256;
+use log::{error, info, warn};
 use succinct_client::request::SuccinctClient;
 use tendermintx::input::InputDataFetcher;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
 
 // Note: Update ABI when updating contract.
 abigen!(BlobstreamX, "./abi/BlobstreamX.abi.json");
 
+// How long a local relay waits for the contract to emit the relayed data commitment.
+const COMMITMENT_EVENT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
 struct BlobstreamXConfig {
     address: Address,
     chain_id: u32,
     local_prove_mode: bool,
     local_relay_mode: bool,
+    block_interval: u64,
+    data_commitment_max: u64,
 }
 
 type NextHeaderInputTuple = sol! { tuple(uint64, bytes32) };
@@ -34,10 +50,11 @@ struct BlobstreamXOperator {
     contract: BlobstreamX<Provider<Http>>,
     client: SuccinctClient,
     data_fetcher: InputDataFetcher,
+    health: HealthState,
 }
 
 impl BlobstreamXOperator {
-    pub async fn new() -> Self {
+    pub async fn new(block_interval: u64, data_commitment_max: u64, health: HealthState) -> Self {
         let contract_address = env::var("CONTRACT_ADDRESS").expect("CONTRACT_ADDRESS must be set");
         let chain_id = env::var("CHAIN_ID").expect("CHAIN_ID must be set");
         let address = contract_address
@@ -63,6 +80,8 @@ impl BlobstreamXOperator {
             chain_id: chain_id.parse::<u32>().expect("invalid chain id"),
             local_prove_mode: local_prove_mode_bool,
             local_relay_mode: local_relay_mode_bool,
+            block_interval,
+            data_commitment_max,
         };
 
         let data_fetcher = InputDataFetcher::default();
@@ -104,6 +123,7 @@ impl BlobstreamXOperator {
             gateway_address,
             client,
             data_fetcher,
+            health,
         }
     }
 
@@ -172,7 +192,60 @@ impl BlobstreamXOperator {
         Ok(request_id)
     }
 
-    async fn run(&mut self, loop_delay_mins: u64, block_interval: u64, data_commitment_max: u64) {
+    /// Relay the proof of request_id, which commits to the blocks [start_block, end_block). In
+    /// local relay mode, the operator sends the relay itself, so it also checks the contract stored
+    /// the data commitment of the RPC for the range.
+    async fn relay(&mut self, request_id: String, start_block: u64, end_block: u64) {
+        // The relay stores the commitment with the current proof nonce, after the current block.
+        let proof_nonce = self.contract.state_proof_nonce().await.unwrap();
+        let from_block = self.contract.client().get_block_number().await.unwrap();
+
+        // If in local mode, this will submit the request on-chain.
+        let res = self
+            .client
+            .relay_proof(
+                request_id,
+                Some(self.ethereum_rpc_url.as_ref()),
+                self.wallet.clone(),
+                self.gateway_address.as_deref(),
+            )
+            .await;
+        if let Err(e) = res {
+            error!("Relay failed: {}", e);
+            return;
+        }
+        info!("Relayed successfully!");
+
+        if self.config.local_relay_mode {
+            let data_commitment = H256(
+                self.data_fetcher
+                    .get_data_commitment(start_block, end_block)
+                    .await,
+            );
+            let (topics, data) =
+                expected_commitment_event(proof_nonce, start_block, end_block, data_commitment);
+            if let Err(e) = await_commitment_event(
+                self.contract.client().as_ref(),
+                self.contract.address(),
+                from_block,
+                &topics,
+                &data,
+                COMMITMENT_EVENT_TIMEOUT,
+            )
+            .await
+            {
+                error!("Relayed commitment check failed: {}", e);
+                return;
+            }
+            info!(
+                "Contract stored the data commitment of blocks [{}, {})",
+                start_block, end_block
+            );
+        }
+        self.health.record_success();
+    }
+
+    async fn run(&mut self, loop_delay_mins: u64, shutdown: watch::Receiver<bool>) {
         info!("Starting BlobstreamX operator");
         let header_range_max = self.contract.data_commitment_max().await.unwrap();
 
@@ -181,119 +254,122 @@ impl BlobstreamXOperator {
             panic!("header_range_max must be greater than 0");
         }
 
-        loop {
-            // Get the function IDs from the contract (they can change if the contract is updated).
-            let next_header_function_id =
-                FixedBytes(self.contract.next_header_function_id().await.unwrap());
-            let header_range_function_id =
-                FixedBytes(self.contract.header_range_function_id().await.unwrap());
-
-            let current_block = self.contract.latest_block().await.unwrap();
+        run_until_shutdown(self, Duration::from_secs(60 * loop_delay_mins), shutdown).await;
+    }
+}
 
-            // Get the head of the chain.
-            let latest_tendermint_signed_header =
-                self.data_fetcher.get_latest_signed_header().await;
-            let latest_tendermint_block_nb = latest_tendermint_signed_header.header.height.value();
+#[async_trait]
+impl OperatorTask for BlobstreamXOperator {
+    async fn run_once(&mut self) {
+        let block_interval = self.config.block_interval;
+        let data_commitment_max = self.config.data_commitment_max;
+
+        // Get the function IDs from the contract (they can change if the contract is updated).
+        let next_header_function_id =
+            FixedBytes(self.contract.next_header_function_id().await.unwrap());
+        let header_range_function_id =
+            FixedBytes(self.contract.header_range_function_id().await.unwrap());
+
+        let current_block = self.contract.latest_block().await.unwrap();
+
+        // Get the head of the chain.
+        let latest_tendermint_block_nb = match self.data_fetcher.get_latest_header().await {
+            Ok(header) => header.height.value(),
+            Err(e) => {
+                error!("Failed to fetch the latest header: {}", e);
+                return;
+            }
+        };
 
-            // Subtract 1 block to ensure the block is stable.
-            let latest_stable_tendermint_block = latest_tendermint_block_nb - 1;
+        // Subtract 1 block to ensure the block is stable.
+        let latest_stable_tendermint_block = latest_tendermint_block_nb - 1;
 
-            // block_to_request is the closest interval of block_interval less than min(latest_stable_tendermint_block, data_commitment_max + current_block)
-            let max_block = std::cmp::min(
-                latest_stable_tendermint_block,
-                data_commitment_max + current_block,
-            );
-            let block_to_request = max_block - (max_block % block_interval);
-
-            // If block_to_request is greater than the current block in the contract, attempt to request.
-            if block_to_request > current_block {
-                // The next block the operator should request.
-                let max_end_block = block_to_request;
-
-                let target_block = self
-                    .data_fetcher
-                    .find_block_to_request(current_block, max_end_block)
-                    .await;
-
-                info!("Attempting to step to block {}", target_block);
-
-                if target_block - current_block == 1 {
-                    // Request the next header if the target block is the next block.
-                    match self
-                        .request_next_header(current_block, next_header_function_id)
-                        .await
-                    {
-                        Ok(request_id) => {
-                            info!("Next header request submitted: {}", request_id);
-
-                            // If in local mode, this will submit the request on-chain.
-                            let res = self
-                                .client
-                                .relay_proof(
-                                    request_id,
-                                    Some(self.ethereum_rpc_url.as_ref()),
-                                    self.wallet.clone(),
-                                    self.gateway_address.as_deref(),
-                                )
-                                .await;
-                            match res {
-                                Ok(_) => info!("Relayed successfully!"),
-                                Err(e) => {
-                                    error!("Relay failed: {}", e);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            error!("Next header request failed: {}", e);
-                            continue;
-                        }
-                    };
-                } else {
-                    // Request a header range if the target block is not the next block.
-                    match self
-                        .request_header_range(current_block, target_block, header_range_function_id)
-                        .await
-                    {
-                        Ok(request_id) => {
-                            info!("Header range request submitted: {}", request_id);
-
-                            // If in local mode, this will submit the request on-chain.
-                            let res = self
-                                .client
-                                .relay_proof(
-                                    request_id,
-                                    Some(self.ethereum_rpc_url.as_ref()),
-                                    self.wallet.clone(),
-                                    self.gateway_address.as_deref(),
-                                )
-                                .await;
-                            match res {
-                                Ok(_) => info!("Relayed successfully!"),
-                                Err(e) => {
-                                    error!("Relay failed: {}", e);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            error!("Header range request failed: {}", e);
-                            continue;
-                        }
-                    };
+        // block_to_request is the closest interval of block_interval less than min(latest_stable_tendermint_block, data_commitment_max + current_block)
+        let max_block = std::cmp::min(
+            latest_stable_tendermint_block,
+            data_commitment_max + current_block,
+        );
+        let block_to_request = max_block - (max_block % block_interval);
+
+        // If block_to_request is greater than the current block in the contract, attempt to request.
+        if block_to_request > current_block {
+            // The next block the operator should request.
+            let max_end_block = block_to_request;
+
+            let target_block = self
+                .data_fetcher
+                .find_block_to_request(current_block, max_end_block)
+                .await;
+
+            info!("Attempting to step to block {}", target_block);
+
+            let target_signed_header = match self
+                .data_fetcher
+                .get_finalized_signed_header_from_number(target_block)
+                .await
+            {
+                Ok(signed_header) => signed_header,
+                Err(e) => {
+                    // The target block's commit is incomplete, retry on the next loop.
+                    warn!("{}", e);
+                    return;
                 }
-            } else {
-                info!("Next block to request is {} which is > the head of the Tendermint chain which is {}. Sleeping.", block_to_request + block_interval, latest_stable_tendermint_block);
+            };
+
+            // The chain may have reorged since the target block was fetched, retry on the next
+            // loop if so.
+            let target_header_hash =
+                H256::from_slice(target_signed_header.header.hash().as_bytes());
+            if let Err(e) = self
+                .data_fetcher
+                .verify_block_still_canonical(target_block, target_header_hash)
+                .await
+            {
+                warn!("{}", e);
+                return;
             }
 
-            tokio::time::sleep(tokio::time::Duration::from_secs(60 * loop_delay_mins)).await;
+            if target_block - current_block == 1 {
+                // Request the next header if the target block is the next block.
+                match self
+                    .request_next_header(current_block, next_header_function_id)
+                    .await
+                {
+                    Ok(request_id) => {
+                        info!("Next header request submitted: {}", request_id);
+                        self.relay(request_id, current_block, target_block).await;
+                    }
+                    Err(e) => {
+                        error!("Next header request failed: {}", e);
+                        return;
+                    }
+                };
+            } else {
+                // Request a header range if the target block is not the next block.
+                match self
+                    .request_header_range(current_block, target_block, header_range_function_id)
+                    .await
+                {
+                    Ok(request_id) => {
+                        info!("Header range request submitted: {}", request_id);
+                        self.relay(request_id, current_block, target_block).await;
+                    }
+                    Err(e) => {
+                        error!("Header range request failed: {}", e);
+                        return;
+                    }
+                };
+            }
+        } else {
+            info!("Next block to request is {} which is > the head of the Tendermint chain which is {}. Sleeping.", block_to_request + block_interval, latest_stable_tendermint_block);
         }
     }
 }
 
 #[tokio::main]
 async fn main() {
-    env::set_var("RUST_LOG", "info");
     dotenv::dotenv().ok();
-    env_logger::init();
+    init_logger("info");
 
     let loop_delay_mins_env = env::var("LOOP_DELAY_MINS");
     let mut loop_delay_mins = 5;
@@ -322,8 +398,19 @@ async fn main() {
             .expect("invalid DATA_COMMITMENT_MAX");
     }
 
-    let mut operator = BlobstreamXOperator::new().await;
-    operator
-        .run(loop_delay_mins, update_delay_blocks, data_commitment_max)
-        .await;
+    // The health endpoint reports the time of the last successful request.
+    let health_addr = env::var("HEALTH_ADDR").unwrap_or(String::from("0.0.0.0:8080"));
+    let health = HealthState::new();
+    let shutdown = spawn_shutdown_listener();
+    let listener = TcpListener::bind(&health_addr)
+        .await
+        .expect("could not bind health endpoint");
+    info!("Serving health endpoint on {}", health_addr);
+    let health_server = tokio::spawn(serve_health(listener, health.clone(), shutdown.clone()));
+
+    let mut operator =
+        BlobstreamXOperator::new(update_delay_blocks, data_commitment_max, health).await;
+    operator.run(loop_delay_mins, shutdown).await;
+
+    let _ = health_server.await;
 }
@@ -7,9 +7,16 @@
 //!
 //!
 
-use std::env;
+use std::path::Path;
 
-use clap::Parser;
+use blobstreamx::fixture::{
+    get_signed_block, get_signed_block_validators, list_fixture_blocks, migrate_fixtures,
+    validate_fixture,
+};
+use blobstreamx::input::verify_commit;
+use blobstreamx::logging::init_logger;
+use clap::{Parser, Subcommand};
+use log::{error, info};
 use tendermintx::input::InputDataFetcher;
 
 #[derive(Parser, Debug, Clone)]
@@ -17,19 +24,89 @@ use tendermintx::input::InputDataFetcher;
 pub struct FetchArgs {
     #[arg(long, default_value = "1")]
     pub block: u64,
+
+    #[command(subcommand)]
+    pub command: Option<FetchCommand>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum FetchCommand {
+    /// Move fixtures from a deprecated directory into the canonical fixture layout.
+    MigrateFixtures {
+        #[arg(long, default_value = "./fixtures/celestia")]
+        from: String,
+        #[arg(long, default_value = "./circuits/fixtures/celestia")]
+        to: String,
+    },
+    /// Verify the commit of a block from its fixture natively, without generating a proof.
+    VerifyCommit {
+        #[arg(long)]
+        block: u64,
+        #[arg(long, default_value = "./circuits/fixtures/celestia")]
+        fixture_path: String,
+    },
+    /// Check every fixture in a directory hashes to the header and validator hashes it contains.
+    ValidateFixtures {
+        #[arg(long, default_value = "./circuits/fixtures/celestia")]
+        fixture_path: String,
+    },
 }
 
 #[tokio::main]
 pub async fn main() {
-    env::set_var("RUST_LOG", "info");
     dotenv::dotenv().ok();
-    env_logger::init();
+    init_logger("info");
     let mut data_fetcher = InputDataFetcher {
         save: true,
         ..Default::default()
     };
 
     let args = FetchArgs::parse();
+    match args.command {
+        Some(FetchCommand::MigrateFixtures { from, to }) => {
+            let nb_moved = migrate_fixtures(Path::new(&from), Path::new(&to))
+                .expect("failed to migrate fixtures");
+            info!("Migrated {} fixtures from {} to {}", nb_moved, from, to);
+            return;
+        }
+        Some(FetchCommand::VerifyCommit {
+            block,
+            fixture_path,
+        }) => {
+            let signed_header = get_signed_block(&fixture_path, block);
+            let validators = get_signed_block_validators(&fixture_path, block);
+            let verification = verify_commit(&signed_header, &validators);
+            if verification.is_valid() {
+                info!("{}", verification);
+            } else {
+                error!("{}", verification);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(FetchCommand::ValidateFixtures { fixture_path }) => {
+            let block_numbers =
+                list_fixture_blocks(&fixture_path).expect("failed to list fixtures");
+            let mut nb_invalid = 0;
+            for block_number in block_numbers.iter() {
+                if let Err(e) = validate_fixture(&fixture_path, *block_number) {
+                    error!("Fixture {} is invalid: {}", block_number, e);
+                    nb_invalid += 1;
+                }
+            }
+            info!(
+                "Validated {} fixtures in {}, {} invalid",
+                block_numbers.len(),
+                fixture_path,
+                nb_invalid
+            );
+            if nb_invalid > 0 {
+                std::process::exit(1);
+            }
+            return;
+        }
+        None => {}
+    }
     let fetch_block = args.block;
 
     // Write signed_header to JSON.
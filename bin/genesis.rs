@@ -7,8 +7,7 @@
 //!
 //!
 
-use std::env;
-
+use blobstreamx::logging::init_logger;
 use clap::Parser;
 use log::info;
 use tendermintx::input::InputDataFetcher;
@@ -22,9 +21,8 @@ pub struct GenesisArgs {
 
 #[tokio::main]
 pub async fn main() {
-    env::set_var("RUST_LOG", "info");
     dotenv::dotenv().ok();
-    env_logger::init();
+    init_logger("info");
     let mut data_fetcher = InputDataFetcher::default();
     let args = GenesisArgs::parse();
 
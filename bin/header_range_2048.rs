@@ -1,9 +1,13 @@
 use blobstreamx::config::CelestiaBlobstreamXConfig2048;
 use blobstreamx::header_range::CombinedSkipCircuit;
+use blobstreamx::logging::init_logger;
 use plonky2x::backend::function::Plonky2xFunction;
 use tendermintx::config::CELESTIA_CHAIN_ID_SIZE_BYTES;
 
 fn main() {
+    dotenv::dotenv().ok();
+    init_logger("info");
+
     const VALIDATOR_SET_SIZE_MAX: usize = 100;
     const NB_MAP_JOBS: usize = 32;
     const BATCH_SIZE: usize = 64;
@@ -3,6 +3,7 @@
 use std::env;
 use std::str::FromStr;
 
+use blobstreamx::logging::init_logger;
 use clap::Parser;
 use ethers::contract::abigen;
 use ethers::signers::LocalWallet;
@@ -89,9 +90,8 @@ pub struct LocalRelayArgs {
 
 #[tokio::main]
 async fn main() {
-    env::set_var("RUST_LOG", "debug");
     dotenv::dotenv().ok();
-    env_logger::init();
+    init_logger("debug");
 
     let operator = BlobstreamXRelayer::new();
 
@@ -0,0 +1,315 @@
+use ethers::types::H256;
+use plonky2x::frontend::merkle::tree::InclusionProof;
+use plonky2x::prelude::RichField;
+use sha2::{Digest, Sha256};
+use tendermint::block::signed_header::SignedHeader;
+use tendermintx::input::InputDataFetcher;
+
+use crate::config::ChainConfig;
+use crate::consts::*;
+use crate::input::{pad_data_commitment_proofs, DataCommitmentInputFetcher};
+use crate::vars::DataCommitmentProofValueType;
+
+/// Builds the inputs of a data commitment over [start_block, end_block) incrementally, as blocks
+/// arrive. Blocks must be ingested in order, starting at start_block. Once end_block is ingested,
+/// the accumulator produces the same circuit input as the batch path
+/// (`get_data_commitment_inputs`).
+#[derive(Debug, Clone)]
+pub struct DataCommitmentAccumulator<const MAX_LEAVES: usize, F: RichField> {
+    start_block: u64,
+    end_block: u64,
+    chain_config: ChainConfig,
+    next_block: u64,
+    start_header: H256,
+    prev_header: H256,
+    data_hash_proofs: Vec<InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES, F>>,
+    last_block_id_proofs: Vec<InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_BLOCK_ID_SIZE_BYTES, F>>,
+}
+
+impl<const MAX_LEAVES: usize, F: RichField> DataCommitmentAccumulator<MAX_LEAVES, F> {
+    pub fn new(start_block: u64, end_block: u64, chain_config: ChainConfig) -> Self {
+        assert!(end_block > start_block);
+        assert!(end_block - start_block <= MAX_LEAVES as u64);
+
+        Self {
+            start_block,
+            end_block,
+            chain_config,
+            next_block: start_block,
+            start_header: H256::zero(),
+            prev_header: H256::zero(),
+            data_hash_proofs: Vec::new(),
+            last_block_id_proofs: Vec::new(),
+        }
+    }
+
+    /// The next block the accumulator expects to ingest.
+    pub fn next_block(&self) -> u64 {
+        self.next_block
+    }
+
+    /// Ingest the next block of the range. Returns the data commitment circuit input once
+    /// end_block has been ingested, and None before.
+    pub fn ingest(
+        &mut self,
+        fetcher: &InputDataFetcher,
+        signed_header: &SignedHeader,
+    ) -> Option<DataCommitmentProofValueType<MAX_LEAVES, F>> {
+        let block_number = signed_header.header.height.value();
+        assert!(
+            self.next_block <= self.end_block,
+            "data commitment range is already complete"
+        );
+        assert_eq!(
+            block_number, self.next_block,
+            "blocks must be ingested in order"
+        );
+
+        let header_hash = H256::from_slice(signed_header.header.hash().as_bytes());
+
+        if block_number == self.start_block {
+            self.start_header = header_hash;
+        } else {
+            // The block must be linked to the previous block.
+            let last_block_id = signed_header.header.last_block_id.unwrap_or_default();
+            assert_eq!(
+                last_block_id.hash.as_bytes(),
+                self.prev_header.as_bytes(),
+                "block {} is not linked to the previous block",
+                block_number
+            );
+            self.last_block_id_proofs
+                .push(fetcher.get_last_block_id_proof(&signed_header.header, self.chain_config));
+        }
+
+        // The data commitment is computed over the range [start_block, end_block - 1].
+        if block_number < self.end_block {
+            self.data_hash_proofs
+                .push(fetcher.get_data_hash_proof(&signed_header.header, self.chain_config));
+        }
+
+        self.prev_header = header_hash;
+        self.next_block += 1;
+
+        if block_number < self.end_block {
+            return None;
+        }
+
+        let mut data_hash_proofs = self.data_hash_proofs.clone();
+        let mut last_block_id_proofs = self.last_block_id_proofs.clone();
+        pad_data_commitment_proofs::<MAX_LEAVES, F>(
+            &mut data_hash_proofs,
+            &mut last_block_id_proofs,
+        );
+
+        Some(DataCommitmentProofValueType {
+            start_header: self.start_header,
+            end_header: header_hash,
+            data_hash_proofs,
+            last_block_id_proofs,
+        })
+    }
+}
+
+/// A Tendermint (RFC 6962) merkle tree which only supports appending leaves, so a running data
+/// commitment can be maintained as blocks arrive. Only the roots of the perfect subtrees the
+/// leaves decompose into are stored, so append and root are O(log n).
+#[derive(Debug, Clone, Default)]
+pub struct IncrementalMerkle {
+    // (number of leaves, root) of each perfect subtree, from the leftmost (largest) subtree.
+    subtrees: Vec<(u64, [u8; 32])>,
+    nb_leaves: u64,
+}
+
+impl IncrementalMerkle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of leaves appended.
+    pub fn len(&self) -> u64 {
+        self.nb_leaves
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nb_leaves == 0
+    }
+
+    /// Append a leaf to the tree.
+    pub fn append(&mut self, leaf: &[u8]) {
+        let mut subtree = (1u64, leaf_hash(leaf));
+        // Merge equal-sized subtrees, as in incrementing a binary counter.
+        while let Some(&(size, root)) = self.subtrees.last() {
+            if size != subtree.0 {
+                break;
+            }
+            self.subtrees.pop();
+            subtree = (size * 2, inner_hash(&root, &subtree.1));
+        }
+        self.subtrees.push(subtree);
+        self.nb_leaves += 1;
+    }
+
+    /// The root of the tree, which matches the root of a Tendermint merkle tree over the same
+    /// leaves.
+    pub fn root(&self) -> H256 {
+        // A Tendermint tree splits at the largest power of two below the number of leaves, so
+        // its root folds the perfect subtrees from the right.
+        let mut subtrees = self.subtrees.iter().rev();
+        let root = match subtrees.next() {
+            Some((_, root)) => subtrees.fold(*root, |right, (_, left)| inner_hash(left, &right)),
+            // The root of an empty tree is the hash of no data.
+            None => Sha256::digest(b"").into(),
+        };
+        H256(root)
+    }
+}
+
+fn leaf_hash(leaf: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update([MERKLE_LEAF_PREFIX])
+        .chain_update(leaf)
+        .finalize()
+        .into()
+}
+
+fn inner_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update([MERKLE_INNER_PREFIX])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2x::backend::circuit::{DefaultParameters, PlonkParameters};
+    use tendermint::crypto::default::Sha256 as TendermintSha256;
+    use tendermint::merkle::simple_hash_from_byte_vectors;
+
+    use super::*;
+    use crate::builder::encode_data_root_tuple_native;
+    use crate::fixture::get_signed_block;
+    use crate::fixture::tests::FIXTURE_PATH;
+
+    const D: usize = 2;
+    type L = DefaultParameters;
+    type F = <L as PlonkParameters<D>>::Field;
+
+    /// Ingest signed_headers, which are consecutive blocks, one at a time, and assert the input
+    /// produced at the last block is the input of the batch path over the same range.
+    fn assert_accumulator_matches_batch<const MAX_LEAVES: usize>(signed_headers: &[SignedHeader]) {
+        let start_block = signed_headers[0].header.height.value();
+        let end_block = signed_headers[signed_headers.len() - 1]
+            .header
+            .height
+            .value();
+
+        let fetcher = InputDataFetcher::default();
+        let mut accumulator = DataCommitmentAccumulator::<MAX_LEAVES, F>::new(
+            start_block,
+            end_block,
+            ChainConfig::default(),
+        );
+        let mut result = None;
+        for signed_header in signed_headers.iter() {
+            assert!(result.is_none());
+            result = accumulator.ingest(&fetcher, signed_header);
+        }
+        let result = result.expect("accumulator did not produce an input at end_block");
+
+        let (data_hash_proofs, last_block_id_proofs) = fetcher
+            .get_data_commitment_proofs::<MAX_LEAVES, F>(
+                signed_headers,
+                start_block,
+                end_block,
+                ChainConfig::default(),
+            );
+        assert_eq!(
+            result.start_header.as_bytes(),
+            signed_headers[0].header.hash().as_bytes()
+        );
+        assert_eq!(
+            result.end_header.as_bytes(),
+            signed_headers[signed_headers.len() - 1]
+                .header
+                .hash()
+                .as_bytes()
+        );
+        for (a, b) in result.data_hash_proofs.iter().zip(data_hash_proofs.iter()) {
+            assert_eq!(a.proof, b.proof);
+            assert_eq!(a.leaf, b.leaf);
+        }
+        for (a, b) in result
+            .last_block_id_proofs
+            .iter()
+            .zip(last_block_id_proofs.iter())
+        {
+            assert_eq!(a.proof, b.proof);
+            assert_eq!(a.leaf, b.leaf);
+        }
+    }
+
+    #[test]
+    fn test_data_commitment_accumulator() {
+        // The fixture has 5 consecutive blocks, so a range of 4 blocks.
+        let signed_headers = (10000..=10004)
+            .map(|block_number| get_signed_block(FIXTURE_PATH, block_number))
+            .collect::<Vec<_>>();
+        assert_accumulator_matches_batch::<8>(&signed_headers);
+    }
+
+    #[cfg_attr(feature = "ci", ignore)]
+    #[tokio::test]
+    async fn test_data_commitment_accumulator_8_blocks() {
+        env_logger::try_init().unwrap_or_default();
+        dotenv::dotenv().ok();
+
+        // Feed a range of 8 blocks, the commitment over [3000000, 3000008).
+        let fetcher = InputDataFetcher::default();
+        let signed_headers = fetcher.get_signed_header_range(3000000, 3000008).await;
+        assert_eq!(signed_headers.len(), 9);
+        assert_accumulator_matches_batch::<8>(&signed_headers);
+    }
+
+    #[test]
+    #[should_panic(expected = "blocks must be ingested in order")]
+    fn test_data_commitment_accumulator_out_of_order() {
+        let fetcher = InputDataFetcher::default();
+        let mut accumulator =
+            DataCommitmentAccumulator::<8, F>::new(10000, 10004, ChainConfig::default());
+        accumulator.ingest(&fetcher, &get_signed_block(FIXTURE_PATH, 10000));
+        accumulator.ingest(&fetcher, &get_signed_block(FIXTURE_PATH, 10002));
+    }
+
+    #[test]
+    fn test_incremental_merkle() {
+        let start_block = 10000u64;
+        let leaves = (0..100u64)
+            .map(|i| {
+                encode_data_root_tuple_native(H256::from_low_u64_be(i + 1), start_block + i)
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+
+        let mut tree = IncrementalMerkle::new();
+        let no_leaves: Vec<Vec<u8>> = Vec::new();
+        assert_eq!(
+            tree.root(),
+            H256(simple_hash_from_byte_vectors::<TendermintSha256>(
+                &no_leaves
+            ))
+        );
+        for (i, leaf) in leaves.iter().enumerate() {
+            tree.append(leaf);
+            assert_eq!(tree.len(), i as u64 + 1);
+            assert_eq!(
+                tree.root(),
+                H256(simple_hash_from_byte_vectors::<TendermintSha256>(
+                    &leaves[..=i]
+                ))
+            );
+        }
+    }
+}
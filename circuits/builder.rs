@@ -1,11 +1,14 @@
+use ethers::types::H256;
 use plonky2x::backend::circuit::Circuit;
 use plonky2x::frontend::merkle::tendermint::TendermintMerkleTree;
 use plonky2x::frontend::vars::EvmVariable;
 use plonky2x::prelude::plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
 use plonky2x::prelude::*;
 
+use crate::config::ChainConfig;
 use crate::consts::*;
 use crate::data_commitment::DataCommitmentOffchainInputs;
+use crate::header::HeaderBuilder;
 use crate::vars::{DataCommitmentProofVariable, MapReduceSubchainVariable};
 
 /// Shared context across all data commitment mapreduce jobs.
@@ -17,6 +20,18 @@ pub struct DataCommitmentSharedCtx {
     pub end_header_hash: Bytes32Variable,
 }
 
+/// Native equivalent of DataCommitmentBuilder::encode_data_root_tuple: abi.encode(height, data_hash).
+pub fn encode_data_root_tuple_native(
+    data_hash: H256,
+    height: u64,
+) -> [u8; ENC_DATA_ROOT_TUPLE_SIZE_BYTES] {
+    let mut encoded_tuple = [0u8; ENC_DATA_ROOT_TUPLE_SIZE_BYTES];
+    // The height is a uint256, so it's left-padded to 32 bytes.
+    encoded_tuple[24..32].copy_from_slice(&height.to_be_bytes());
+    encoded_tuple[32..].copy_from_slice(data_hash.as_bytes());
+    encoded_tuple
+}
+
 pub trait DataCommitmentBuilder<L: PlonkParameters<D>, const D: usize> {
     /// Encodes the data hash and height as a tuple with abi.encode(height, data_hash).
     /// Spec: https://github.com/celestiaorg/celestia-core/blob/6933af1ead0ddf4a8c7516690e3674c6cdfa7bd8/rpc/core/blocks.go#L325-L334
@@ -30,6 +45,9 @@ pub trait DataCommitmentBuilder<L: PlonkParameters<D>, const D: usize> {
     /// Note: Data commitment is exclusive of end_block.
     /// Note: end_block should be >= start_block.
     /// MAX_LEAVES is the maximum range of blocks that can be included in the data commitment.
+    /// Note: The data hashes past the range (from index end_block - start_block) must be zero.
+    /// Note: The tree is hashed bottom-up, one layer at a time, so each inner node is hashed once.
+    /// The aunts shared by the merkle paths of several leaves are never recomputed per leaf.
     fn get_data_commitment<const MAX_LEAVES: usize>(
         &mut self,
         data_hashes: &ArrayVariable<Bytes32Variable, MAX_LEAVES>,
@@ -37,8 +55,29 @@ pub trait DataCommitmentBuilder<L: PlonkParameters<D>, const D: usize> {
         end_block: U64Variable,
     ) -> Bytes32Variable;
 
+    /// Aggregate multiple data commitments into a single super-commitment. Computes the root of a
+    /// Tendermint merkle tree whose leaves are the individual range commitments.
+    /// Note: commitments should be non-empty.
+    fn aggregate_data_commitments(&mut self, commitments: &[Bytes32Variable]) -> Bytes32Variable;
+
+    /// Compute a Merkle mountain range (MMR) over the data root tuples of the NB_LEAVES blocks
+    /// starting at start_block, as an alternative to the balanced tree of get_data_commitment.
+    /// The peaks are the roots of the perfect binary trees over the leaves, from largest to
+    /// smallest. The root bags the peaks from right to left: inner_hash(peak_0, inner_hash(peak_1, ...)).
+    /// Leaves and inner nodes are hashed following the Tendermint tree rules.
+    /// Returns the MMR root and the peaks.
+    /// Note: Unlike get_data_commitment, every leaf is included.
+    fn get_data_commitment_mmr<const NB_LEAVES: usize>(
+        &mut self,
+        data_hashes: &ArrayVariable<Bytes32Variable, NB_LEAVES>,
+        start_block: U64Variable,
+    ) -> (Bytes32Variable, Vec<Bytes32Variable>);
+
     /// Verify the chain of headers is linked for the subrange in the data commitment proof & generate the subrange's data_merkle_root.
     /// Verify the header at global_end_block is the global_end_header_hash and don't include blocks after global_end_block in the merkle root computation.
+    /// Verify the header at the height of each of the checkpoints in the subrange is the checkpoint's header hash.
+    /// The headers are linked by their last_block_id alone, independent of their validator sets, so the subrange may span
+    /// validator set changes.
     ///
     /// Specifically, a MapReduce circuit with <NB_MAP_JOBS=4, BATCH_SIZE=4> over blocks [0, 16) will invoke prove_subchain 4 times. Each of the 4 prove_subchain calls
     /// over [0, 4), [4, 8), [8, 12), [12, 16) will 1) prove the subchain of headers are linked and 2) output their corresponding data_merkle_root.
@@ -49,18 +88,23 @@ pub trait DataCommitmentBuilder<L: PlonkParameters<D>, const D: usize> {
         batch_end_block: U64Variable,
         global_end_block: U64Variable,
         global_end_header_hash: Bytes32Variable,
+        chain_config: ChainConfig,
+        checkpoints: &'static [(u64, H256)],
     ) -> MapReduceSubchainVariable;
 
     /// Verify the chain of headers is linked from start_block to end_block, and generate the corresponding data_merkle_root.
     /// NB_MAP_JOBS * BATCH_SIZE is the maximum range of blocks that can be included in the data commitment.
     /// Note: Data commitment is exclusive of end_block.
     /// mapreduce is used to parallelize the verification of the chain of headers, by splitting the range of blocks into NB_MAP_JOBS batches of BATCH_SIZE blocks.
+    /// If the range crosses the height of any of the checkpoints, the chain of headers must pass through the checkpoint's header hash.
     fn prove_data_commitment<C: Circuit, const NB_MAP_JOBS: usize, const BATCH_SIZE: usize>(
         &mut self,
         start_block: U64Variable,
         start_header_hash: Bytes32Variable,
         end_block: U64Variable,
         end_header_hash: Bytes32Variable,
+        chain_config: ChainConfig,
+        checkpoints: &'static [(u64, H256)],
     ) -> Bytes32Variable
     where
         <<L as PlonkParameters<D>>::Config as GenericConfig<D>>::Hasher:
@@ -75,6 +119,7 @@ pub trait DataCommitmentBuilder<L: PlonkParameters<D>, const D: usize> {
         prev_block_number: U64Variable,
         prev_header_hash: Bytes32Variable,
         next_block_number: U64Variable,
+        chain_config: ChainConfig,
     ) -> Bytes32Variable;
 }
 
@@ -128,12 +173,19 @@ impl<L: PlonkParameters<D>, const D: usize> DataCommitmentBuilder<L, D> for Circ
         self.assert_is_equal(nb_blocks_in_batch.limbs[1], zero);
 
         let mut leaves = Vec::new();
+        let zero_hash = self.constant::<Bytes32Variable>(H256::zero());
 
         // Compute the leaves of the merkle tree.
         for i in 0..MAX_LEAVES {
             let curr_idx = self.constant::<U64Variable>(i as u64);
             let block_height = self.add(start_block, curr_idx);
 
+            // Unused slots must hold the zero hash, so they can't carry arbitrary data.
+            let is_leaf_enabled = self.lt(curr_idx, nb_blocks_in_batch);
+            let is_zero_hash = self.is_equal(data_hashes[i], zero_hash);
+            let unused_slot_check = self.or(is_leaf_enabled, is_zero_hash);
+            self.assert_is_equal(unused_slot_check, true_var);
+
             // Each leaf in Blobstream is abi.encodePacked(height, data_hash).
             leaves.push(self.encode_data_root_tuple(&data_hashes[i], &block_height));
         }
@@ -147,6 +199,67 @@ impl<L: PlonkParameters<D>, const D: usize> DataCommitmentBuilder<L, D> for Circ
         )
     }
 
+    fn aggregate_data_commitments(&mut self, commitments: &[Bytes32Variable]) -> Bytes32Variable {
+        assert!(
+            !commitments.is_empty(),
+            "must aggregate at least one data commitment"
+        );
+
+        if commitments.len() == 1 {
+            return self.leaf_hash(&commitments[0].as_bytes());
+        }
+
+        // Following the Tendermint tree rules, the left subtree holds the largest power of two
+        // strictly less than the number of leaves.
+        let split_point = commitments.len().next_power_of_two() / 2;
+        let left = self.aggregate_data_commitments(&commitments[..split_point]);
+        let right = self.aggregate_data_commitments(&commitments[split_point..]);
+        self.inner_hash(&left, &right)
+    }
+
+    fn get_data_commitment_mmr<const NB_LEAVES: usize>(
+        &mut self,
+        data_hashes: &ArrayVariable<Bytes32Variable, NB_LEAVES>,
+        start_block: U64Variable,
+    ) -> (Bytes32Variable, Vec<Bytes32Variable>) {
+        assert!(NB_LEAVES > 0, "the MMR must have at least one leaf");
+
+        let mut leaf_hashes = Vec::new();
+        for i in 0..NB_LEAVES {
+            let curr_idx = self.constant::<U64Variable>(i as u64);
+            let block_height = self.add(start_block, curr_idx);
+            let leaf = self.encode_data_root_tuple(&data_hashes[i], &block_height);
+            leaf_hashes.push(self.leaf_hash(&leaf.0));
+        }
+
+        // Each set bit of NB_LEAVES, from the most significant, is a perfect binary tree of that
+        // size.
+        let mut peaks = Vec::new();
+        let mut offset = 0;
+        for bit in (0..usize::BITS).rev() {
+            let tree_size = 1usize << bit;
+            if NB_LEAVES & tree_size == 0 {
+                continue;
+            }
+            let mut level = leaf_hashes[offset..offset + tree_size].to_vec();
+            while level.len() > 1 {
+                level = level
+                    .chunks(2)
+                    .map(|pair| self.inner_hash(&pair[0], &pair[1]))
+                    .collect();
+            }
+            peaks.push(level[0]);
+            offset += tree_size;
+        }
+
+        // Bag the peaks from right to left.
+        let mut root = peaks[peaks.len() - 1];
+        for peak in peaks[..peaks.len() - 1].iter().rev() {
+            root = self.inner_hash(peak, &root);
+        }
+        (root, peaks)
+    }
+
     fn prove_subchain<const BATCH_SIZE: usize>(
         &mut self,
         data_comm_proof: &DataCommitmentProofVariable<BATCH_SIZE>,
@@ -154,6 +267,8 @@ impl<L: PlonkParameters<D>, const D: usize> DataCommitmentBuilder<L, D> for Circ
         batch_end_block: U64Variable,
         global_end_block: U64Variable,
         global_end_header_hash: Bytes32Variable,
+        chain_config: ChainConfig,
+        checkpoints: &'static [(u64, H256)],
     ) -> MapReduceSubchainVariable {
         let one = self.constant::<U64Variable>(1u64);
         let true_bool = self._true();
@@ -163,10 +278,12 @@ impl<L: PlonkParameters<D>, const D: usize> DataCommitmentBuilder<L, D> for Circ
         let batch_end_header_hash = data_comm_proof.end_header;
 
         // Path of the data_hash and last_block_id against the Tendermint header.
-        let data_hash_path =
-            self.constant::<ArrayVariable<BoolVariable, 4>>(vec![false, true, true, false]);
-        let last_block_id_path =
-            self.constant::<ArrayVariable<BoolVariable, 4>>(vec![false, false, true, false]);
+        let data_hash_path = self.constant::<ArrayVariable<BoolVariable, HEADER_PROOF_DEPTH>>(
+            chain_config.data_hash_path(),
+        );
+        let last_block_id_path = self.constant::<ArrayVariable<BoolVariable, HEADER_PROOF_DEPTH>>(
+            chain_config.last_block_id_path(),
+        );
 
         // If batch_start_block < global_end_block, this batch has headers that need to be verified.
         // If is_batch_enabled is false, in the reduce stage the batch will be considered empty, and
@@ -185,6 +302,19 @@ impl<L: PlonkParameters<D>, const D: usize> DataCommitmentBuilder<L, D> for Circ
             let is_last_block = self.is_equal(last_block_to_process, curr_idx);
             let is_not_last_block = self.not(is_last_block);
 
+            // If block curr_idx is a checkpoint, curr_header (the header hash of block curr_idx) must be the checkpoint's header hash.
+            for (checkpoint_block, checkpoint_header_hash) in checkpoints.iter() {
+                let checkpoint_block = self.constant::<U64Variable>(*checkpoint_block);
+                let checkpoint_header_hash =
+                    self.constant::<Bytes32Variable>(*checkpoint_header_hash);
+                let is_checkpoint = self.is_equal(curr_idx, checkpoint_block);
+                let is_enabled_checkpoint = self.and(curr_block_enabled, is_checkpoint);
+                let is_not_enabled_checkpoint = self.not(is_enabled_checkpoint);
+                let matches_checkpoint = self.is_equal(curr_header, checkpoint_header_hash);
+                let checkpoint_check = self.or(is_not_enabled_checkpoint, matches_checkpoint);
+                self.assert_is_equal(checkpoint_check, true_bool);
+            }
+
             // The computed root of data_hash_proofs[i] should be the hash of block curr_idx.
             let data_hash_proof_root = self
                 .get_root_from_merkle_proof::<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES>(
@@ -206,6 +336,13 @@ impl<L: PlonkParameters<D>, const D: usize> DataCommitmentBuilder<L, D> for Circ
             let prev_header_check = self.or(curr_block_disabled, is_valid_prev_header);
             self.assert_is_equal(prev_header_check, true_bool);
 
+            // Block curr_idx must have at least one part.
+            let is_part_set_header_valid = self.is_part_set_header_total_nonzero(
+                &data_comm_proof.last_block_id_proofs[i].leaf[..],
+            );
+            let part_set_header_check = self.or(curr_block_disabled, is_part_set_header_valid);
+            self.assert_is_equal(part_set_header_check, true_bool);
+
             // Verify the data hash proof is valid against block curr_idx.
             let is_data_hash_proof_valid = self.is_equal(data_hash_proof_root, header_hash.into());
             let data_hash_check = self.or(curr_block_disabled, is_data_hash_proof_valid);
@@ -242,12 +379,22 @@ impl<L: PlonkParameters<D>, const D: usize> DataCommitmentBuilder<L, D> for Circ
         let end_block_num =
             self.select(is_end_block_lt_start, batch_start_block, temp_end_block_num);
 
+        // The hint fetches the data hashes of the whole batch, so zero the data hashes of the
+        // blocks past end_block_num, which are not part of the data commitment.
+        let zero_hash = self.constant::<Bytes32Variable>(H256::zero());
         let data_hashes = ArrayVariable::<Bytes32Variable, BATCH_SIZE>::from(
             data_comm_proof
                 .data_hash_proofs
                 .data
                 .iter()
-                .map(|proof| Bytes32Variable::from(&proof.leaf[2..2 + HASH_SIZE]))
+                .enumerate()
+                .map(|(i, proof)| {
+                    let loop_idx = self.constant::<U64Variable>(i as u64);
+                    let curr_idx = self.add(batch_start_block, loop_idx);
+                    let is_in_range = self.lt(curr_idx, end_block_num);
+                    let data_hash = Bytes32Variable::from(&proof.leaf[2..2 + HASH_SIZE]);
+                    self.select(is_in_range, data_hash, zero_hash)
+                })
                 .collect::<Vec<_>>(),
         );
 
@@ -276,6 +423,8 @@ impl<L: PlonkParameters<D>, const D: usize> DataCommitmentBuilder<L, D> for Circ
         start_header_hash: Bytes32Variable,
         end_block: U64Variable,
         end_header_hash: Bytes32Variable,
+        chain_config: ChainConfig,
+        checkpoints: &'static [(u64, H256)],
     ) -> Bytes32Variable
     where
         <<L as PlonkParameters<D>>::Config as GenericConfig<D>>::Hasher:
@@ -325,14 +474,14 @@ impl<L: PlonkParameters<D>, const D: usize> DataCommitmentBuilder<L, D> for Circ
                     let mut input_stream = VariableStream::new();
                     input_stream.write(&batch_start_block);
                     input_stream.write(&batch_end_block);
-                    let data_comm_fetcher = DataCommitmentOffchainInputs::<BATCH_SIZE> {};
+                    let data_comm_fetcher = DataCommitmentOffchainInputs::<BATCH_SIZE> { chain_config };
                     let output_stream = builder
                         .async_hint(input_stream, data_comm_fetcher);
                     let data_comm_proof = output_stream
                         .read::<DataCommitmentProofVariable<BATCH_SIZE>>(builder);
 
                     // Verify the chain of headers is linked for the batch & compute the corresponding data_merkle_root.
-                    builder.prove_subchain(&data_comm_proof, batch_start_block, batch_end_block, global_end_block, global_end_header_hash)
+                    builder.prove_subchain(&data_comm_proof, batch_start_block, batch_end_block, global_end_block, global_end_header_hash, chain_config, checkpoints)
                 },
                 |_, left_subchain, right_subchain, builder| {
                     // The following logic handles the reduce stage of the mapreduce.
@@ -405,6 +554,17 @@ impl<L: PlonkParameters<D>, const D: usize> DataCommitmentBuilder<L, D> for Circ
         self.assert_is_equal(result.end_block, end_block);
         self.assert_is_equal(result.end_header, end_header_hash);
 
+        // The subchains check the checkpoints in [start_block, end_block), so check the end block.
+        for (checkpoint_block, checkpoint_header_hash) in checkpoints.iter() {
+            let checkpoint_block = self.constant::<U64Variable>(*checkpoint_block);
+            let checkpoint_header_hash = self.constant::<Bytes32Variable>(*checkpoint_header_hash);
+            let is_checkpoint = self.is_equal(end_block, checkpoint_block);
+            let is_not_checkpoint = self.not(is_checkpoint);
+            let matches_checkpoint = self.is_equal(end_header_hash, checkpoint_header_hash);
+            let checkpoint_check = self.or(is_not_checkpoint, matches_checkpoint);
+            self.assert_is_equal(checkpoint_check, true_v);
+        }
+
         result.data_merkle_root
     }
 
@@ -413,18 +573,20 @@ impl<L: PlonkParameters<D>, const D: usize> DataCommitmentBuilder<L, D> for Circ
         prev_block_number: U64Variable,
         prev_header_hash: Bytes32Variable,
         next_block_number: U64Variable,
+        chain_config: ChainConfig,
     ) -> Bytes32Variable {
         // Compute data commitment (always for 1 leaf).
         let mut input_stream = VariableStream::new();
         input_stream.write(&prev_block_number);
         input_stream.write(&next_block_number);
-        let data_comm_fetcher = DataCommitmentOffchainInputs::<1> {};
+        let data_comm_fetcher = DataCommitmentOffchainInputs::<1> { chain_config };
         let output_stream = self.async_hint(input_stream, data_comm_fetcher);
         let data_comm_proof = output_stream.read::<DataCommitmentProofVariable<1>>(self);
 
         // Path of the data_hash against the Tendermint header.
-        let data_hash_path =
-            self.constant::<ArrayVariable<BoolVariable, 4>>(vec![false, true, true, false]);
+        let data_hash_path = self.constant::<ArrayVariable<BoolVariable, HEADER_PROOF_DEPTH>>(
+            chain_config.data_hash_path(),
+        );
         // Confirm the data_comm_proof corresponds to the prev_header_hash.
         let data_hash_proof_root = self
             .get_root_from_merkle_proof::<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES>(
@@ -447,11 +609,20 @@ impl<L: PlonkParameters<D>, const D: usize> DataCommitmentBuilder<L, D> for Circ
 // Alternatively, add env::set_var("RUST_LOG", "debug") to the top of the test.
 #[cfg(test)]
 pub(crate) mod tests {
-    use ethers::types::H256;
+    use std::time::{Duration, Instant};
+
+    use rand::Rng;
+    use sha2::Digest;
+    use tendermint::block::signed_header::SignedHeader;
+    use tendermint::crypto::default::Sha256;
+    use tendermint::merkle::simple_hash_from_byte_vectors;
     use tendermintx::input::InputDataFetcher;
     use tokio::runtime::Runtime;
 
     use super::*;
+    use crate::accumulator::DataCommitmentAccumulator;
+    use crate::fixture::get_signed_block;
+    use crate::fixture::tests::{get_signed_blocks_with_validator_rotation, FIXTURE_PATH};
     use crate::input::DataCommitmentInputFetcher;
     use crate::vars::*;
 
@@ -470,7 +641,11 @@ pub(crate) mod tests {
 
         let result = rt.block_on(async {
             input_data_fetcher
-                .get_data_commitment_inputs::<MAX_LEAVES, F>(start_height as u64, end_height as u64)
+                .get_data_commitment_inputs::<MAX_LEAVES, F>(
+                    start_height as u64,
+                    end_height as u64,
+                    ChainConfig::default(),
+                )
                 .await
         });
 
@@ -528,6 +703,116 @@ pub(crate) mod tests {
         circuit.verify(&proof, &input, &output);
     }
 
+    fn test_get_data_commitment_unused_slots_template(unused_slot_data_hash: H256) {
+        env_logger::try_init().unwrap_or_default();
+
+        const MAX_LEAVES: usize = 4;
+        const NUM_BLOCKS: usize = 2;
+        const START_BLOCK: u64 = 10000;
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let data_hashes = builder.read::<ArrayVariable<Bytes32Variable, MAX_LEAVES>>();
+        let start_block = builder.constant::<U64Variable>(START_BLOCK);
+        let end_block = builder.constant::<U64Variable>(START_BLOCK + NUM_BLOCKS as u64);
+        let data_commitment =
+            builder.get_data_commitment::<MAX_LEAVES>(&data_hashes, start_block, end_block);
+        builder.write::<Bytes32Variable>(data_commitment);
+        let circuit = builder.build();
+
+        let mut rng = rand::thread_rng();
+        let mut data_hash_values = (0..NUM_BLOCKS).map(|_| H256(rng.gen())).collect::<Vec<_>>();
+        data_hash_values.push(H256::zero());
+        data_hash_values.push(unused_slot_data_hash);
+
+        let mut input = circuit.input();
+        input.write::<ArrayVariable<Bytes32Variable, MAX_LEAVES>>(data_hash_values.clone());
+        let (proof, mut output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+
+        let leaves = data_hash_values[..NUM_BLOCKS]
+            .iter()
+            .enumerate()
+            .map(|(i, data_hash)| {
+                encode_data_root_tuple_native(*data_hash, START_BLOCK + i as u64).to_vec()
+            })
+            .collect::<Vec<_>>();
+        let expected_data_commitment = simple_hash_from_byte_vectors::<Sha256>(&leaves);
+        assert_eq!(
+            output.read::<Bytes32Variable>(),
+            H256(expected_data_commitment)
+        );
+    }
+
+    #[test]
+    fn test_get_data_commitment_unused_slots() {
+        test_get_data_commitment_unused_slots_template(H256::zero());
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_get_data_commitment_unused_slot_garbage() {
+        test_get_data_commitment_unused_slots_template(H256::from_low_u64_be(0xdead));
+    }
+
+    /// Compute the root of a Tendermint merkle tree from the leaf at index, recomputing each aunt
+    /// on its merkle path from the leaves, as if each leaf were proven on its own.
+    fn naive_root_from_leaf(leaves: &[Vec<u8>], index: usize) -> [u8; 32] {
+        if leaves.len() == 1 {
+            return sha2::Sha256::digest([&[MERKLE_LEAF_PREFIX], leaves[0].as_slice()].concat())
+                .into();
+        }
+        let split = leaves.len().next_power_of_two() / 2;
+        let (left, right) = if index < split {
+            (
+                naive_root_from_leaf(&leaves[..split], index),
+                simple_hash_from_byte_vectors::<Sha256>(&leaves[split..]),
+            )
+        } else {
+            (
+                simple_hash_from_byte_vectors::<Sha256>(&leaves[..split]),
+                naive_root_from_leaf(&leaves[split..], index - split),
+            )
+        };
+        sha2::Sha256::digest([&[MERKLE_INNER_PREFIX], &left[..], &right[..]].concat()).into()
+    }
+
+    #[test]
+    fn test_get_data_commitment_matches_naive_paths() {
+        env_logger::try_init().unwrap_or_default();
+
+        const MAX_LEAVES: usize = 16;
+        const START_BLOCK: u64 = 10000;
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let data_hashes = builder.read::<ArrayVariable<Bytes32Variable, MAX_LEAVES>>();
+        let start_block = builder.constant::<U64Variable>(START_BLOCK);
+        let end_block = builder.constant::<U64Variable>(START_BLOCK + MAX_LEAVES as u64);
+        let data_commitment =
+            builder.get_data_commitment::<MAX_LEAVES>(&data_hashes, start_block, end_block);
+        builder.write::<Bytes32Variable>(data_commitment);
+        let circuit = builder.build();
+
+        let mut rng = rand::thread_rng();
+        let data_hash_values = (0..MAX_LEAVES).map(|_| H256(rng.gen())).collect::<Vec<_>>();
+        let mut input = circuit.input();
+        input.write::<ArrayVariable<Bytes32Variable, MAX_LEAVES>>(data_hash_values.clone());
+        let (proof, mut output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+        let data_commitment = output.read::<Bytes32Variable>();
+
+        // The root from every leaf's own merkle path is the commitment computed over the window.
+        let leaves = data_hash_values
+            .iter()
+            .enumerate()
+            .map(|(i, data_hash)| {
+                encode_data_root_tuple_native(*data_hash, START_BLOCK + i as u64).to_vec()
+            })
+            .collect::<Vec<_>>();
+        for i in 0..MAX_LEAVES {
+            assert_eq!(H256(naive_root_from_leaf(&leaves, i)), data_commitment);
+        }
+    }
+
     #[test]
     #[cfg_attr(feature = "ci", ignore)]
     fn test_prove_header_chain() {
@@ -549,6 +834,8 @@ pub(crate) mod tests {
             end_block,
             end_block,
             data_commitment_var.end_header,
+            ChainConfig::default(),
+            &[],
         );
 
         let circuit = builder.build();
@@ -563,6 +850,254 @@ pub(crate) mod tests {
         circuit.verify(&proof, &input, &output);
     }
 
+    fn test_prove_subchain_checkpoints_template(checkpoints: &'static [(u64, H256)]) {
+        env_logger::try_init().unwrap_or_default();
+
+        const MAX_LEAVES: usize = 4;
+        const START_BLOCK: u64 = 10000;
+        const END_BLOCK: u64 = START_BLOCK + MAX_LEAVES as u64;
+
+        let fetcher = InputDataFetcher::default();
+        let mut accumulator = DataCommitmentAccumulator::<MAX_LEAVES, F>::new(
+            START_BLOCK,
+            END_BLOCK,
+            ChainConfig::default(),
+        );
+        let data_comm_proof = (START_BLOCK..END_BLOCK + 1)
+            .filter_map(|block| {
+                accumulator.ingest(&fetcher, &get_signed_block(FIXTURE_PATH, block))
+            })
+            .next()
+            .unwrap();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let start_block = builder.constant::<U64Variable>(START_BLOCK);
+        let end_block = builder.constant::<U64Variable>(END_BLOCK);
+        let data_comm_proof_var = builder.read::<DataCommitmentProofVariable<MAX_LEAVES>>();
+        builder.prove_subchain::<MAX_LEAVES>(
+            &data_comm_proof_var,
+            start_block,
+            end_block,
+            end_block,
+            data_comm_proof_var.end_header,
+            ChainConfig::default(),
+            checkpoints,
+        );
+        let circuit = builder.build();
+
+        let mut input = circuit.input();
+        input.write::<DataCommitmentProofVariable<MAX_LEAVES>>(data_comm_proof);
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    /// Leak the checkpoints, as the checkpoints of a circuit are constants.
+    fn leak_checkpoints(checkpoints: Vec<(u64, H256)>) -> &'static [(u64, H256)] {
+        Box::leak(checkpoints.into_boxed_slice())
+    }
+
+    #[test]
+    fn test_prove_subchain_checkpoints() {
+        let checkpoint_hash = H256::from_slice(
+            get_signed_block(FIXTURE_PATH, 10002)
+                .header
+                .hash()
+                .as_bytes(),
+        );
+        // A checkpoint on the proven chain, and a checkpoint outside of the range.
+        test_prove_subchain_checkpoints_template(leak_checkpoints(vec![
+            (10002, checkpoint_hash),
+            (20000, H256::from_low_u64_be(1)),
+        ]));
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_prove_subchain_checkpoints_fork() {
+        // The proven chain diverges from the canonical chain, whose block 10002 is the checkpoint.
+        test_prove_subchain_checkpoints_template(leak_checkpoints(vec![(
+            10002,
+            H256::from_low_u64_be(1),
+        )]));
+    }
+
+    fn test_prove_subchain_last_block_id_template(tampered_block: Option<u64>) {
+        env_logger::try_init().unwrap_or_default();
+
+        const MAX_LEAVES: usize = 4;
+        const START_BLOCK: u64 = 10000;
+        const END_BLOCK: u64 = START_BLOCK + MAX_LEAVES as u64;
+
+        // Point the last_block_id of tampered_block at a different header, and relink the headers
+        // after it, so every proof is valid against its header and only the link from
+        // tampered_block to the previous header is broken.
+        let mut signed_headers = (START_BLOCK..END_BLOCK + 1)
+            .map(|block_number| get_signed_block(FIXTURE_PATH, block_number))
+            .collect::<Vec<_>>();
+        if let Some(tampered_block) = tampered_block {
+            let tampered_idx = (tampered_block - START_BLOCK) as usize;
+            for i in tampered_idx..signed_headers.len() {
+                let prev_header_hash = if i == tampered_idx {
+                    tendermint::Hash::Sha256([0xab; 32])
+                } else {
+                    signed_headers[i - 1].header.hash()
+                };
+                let header = &mut signed_headers[i].header;
+                let mut last_block_id = header.last_block_id.unwrap();
+                last_block_id.hash = prev_header_hash;
+                header.last_block_id = Some(last_block_id);
+            }
+        }
+
+        // The accumulator rejects unlinked headers, so the proofs are built directly.
+        let fetcher = InputDataFetcher::default();
+        let header_hash =
+            |signed_header: &SignedHeader| H256::from_slice(signed_header.header.hash().as_bytes());
+        let data_comm_proof = DataCommitmentProofValueType::<MAX_LEAVES, F> {
+            start_header: header_hash(&signed_headers[0]),
+            end_header: header_hash(&signed_headers[MAX_LEAVES]),
+            data_hash_proofs: signed_headers[..MAX_LEAVES]
+                .iter()
+                .map(|signed_header| {
+                    fetcher.get_data_hash_proof(&signed_header.header, ChainConfig::default())
+                })
+                .collect(),
+            last_block_id_proofs: signed_headers[1..]
+                .iter()
+                .map(|signed_header| {
+                    fetcher.get_last_block_id_proof(&signed_header.header, ChainConfig::default())
+                })
+                .collect(),
+        };
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let start_block = builder.constant::<U64Variable>(START_BLOCK);
+        let end_block = builder.constant::<U64Variable>(END_BLOCK);
+        let data_comm_proof_var = builder.read::<DataCommitmentProofVariable<MAX_LEAVES>>();
+        builder.prove_subchain::<MAX_LEAVES>(
+            &data_comm_proof_var,
+            start_block,
+            end_block,
+            end_block,
+            data_comm_proof_var.end_header,
+            ChainConfig::default(),
+            &[],
+        );
+        let circuit = builder.build();
+
+        let mut input = circuit.input();
+        input.write::<DataCommitmentProofVariable<MAX_LEAVES>>(data_comm_proof);
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_prove_subchain_last_block_id() {
+        test_prove_subchain_last_block_id_template(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_prove_subchain_last_block_id_tampered() {
+        test_prove_subchain_last_block_id_template(Some(10002));
+    }
+
+    /// Prove the subchain of signed_headers, which are MAX_LEAVES + 1 consecutive blocks, and
+    /// assert its data merkle root is the root of the blocks' data root tuples.
+    fn assert_prove_subchain_data_root<const MAX_LEAVES: usize>(signed_headers: &[SignedHeader]) {
+        assert_eq!(signed_headers.len(), MAX_LEAVES + 1);
+        let start_block = signed_headers[0].header.height.value();
+        let end_block = start_block + MAX_LEAVES as u64;
+
+        let fetcher = InputDataFetcher::default();
+        let mut accumulator = DataCommitmentAccumulator::<MAX_LEAVES, F>::new(
+            start_block,
+            end_block,
+            ChainConfig::default(),
+        );
+        let data_comm_proof = signed_headers
+            .iter()
+            .filter_map(|signed_header| accumulator.ingest(&fetcher, signed_header))
+            .next()
+            .unwrap();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let start_block_var = builder.constant::<U64Variable>(start_block);
+        let end_block_var = builder.constant::<U64Variable>(end_block);
+        let data_comm_proof_var = builder.read::<DataCommitmentProofVariable<MAX_LEAVES>>();
+        let subchain = builder.prove_subchain::<MAX_LEAVES>(
+            &data_comm_proof_var,
+            start_block_var,
+            end_block_var,
+            end_block_var,
+            data_comm_proof_var.end_header,
+            ChainConfig::default(),
+            &[],
+        );
+        builder.write::<Bytes32Variable>(subchain.data_merkle_root);
+        let circuit = builder.build();
+
+        let mut input = circuit.input();
+        input.write::<DataCommitmentProofVariable<MAX_LEAVES>>(data_comm_proof);
+        let (proof, mut output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+
+        // The data hashes are unaffected by the validator set, so neither is the data commitment.
+        let encoded_tuples = signed_headers[..MAX_LEAVES]
+            .iter()
+            .map(|signed_header| {
+                encode_data_root_tuple_native(
+                    H256::from_slice(signed_header.header.data_hash.unwrap().as_bytes()),
+                    signed_header.header.height.value(),
+                )
+                .to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            output.read::<Bytes32Variable>(),
+            H256(simple_hash_from_byte_vectors::<Sha256>(&encoded_tuples))
+        );
+    }
+
+    #[test]
+    fn test_prove_subchain_validator_rotation() {
+        env_logger::try_init().unwrap_or_default();
+
+        // The validator set rotates in the middle of the range.
+        let signed_headers =
+            get_signed_blocks_with_validator_rotation(FIXTURE_PATH, 10000, 10004, 10002);
+        assert_ne!(
+            signed_headers[0].header.validators_hash,
+            signed_headers[signed_headers.len() - 1]
+                .header
+                .validators_hash
+        );
+        assert_prove_subchain_data_root::<4>(&signed_headers);
+    }
+
+    #[cfg_attr(feature = "ci", ignore)]
+    #[tokio::test]
+    async fn test_prove_subchain_real_validator_set_change() {
+        env_logger::try_init().unwrap_or_default();
+        dotenv::dotenv().ok();
+
+        // Voting powers change with delegations, so the validator set of Celestia mainnet changes
+        // within a few hundred blocks. Prove a range of 4 blocks with the change in the middle.
+        let fetcher = InputDataFetcher::default();
+        let change_block = fetcher
+            .find_validator_set_changes(3000000, 3000500)
+            .await
+            .unwrap()[0];
+        let signed_headers = fetcher
+            .get_signed_header_range(change_block - 2, change_block + 2)
+            .await;
+        assert_ne!(
+            signed_headers[0].header.validators_hash,
+            signed_headers[4].header.validators_hash
+        );
+        assert_prove_subchain_data_root::<4>(&signed_headers);
+    }
+
     #[test]
     fn test_encode_data_root_tuple() {
         env_logger::try_init().unwrap_or_default();
@@ -579,31 +1114,264 @@ pub(crate) mod tests {
         builder.write(data_root_tuple);
         let circuit = builder.build();
 
-        let mut expected_data_tuple_root = Vec::new();
+        let input = circuit.input();
+        let (proof, mut output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+
+        let data_root_tuple_value =
+            output.read::<ArrayVariable<ByteVariable, ENC_DATA_ROOT_TUPLE_SIZE_BYTES>>();
+        assert_eq!(
+            data_root_tuple_value,
+            encode_data_root_tuple_native(H256::from_slice(&[255u8; 32]), 256).to_vec()
+        );
+
+        log::info!("Verified proof");
+    }
+
+    #[test]
+    fn test_encode_data_root_tuple_random() {
+        env_logger::try_init().unwrap_or_default();
 
-        let expected_height = [
-            0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 1, 0,
-        ];
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let data_hash = builder.read::<Bytes32Variable>();
+        let height = builder.read::<U64Variable>();
+        let data_root_tuple = builder.encode_data_root_tuple(&data_hash, &height);
+        builder.write(data_root_tuple);
+        let circuit = builder.build();
 
-        // Compute the expected output for testing
-        let expected_data_root = vec![
-            255u8, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
-            255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
-        ];
+        let mut rng = rand::thread_rng();
+        for _ in 0..8 {
+            let data_hash_value = H256(rng.gen());
+            let height_value = rng.gen::<u64>();
+
+            let mut input = circuit.input();
+            input.write::<Bytes32Variable>(data_hash_value);
+            input.write::<U64Variable>(height_value);
+            let (proof, mut output) = circuit.prove(&input);
+            circuit.verify(&proof, &input, &output);
+
+            let data_root_tuple_value =
+                output.read::<ArrayVariable<ByteVariable, ENC_DATA_ROOT_TUPLE_SIZE_BYTES>>();
+            assert_eq!(
+                data_root_tuple_value,
+                encode_data_root_tuple_native(data_hash_value, height_value).to_vec()
+            );
+        }
+    }
 
-        expected_data_tuple_root.extend_from_slice(&expected_height);
+    #[test]
+    fn test_aggregate_data_commitments() {
+        env_logger::try_init().unwrap_or_default();
 
-        expected_data_tuple_root.extend_from_slice(&expected_data_root);
+        let mut builder = CircuitBuilder::<L, D>::new();
+
+        let commitments = [[1u8; 32], [2u8; 32]];
+
+        let commitment_vars = commitments
+            .iter()
+            .map(|commitment| builder.constant::<Bytes32Variable>(H256(*commitment)))
+            .collect::<Vec<_>>();
+        let aggregate = builder.aggregate_data_commitments(&commitment_vars);
+        builder.write(aggregate);
+        let circuit = builder.build();
 
         let input = circuit.input();
         let (proof, mut output) = circuit.prove(&input);
         circuit.verify(&proof, &input, &output);
 
-        let data_root_tuple_value =
-            output.read::<ArrayVariable<ByteVariable, ENC_DATA_ROOT_TUPLE_SIZE_BYTES>>();
-        assert_eq!(data_root_tuple_value, expected_data_tuple_root);
+        let expected_aggregate = simple_hash_from_byte_vectors::<Sha256>(&commitments);
+        let aggregate_value = output.read::<Bytes32Variable>();
+        assert_eq!(aggregate_value, H256(expected_aggregate));
+    }
+
+    /// Native reference MMR over the data root tuples of data_hashes starting at start_block.
+    /// Returns the root and the peaks, built by appending leaves one at a time.
+    fn get_data_commitment_mmr_native(data_hashes: &[H256], start_block: u64) -> (H256, Vec<H256>) {
+        use sha2::Digest;
+
+        let hash = |prefix: u8, data: &[u8]| -> H256 {
+            let mut hasher = sha2::Sha256::new();
+            hasher.update([prefix]);
+            hasher.update(data);
+            H256::from_slice(&hasher.finalize())
+        };
+
+        // Each peak is stored with its height. Appending a leaf merges equal-height peaks.
+        let mut peaks: Vec<(H256, u32)> = Vec::new();
+        for (i, data_hash) in data_hashes.iter().enumerate() {
+            let leaf = encode_data_root_tuple_native(*data_hash, start_block + i as u64);
+            let mut node = (hash(0, &leaf), 0);
+            while let Some(&(left, height)) = peaks.last() {
+                if height != node.1 {
+                    break;
+                }
+                peaks.pop();
+                node = (
+                    hash(1, &[left.as_bytes(), node.0.as_bytes()].concat()),
+                    height + 1,
+                );
+            }
+            peaks.push(node);
+        }
+
+        let peaks = peaks.into_iter().map(|(peak, _)| peak).collect::<Vec<_>>();
+        let mut root = peaks[peaks.len() - 1];
+        for peak in peaks[..peaks.len() - 1].iter().rev() {
+            root = hash(1, &[peak.as_bytes(), root.as_bytes()].concat());
+        }
+        (root, peaks)
+    }
+
+    #[test]
+    fn test_get_data_commitment_mmr() {
+        env_logger::try_init().unwrap_or_default();
+
+        const NB_LEAVES: usize = 10;
+        const START_BLOCK: u64 = 10000;
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let data_hashes = builder.read::<ArrayVariable<Bytes32Variable, NB_LEAVES>>();
+        let start_block = builder.constant::<U64Variable>(START_BLOCK);
+        let (root, peaks) = builder.get_data_commitment_mmr::<NB_LEAVES>(&data_hashes, start_block);
+        builder.write::<Bytes32Variable>(root);
+        for peak in peaks.iter() {
+            builder.write::<Bytes32Variable>(*peak);
+        }
+        let circuit = builder.build();
+
+        let mut rng = rand::thread_rng();
+        let data_hash_values = (0..NB_LEAVES).map(|_| H256(rng.gen())).collect::<Vec<_>>();
+
+        let mut input = circuit.input();
+        input.write::<ArrayVariable<Bytes32Variable, NB_LEAVES>>(data_hash_values.clone());
+        let (proof, mut output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+
+        let (expected_root, expected_peaks) =
+            get_data_commitment_mmr_native(&data_hash_values, START_BLOCK);
+        // 10 leaves form a peak over 8 leaves and a peak over 2 leaves.
+        assert_eq!(expected_peaks.len(), 2);
+        assert_eq!(output.read::<Bytes32Variable>(), expected_root);
+        for expected_peak in expected_peaks {
+            assert_eq!(output.read::<Bytes32Variable>(), expected_peak);
+        }
+    }
+
+    /// Compute the data commitment over all MAX_LEAVES leaves with plonky2's in-circuit SHA256,
+    /// following the same Tendermint tree rules as get_data_commitment.
+    fn get_data_commitment_plonky2_sha<const MAX_LEAVES: usize>(
+        builder: &mut CircuitBuilder<L, D>,
+        data_hashes: &ArrayVariable<Bytes32Variable, MAX_LEAVES>,
+        start_block: U64Variable,
+    ) -> Bytes32Variable {
+        fn compute_root(
+            builder: &mut CircuitBuilder<L, D>,
+            leaf_hashes: &[Bytes32Variable],
+        ) -> Bytes32Variable {
+            if leaf_hashes.len() == 1 {
+                return leaf_hashes[0];
+            }
+            let split_point = leaf_hashes.len().next_power_of_two() / 2;
+            let left = compute_root(builder, &leaf_hashes[..split_point]);
+            let right = compute_root(builder, &leaf_hashes[split_point..]);
+            let mut preimage = vec![builder.constant::<ByteVariable>(1u8)];
+            preimage.extend(left.as_bytes());
+            preimage.extend(right.as_bytes());
+            builder.sha256(&preimage)
+        }
+
+        let leaf_hashes = (0..MAX_LEAVES)
+            .map(|i| {
+                let curr_idx = builder.constant::<U64Variable>(i as u64);
+                let block_height = builder.add(start_block, curr_idx);
+                let leaf = builder.encode_data_root_tuple(&data_hashes[i], &block_height);
+                let mut preimage = vec![builder.constant::<ByteVariable>(0u8)];
+                preimage.extend(leaf.0);
+                builder.sha256(&preimage)
+            })
+            .collect::<Vec<_>>();
+        compute_root(builder, &leaf_hashes)
+    }
+
+    /// Build and prove a data commitment over a full window of WINDOW blocks with either SHA256
+    /// backend. Returns the number of rows in the circuit and the proving time.
+    fn bench_data_commitment_template<const WINDOW: usize>(use_curta: bool) -> (usize, Duration) {
+        const START_BLOCK: u64 = 10000;
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let data_hashes = builder.read::<ArrayVariable<Bytes32Variable, WINDOW>>();
+        let start_block = builder.constant::<U64Variable>(START_BLOCK);
+        let end_block = builder.constant::<U64Variable>(START_BLOCK + WINDOW as u64);
+        let data_commitment = if use_curta {
+            builder.get_data_commitment::<WINDOW>(&data_hashes, start_block, end_block)
+        } else {
+            get_data_commitment_plonky2_sha::<WINDOW>(&mut builder, &data_hashes, start_block)
+        };
+        builder.write::<Bytes32Variable>(data_commitment);
+        let circuit = builder.build();
+
+        let mut input = circuit.input();
+        input.write::<ArrayVariable<Bytes32Variable, WINDOW>>(
+            (0..WINDOW)
+                .map(|i| H256::from_low_u64_be(i as u64 + 1))
+                .collect::<Vec<_>>(),
+        );
 
-        println!("Verified proof");
+        let start = Instant::now();
+        let (proof, mut output) = circuit.prove(&input);
+        let elapsed = start.elapsed();
+        circuit.verify(&proof, &input, &output);
+
+        // Both backends must commit to the same data root tuples.
+        let expected = simple_hash_from_byte_vectors::<Sha256>(
+            &(0..WINDOW)
+                .map(|i| {
+                    encode_data_root_tuple_native(
+                        H256::from_low_u64_be(i as u64 + 1),
+                        START_BLOCK + i as u64,
+                    )
+                    .to_vec()
+                })
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(output.read::<Bytes32Variable>(), H256(expected));
+
+        (circuit.data.common.degree(), elapsed)
+    }
+
+    fn bench_data_commitment_window<const WINDOW: usize>() -> Vec<String> {
+        [("plonky2", false), ("curta", true)]
+            .into_iter()
+            .map(|(backend, use_curta)| {
+                let (nb_rows, elapsed) = bench_data_commitment_template::<WINDOW>(use_curta);
+                format!(
+                    "| {:>6} | {:>7} | {:>9} | {:>10.2} |",
+                    WINDOW,
+                    backend,
+                    nb_rows,
+                    elapsed.as_secs_f64()
+                )
+            })
+            .collect()
+    }
+
+    // Compare plonky2 SHA256 and Curta SHA256 for get_data_commitment. This is slow, so run it
+    // explicitly with `RUST_LOG=info cargo test --release bench_data_commitment_sha_backends --
+    // --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_data_commitment_sha_backends() {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut rows = Vec::new();
+        rows.extend(bench_data_commitment_window::<100>());
+        rows.extend(bench_data_commitment_window::<400>());
+        rows.extend(bench_data_commitment_window::<1000>());
+
+        log::info!("| window | backend | nb_rows   | prove_secs |");
+        log::info!("|--------|---------|-----------|------------|");
+        for row in rows {
+            log::info!("{}", row);
+        }
     }
 }
@@ -1,8 +1,291 @@
+use std::fmt;
+
+use ethers::types::H256;
+use serde::{Deserialize, Serialize};
 use tendermintx::config::{
-    TendermintConfig, CELESTIA_CHAIN_ID_BYTES, CELESTIA_CHAIN_ID_SIZE_BYTES,
-    MOCHA_4_CHAIN_ID_BYTES, MOCHA_4_CHAIN_ID_SIZE_BYTES,
+    CelestiaConfig, Mocha4Config, TendermintConfig, CELESTIA_CHAIN_ID_BYTES,
+    CELESTIA_CHAIN_ID_SIZE_BYTES, MOCHA_4_CHAIN_ID_BYTES, MOCHA_4_CHAIN_ID_SIZE_BYTES,
 };
 
+use crate::consts::*;
+
+/// Layout of the fields in the merkle tree of a chain's header. Celestia uses the same 14-field
+/// header ordering as Tendermint, but forks with a different header ordering can override the
+/// leaf indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainConfig {
+    pub nb_header_fields: usize,
+    pub version_index: usize,
+    pub chain_id_index: usize,
+    pub block_height_index: usize,
+    pub time_index: usize,
+    pub last_block_id_index: usize,
+    pub last_commit_hash_index: usize,
+    pub data_hash_index: usize,
+    pub next_validators_hash_index: usize,
+    pub proposer_address_index: usize,
+}
+
+/// A field of the header proven against the header hash by the circuits of this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeafName {
+    Version,
+    LastBlockId,
+    DataHash,
+    ValidatorsHash,
+    NextValidatorsHash,
+}
+
+/// A header layout the circuits can't prove against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainConfigError {
+    /// A leaf index is not a leaf of a header of nb_header_fields fields.
+    IndexOutOfRange {
+        index: usize,
+        nb_header_fields: usize,
+    },
+    /// A leaf the circuits prove has a path of another depth than HEADER_PROOF_DEPTH, which is
+    /// the depth of the circuits' header proofs.
+    InvalidProofDepth { leaf: LeafName, depth: usize },
+}
+
+impl fmt::Display for ChainConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainConfigError::IndexOutOfRange {
+                index,
+                nb_header_fields,
+            } => write!(
+                f,
+                "leaf index {} is out of range of a header of {} fields",
+                index, nb_header_fields
+            ),
+            ChainConfigError::InvalidProofDepth { leaf, depth } => write!(
+                f,
+                "{:?} has a proof of depth {}, expected {}",
+                leaf, depth, HEADER_PROOF_DEPTH
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChainConfigError {}
+
+impl ChainConfig {
+    /// Celestia's header layout.
+    pub const CELESTIA: ChainConfig = ChainConfig {
+        nb_header_fields: HEADER_NB_FIELDS,
+        version_index: VERSION_INDEX,
+        chain_id_index: CHAIN_ID_INDEX,
+        block_height_index: BLOCK_HEIGHT_INDEX,
+        time_index: TIME_INDEX,
+        last_block_id_index: LAST_BLOCK_ID_INDEX,
+        last_commit_hash_index: LAST_COMMIT_HASH_INDEX,
+        data_hash_index: DATA_HASH_INDEX,
+        next_validators_hash_index: NEXT_VALIDATORS_HASH_INDEX,
+        proposer_address_index: PROPOSER_ADDRESS_INDEX,
+    };
+
+    /// Check the circuits can prove against the layout, and return it. Every index must be a leaf
+    /// of the header, and the leaves in proven_header_leaves must have proofs of depth
+    /// HEADER_PROOF_DEPTH. Construct configs other than CELESTIA with
+    /// `ChainConfig { .. }.checked()?`, as the paths of an unchecked config may panic.
+    pub fn checked(self) -> Result<Self, ChainConfigError> {
+        let indices = [
+            self.version_index,
+            self.chain_id_index,
+            self.block_height_index,
+            self.time_index,
+            self.last_block_id_index,
+            self.last_commit_hash_index,
+            self.data_hash_index,
+            self.next_validators_hash_index,
+            self.proposer_address_index,
+        ];
+        if let Some(index) = indices
+            .into_iter()
+            .find(|index| *index >= self.nb_header_fields)
+        {
+            return Err(ChainConfigError::IndexOutOfRange {
+                index,
+                nb_header_fields: self.nb_header_fields,
+            });
+        }
+        if let Some((leaf, _, path)) = self
+            .proven_header_leaves()
+            .into_iter()
+            .find(|(_, _, path)| path.len() != HEADER_PROOF_DEPTH)
+        {
+            return Err(ChainConfigError::InvalidProofDepth {
+                leaf,
+                depth: path.len(),
+            });
+        }
+        Ok(self)
+    }
+
+    /// Path of the data_hash against the header.
+    pub fn data_hash_path(&self) -> Vec<bool> {
+        get_path_indices(self.data_hash_index, self.nb_header_fields)
+    }
+
+    /// Path of the last_block_id against the header.
+    pub fn last_block_id_path(&self) -> Vec<bool> {
+        get_path_indices(self.last_block_id_index, self.nb_header_fields)
+    }
+
+    /// Path of the last_commit_hash against the header.
+    pub fn last_commit_hash_path(&self) -> Vec<bool> {
+        get_path_indices(self.last_commit_hash_index, self.nb_header_fields)
+    }
+
+    /// Path of the version against the header.
+    pub fn version_path(&self) -> Vec<bool> {
+        get_path_indices(self.version_index, self.nb_header_fields)
+    }
+
+    /// Path of the chain_id against the header.
+    pub fn chain_id_path(&self) -> Vec<bool> {
+        get_path_indices(self.chain_id_index, self.nb_header_fields)
+    }
+
+    /// Path of the block height against the header.
+    pub fn block_height_path(&self) -> Vec<bool> {
+        get_path_indices(self.block_height_index, self.nb_header_fields)
+    }
+
+    /// Path of the block time against the header.
+    pub fn time_path(&self) -> Vec<bool> {
+        get_path_indices(self.time_index, self.nb_header_fields)
+    }
+
+    /// Path of the next_validators_hash against the header.
+    pub fn next_validators_hash_path(&self) -> Vec<bool> {
+        get_path_indices(self.next_validators_hash_index, self.nb_header_fields)
+    }
+
+    /// Path of the proposer_address against the header.
+    pub fn proposer_address_path(&self) -> Vec<bool> {
+        get_path_indices(self.proposer_address_index, self.nb_header_fields)
+    }
+
+    /// The name, index and path of each header leaf the circuits of this crate prove, in index
+    /// order, with the paths the circuits use:
+    /// - Version: the skip circuit, if BlockVersionConfig enables a version check.
+    /// - LastBlockId: the skip circuit's data commitment.
+    /// - DataHash: the step and skip circuits' data commitments.
+    /// - ValidatorsHash: the equivocation and validator signed circuits. They use the Tendermint
+    ///   header layout, so its index and path don't depend on the config.
+    /// - NextValidatorsHash: the skip circuit.
+    ///
+    /// The leaves the tendermintx step and skip circuits prove themselves, and those only proven by
+    /// gadgets no circuit uses, aren't listed.
+    pub fn proven_header_leaves(&self) -> Vec<(LeafName, usize, Vec<bool>)> {
+        let mut leaves = vec![
+            (LeafName::Version, self.version_index, self.version_path()),
+            (
+                LeafName::LastBlockId,
+                self.last_block_id_index,
+                self.last_block_id_path(),
+            ),
+            (
+                LeafName::DataHash,
+                self.data_hash_index,
+                self.data_hash_path(),
+            ),
+            (
+                LeafName::ValidatorsHash,
+                VALIDATORS_HASH_INDEX,
+                validators_hash_path(),
+            ),
+            (
+                LeafName::NextValidatorsHash,
+                self.next_validators_hash_index,
+                self.next_validators_hash_path(),
+            ),
+        ];
+        leaves.sort_by_key(|(_, index, _)| *index);
+        leaves
+    }
+}
+
+impl Default for ChainConfig {
+    fn default() -> Self {
+        ChainConfig::CELESTIA
+    }
+}
+
+/// Path of the validators_hash against the header, in the Tendermint header layout the
+/// tendermintx circuits use.
+pub fn validators_hash_path() -> Vec<bool> {
+    get_path_indices(VALIDATORS_HASH_INDEX, HEADER_NB_FIELDS)
+}
+
+/// Get the path of the leaf at index in a Tendermint merkle tree with nb_leaves leaves. The path
+/// is ordered from the leaf to the root, where true indicates the node is the right child.
+pub fn get_path_indices(index: usize, nb_leaves: usize) -> Vec<bool> {
+    assert!(index < nb_leaves);
+    if nb_leaves == 1 {
+        return Vec::new();
+    }
+    // The left subtree holds the largest power of two strictly less than nb_leaves.
+    let split_point = nb_leaves.next_power_of_two() / 2;
+    let mut path = if index < split_point {
+        get_path_indices(index, split_point)
+    } else {
+        get_path_indices(index - split_point, nb_leaves - split_point)
+    };
+    path.push(index >= split_point);
+    path
+}
+
+/// The header layout of the chain a config targets. Defaults to Celestia's header layout. The
+/// step and skip circuits check the layout with ChainConfig::checked when they are defined.
+pub trait HeaderLayoutConfig {
+    const CHAIN_CONFIG: ChainConfig = ChainConfig::CELESTIA;
+}
+
+impl HeaderLayoutConfig for CelestiaConfig {}
+impl HeaderLayoutConfig for Mocha4Config {}
+
+/// Trusted (height, header hash) checkpoints of the chain a config targets. A proven chain of
+/// headers that crosses a checkpoint height must pass through the checkpoint's header hash, so a
+/// long-range fork which diverges before the checkpoint can't be proven. The checkpoints are
+/// constants of the circuit, so changing them changes the circuit.
+pub trait CheckpointConfig {
+    const CHECKPOINTS: &'static [(u64, H256)] = &[];
+}
+
+/// Celestia mainnet has no checkpoints yet.
+impl CheckpointConfig for CelestiaConfig {}
+
+impl CheckpointConfig for Mocha4Config {
+    const CHECKPOINTS: &'static [(u64, H256)] = &[(
+        10000,
+        H256([
+            0xa0, 0x12, 0x3d, 0x5e, 0x4b, 0x8b, 0x88, 0x88, 0xa6, 0x1f, 0x93, 0x1e, 0xe2, 0x25,
+            0x2d, 0x83, 0x56, 0x8b, 0x97, 0xc2, 0x23, 0xe0, 0xec, 0xa9, 0x79, 0x5b, 0x29, 0xb8,
+            0xbd, 0x8c, 0xba, 0x2d,
+        ]),
+    )];
+}
+
+/// Checks a skip makes on the versions of the trusted and target headers.
+pub trait BlockVersionConfig {
+    /// Whether a skip asserts the trusted and target headers have the same block (consensus)
+    /// version, so a skip can't cross a hard fork which changes the consensus rules. The app
+    /// version may change. Off by default.
+    const ASSERT_SAME_BLOCK_VERSION: bool = false;
+
+    /// Whether a skip asserts the target header's app version is at least the trusted header's, as
+    /// a regression signals a fork. Off by default.
+    const ASSERT_APP_VERSION_NOT_REGRESSED: bool = false;
+}
+
+impl BlockVersionConfig for CelestiaConfig {}
+impl BlockVersionConfig for Mocha4Config {}
+
 /// Celestia's BlobstreamX config for max batch size of 1024 blocks.
 #[derive(Debug, Clone, PartialEq)]
 pub struct CelestiaBlobstreamXConfig1024;
@@ -26,3 +309,117 @@ impl TendermintConfig<MOCHA_4_CHAIN_ID_SIZE_BYTES> for Mocha4BlobstreamXConfig10
     const CHAIN_ID_BYTES: &'static [u8] = MOCHA_4_CHAIN_ID_BYTES;
     const SKIP_MAX: usize = 1024;
 }
+
+impl HeaderLayoutConfig for CelestiaBlobstreamXConfig1024 {}
+impl HeaderLayoutConfig for CelestiaBlobstreamXConfig2048 {}
+impl HeaderLayoutConfig for Mocha4BlobstreamXConfig1024 {}
+
+impl CheckpointConfig for CelestiaBlobstreamXConfig1024 {}
+impl CheckpointConfig for CelestiaBlobstreamXConfig2048 {}
+impl CheckpointConfig for Mocha4BlobstreamXConfig1024 {}
+
+impl BlockVersionConfig for CelestiaBlobstreamXConfig1024 {}
+impl BlockVersionConfig for CelestiaBlobstreamXConfig2048 {}
+impl BlockVersionConfig for Mocha4BlobstreamXConfig1024 {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::get_signed_block;
+    use crate::fixture::tests::FIXTURE_PATH;
+
+    #[test]
+    fn test_default_chain_config_paths() {
+        let chain_config = ChainConfig::default();
+        assert_eq!(chain_config, ChainConfig::CELESTIA);
+        assert_eq!(
+            chain_config.data_hash_path(),
+            vec![false, true, true, false]
+        );
+        assert_eq!(
+            chain_config.last_block_id_path(),
+            vec![false, false, true, false]
+        );
+        assert_eq!(
+            chain_config.last_commit_hash_path(),
+            vec![true, false, true, false]
+        );
+        assert_eq!(
+            chain_config.version_path(),
+            vec![false, false, false, false]
+        );
+        assert_eq!(
+            chain_config.chain_id_path(),
+            vec![true, false, false, false]
+        );
+        assert_eq!(
+            chain_config.block_height_path(),
+            vec![false, true, false, false]
+        );
+        assert_eq!(chain_config.time_path(), vec![true, true, false, false]);
+        assert_eq!(
+            chain_config.next_validators_hash_path(),
+            vec![false, false, false, true]
+        );
+        assert_eq!(chain_config.proposer_address_path(), vec![true, true, true]);
+    }
+
+    #[test]
+    fn test_proven_header_leaves() {
+        // The paths of the Tendermint header layout, which the tendermintx step circuit hardcodes.
+        let expected = vec![
+            (LeafName::Version, 0, vec![false, false, false, false]),
+            (LeafName::LastBlockId, 4, vec![false, false, true, false]),
+            (LeafName::DataHash, 6, vec![false, true, true, false]),
+            (LeafName::ValidatorsHash, 7, vec![true, true, true, false]),
+            (
+                LeafName::NextValidatorsHash,
+                8,
+                vec![false, false, false, true],
+            ),
+        ];
+        assert_eq!(ChainConfig::default().proven_header_leaves(), expected);
+    }
+
+    #[test]
+    fn test_checked_chain_config() {
+        assert_eq!(ChainConfig::CELESTIA.checked(), Ok(ChainConfig::CELESTIA));
+
+        // A header of 17 fields is a tree of depth 5 for its first leaves.
+        let deeper = ChainConfig {
+            nb_header_fields: 17,
+            ..ChainConfig::CELESTIA
+        };
+        assert_eq!(
+            deeper.checked(),
+            Err(ChainConfigError::InvalidProofDepth {
+                leaf: LeafName::Version,
+                depth: 5,
+            })
+        );
+
+        // The data_hash is not a leaf of a header of 6 fields.
+        let shorter = ChainConfig {
+            nb_header_fields: 6,
+            ..ChainConfig::CELESTIA
+        };
+        assert_eq!(
+            shorter.checked(),
+            Err(ChainConfigError::IndexOutOfRange {
+                index: DATA_HASH_INDEX,
+                nb_header_fields: 6,
+            })
+        );
+    }
+
+    #[test]
+    fn test_mocha_4_checkpoints() {
+        for (block_number, header_hash) in Mocha4Config::CHECKPOINTS.iter() {
+            let signed_header = get_signed_block(FIXTURE_PATH, *block_number);
+            assert_eq!(
+                signed_header.header.hash().as_bytes(),
+                header_hash.as_bytes()
+            );
+        }
+    }
+}
@@ -6,6 +6,18 @@ pub const PROTOBUF_HASH_SIZE_BYTES: usize = HASH_SIZE + 2;
 /// The number of bits in a protobuf-encoded tendermint block ID.
 pub const PROTOBUF_BLOCK_ID_SIZE_BYTES: usize = 72;
 
+// Offsets into a protobuf-encoded block ID: the 34-byte hash field, then the part set header
+// (field 2), which starts with its total (tag (1 << 3) | 0, varint). The block ID is 72 bytes
+// while the total is nonzero and below 128, so it is encoded in a single varint byte.
+pub const PART_SET_HEADER_TOTAL_TAG_INDEX: usize = 36;
+pub const PART_SET_HEADER_TOTAL_TAG: u8 = 0x08;
+pub const PART_SET_HEADER_TOTAL_INDEX: usize = 37;
+
+// Domain separation prefixes of the Tendermint (RFC 6962) merkle tree. Leaves are hashed as
+// SHA256(0x00 || leaf), inner nodes as SHA256(0x01 || left || right).
+pub const MERKLE_LEAF_PREFIX: u8 = 0x00;
+pub const MERKLE_INNER_PREFIX: u8 = 0x01;
+
 // Depth of the proofs against the header.
 pub const HEADER_PROOF_DEPTH: usize = 4;
 
@@ -14,10 +26,88 @@ pub const HEADER_PROOF_DEPTH: usize = 4;
 pub const VARINT_BYTES_LENGTH_MAX: usize = 9;
 pub const PROTOBUF_VARINT_SIZE_BYTES: usize = VARINT_BYTES_LENGTH_MAX + 1;
 
+// Protobuf tag of the block field of the version leaf in the header: (1 << 3) | 0 (varint wire
+// type). The version leaf is 4 bytes while the block and app versions are nonzero and below 128.
+pub const VERSION_BLOCK_TAG: u8 = 0x08;
+// Protobuf tag of the app field of the version leaf in the header: (2 << 3) | 0 (varint wire type).
+pub const VERSION_APP_TAG: u8 = 0x10;
+// Versions below 128 fit in the 7 value bits of a single varint byte.
+pub const VERSION_NB_BITS: usize = 7;
+pub const PROTOBUF_VERSION_SIZE_BYTES: usize = 4;
+
+// The proposer_address leaf in the header is a protobuf-encoded BytesValue of the 20-byte address.
+// The header has 14 leaves, so the last leaf is at depth 3.
+pub const ADDRESS_SIZE_BYTES: usize = 20;
+pub const PROTOBUF_ADDRESS_SIZE_BYTES: usize = ADDRESS_SIZE_BYTES + 2;
+pub const PROPOSER_ADDRESS_PROOF_DEPTH: usize = 3;
+
+// Protobuf tag of the chain_id leaf in the header: (1 << 3) | 2 (length-delimited wire type).
+pub const CHAIN_ID_TAG: u8 = 0x0a;
+
+// Offsets into the protobuf-encoded Timestamp of the time leaf in the header. The seconds field
+// (tag (1 << 3) | 0) is a varint, which is 5 bytes for any time between 1978 and 3058.
+pub const TIME_SECONDS_TAG: u8 = 0x08;
+pub const TIME_SECONDS_INDEX: usize = 1;
+pub const TIME_SECONDS_VARINT_SIZE_BYTES: usize = 5;
+
 // The number of bytes in an encoded data root tuple.
 pub const ENC_DATA_ROOT_TUPLE_SIZE_BYTES: usize = 64;
 
 // Header indices for the Merkle tree.
+pub const HEADER_NB_FIELDS: usize = 14;
+pub const VERSION_INDEX: usize = 0;
+pub const CHAIN_ID_INDEX: usize = 1;
 pub const BLOCK_HEIGHT_INDEX: usize = 2;
+pub const TIME_INDEX: usize = 3;
 pub const LAST_BLOCK_ID_INDEX: usize = 4;
+pub const LAST_COMMIT_HASH_INDEX: usize = 5;
 pub const DATA_HASH_INDEX: usize = 6;
+// The validators_hash is proven by the tendermintx step and skip circuits, which hardcode the
+// Tendermint header layout.
+pub const VALIDATORS_HASH_INDEX: usize = 7;
+pub const NEXT_VALIDATORS_HASH_INDEX: usize = 8;
+pub const PROPOSER_ADDRESS_INDEX: usize = 13;
+
+// A leaf of the validator set's merkle tree is a protobuf-encoded SimpleValidator: the pubkey
+// (field 1), a PublicKey message holding the 32-byte Ed25519 key (field 1), then the voting power
+// (field 2) as a varint of 1 to 9 bytes, as voting powers are non-negative i64s.
+pub const VALIDATOR_PUBKEY_PREFIX: [u8; 4] = [0x0a, 0x22, 0x0a, 0x20];
+// Protobuf tag of the voting power in a SimpleValidator: (2 << 3) | 0 (varint wire type).
+pub const VALIDATOR_VOTING_POWER_TAG: u8 = 0x10;
+pub const VALIDATOR_VOTING_POWER_MAX_SIZE_BYTES: usize = 9;
+pub const VALIDATOR_MAX_SIZE_BYTES: usize =
+    VALIDATOR_PUBKEY_PREFIX.len() + HASH_SIZE + 1 + VALIDATOR_VOTING_POWER_MAX_SIZE_BYTES;
+
+// The signed message is prefixed with its length as a varint, which is a single byte as signed
+// messages are shorter than 128 bytes.
+pub const VOTE_LENGTH_PREFIX_INDEX: usize = 0;
+pub const VOTE_LENGTH_PREFIX_SIZE_BYTES: usize = 1;
+
+// Offsets into a length-prefixed, protobuf-encoded CanonicalVote (the signed message).
+// The message starts with the length prefix, followed by the vote type (field 1), then the
+// height as an sfixed64 (field 2), encoded little-endian.
+pub const VOTE_TYPE_TAG_INDEX: usize = 1;
+pub const VOTE_TYPE_INDEX: usize = 2;
+// Protobuf tag of the type field in a CanonicalVote: (1 << 3) | 0 (varint wire type).
+pub const VOTE_TYPE_TAG: u8 = 0x08;
+// SignedMsgType of a precommit. A prevote is 0x01.
+pub const VOTE_TYPE_PRECOMMIT: u8 = 0x02;
+pub const VOTE_HEIGHT_TAG_INDEX: usize = 3;
+pub const VOTE_HEIGHT_INDEX: usize = 4;
+pub const VOTE_HEIGHT_SIZE_BYTES: usize = 8;
+// Protobuf tag of the height field in a CanonicalVote: (2 << 3) | 1 (64-bit wire type).
+pub const VOTE_HEIGHT_TAG: u8 = 0x11;
+// The round (field 3) follows the height as an sfixed64, and is omitted when it is 0, in which
+// case the block ID (field 4) follows the height instead.
+pub const VOTE_ROUND_TAG_INDEX: usize = VOTE_HEIGHT_INDEX + VOTE_HEIGHT_SIZE_BYTES;
+// Protobuf tag of the round field in a CanonicalVote: (3 << 3) | 1 (64-bit wire type).
+pub const VOTE_ROUND_TAG: u8 = 0x19;
+pub const VOTE_ROUND_INDEX: usize = VOTE_ROUND_TAG_INDEX + 1;
+pub const VOTE_ROUND_SIZE_BYTES: usize = 8;
+// The block ID starts with its tag and length, followed by the tag and length of its hash, so the
+// block hash starts 4 bytes after the block ID's tag.
+pub const VOTE_BLOCK_HASH_INDEX: usize = VOTE_ROUND_TAG_INDEX + 4;
+pub const VOTE_BLOCK_HASH_INDEX_WITH_ROUND: usize = VOTE_ROUND_INDEX + VOTE_ROUND_SIZE_BYTES + 4;
+// The maximum length of a length-prefixed signed message. Signed messages of Celestia commits are
+// about 110 bytes long, and are padded to this length in the circuits.
+pub const VOTE_MESSAGE_MAX_SIZE_BYTES: usize = 124;
@@ -1,19 +1,27 @@
+use std::fmt;
+
 use async_trait::async_trait;
 use ethers::types::H256;
-use plonky2x::backend::circuit::Circuit;
+use plonky2x::backend::circuit::{Circuit, PublicInput};
 use plonky2x::frontend::hint::asynchronous::hint::AsyncHint;
 use plonky2x::frontend::mapreduce::generator::MapReduceGenerator;
 use plonky2x::frontend::uint::uint64::U64Variable;
-use plonky2x::prelude::{Bytes32Variable, CircuitBuilder, PlonkParameters, ValueStream};
+use plonky2x::prelude::plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
+use plonky2x::prelude::{
+    Bytes32Variable, CircuitBuilder, HintRegistry, PlonkParameters, ValueStream,
+};
 use serde::{Deserialize, Serialize};
 use tendermintx::input::InputDataFetcher;
 
 use crate::builder::{DataCommitmentBuilder, DataCommitmentSharedCtx};
+use crate::config::ChainConfig;
 use crate::input::DataCommitmentInputFetcher;
 use crate::vars::*;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DataCommitmentOffchainInputs<const MAX_LEAVES: usize> {}
+pub struct DataCommitmentOffchainInputs<const MAX_LEAVES: usize> {
+    pub chain_config: ChainConfig,
+}
 
 #[async_trait]
 impl<const MAX_LEAVES: usize, L: PlonkParameters<D>, const D: usize> AsyncHint<L, D>
@@ -30,7 +38,11 @@ impl<const MAX_LEAVES: usize, L: PlonkParameters<D>, const D: usize> AsyncHint<L
         let mut data_fetcher = InputDataFetcher::default();
 
         let result = data_fetcher
-            .get_data_commitment_inputs::<MAX_LEAVES, L::Field>(start_block, end_block)
+            .get_data_commitment_inputs::<MAX_LEAVES, L::Field>(
+                start_block,
+                end_block,
+                self.chain_config,
+            )
             .await;
 
         let data_comm_proof = DataCommitmentProofValueType {
@@ -44,6 +56,115 @@ impl<const MAX_LEAVES: usize, L: PlonkParameters<D>, const D: usize> AsyncHint<L
     }
 }
 
+/// The range of blocks a data commitment proof commits to, and the proof nonce of the contract the
+/// commitment is submitted under. The commitment covers the data roots of the blocks in
+/// [start_height, end_height).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataCommitmentRange {
+    start_height: u64,
+    start_header: H256,
+    end_height: u64,
+    end_header: H256,
+    nonce: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataCommitmentRangeError {
+    /// The end height is not greater than the start height.
+    InvalidRange { start_height: u64, end_height: u64 },
+    /// The start and end headers are the same header, although their heights differ.
+    SameHeader { header: H256 },
+}
+
+impl fmt::Display for DataCommitmentRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataCommitmentRangeError::InvalidRange {
+                start_height,
+                end_height,
+            } => write!(
+                f,
+                "end height {} is not greater than start height {}",
+                end_height, start_height
+            ),
+            DataCommitmentRangeError::SameHeader { header } => write!(
+                f,
+                "start and end header are both {:?} at different heights",
+                header
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DataCommitmentRangeError {}
+
+impl DataCommitmentRange {
+    pub fn new(
+        start_height: u64,
+        start_header: H256,
+        end_height: u64,
+        end_header: H256,
+        nonce: u64,
+    ) -> Result<Self, DataCommitmentRangeError> {
+        if end_height <= start_height {
+            return Err(DataCommitmentRangeError::InvalidRange {
+                start_height,
+                end_height,
+            });
+        }
+        if start_header == end_header {
+            return Err(DataCommitmentRangeError::SameHeader {
+                header: start_header,
+            });
+        }
+        Ok(Self {
+            start_height,
+            start_header,
+            end_height,
+            end_header,
+            nonce,
+        })
+    }
+
+    pub fn start_height(&self) -> u64 {
+        self.start_height
+    }
+
+    pub fn start_header(&self) -> H256 {
+        self.start_header
+    }
+
+    pub fn end_height(&self) -> u64 {
+        self.end_height
+    }
+
+    pub fn end_header(&self) -> H256 {
+        self.end_header
+    }
+
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// Write the range as the input of DataCommitmentCircuit. The nonce is not an input of the
+    /// circuit, it is checked by the contract the proof is submitted to.
+    pub fn write_input<L: PlonkParameters<D>, const D: usize>(
+        &self,
+        input: &mut PublicInput<L, D>,
+    ) {
+        input.evm_write::<U64Variable>(self.start_height);
+        input.evm_write::<Bytes32Variable>(self.start_header);
+        input.evm_write::<U64Variable>(self.end_height);
+        input.evm_write::<Bytes32Variable>(self.end_header);
+    }
+}
+
+/// Proves the data commitment of the blocks in [start_block, end_block), for up to
+/// NB_MAP_JOBS * BATCH_SIZE blocks.
+/// Note: Each of the NB_MAP_JOBS map circuits proves its SHA256 hashes (of BATCH_SIZE headers and
+/// data root tuples) with its own SHA256 gadget, so NB_MAP_JOBS is the number of SHA256 gadgets and
+/// BATCH_SIZE their capacity. For the same window, fewer, larger gadgets mean fewer STARK proofs
+/// but more memory per proof, so pick them based on the prover's hardware.
 #[derive(Debug, Clone)]
 pub struct DataCommitmentCircuit<const NB_MAP_JOBS: usize, const BATCH_SIZE: usize> {
     _config: usize,
@@ -63,37 +184,113 @@ impl<const NB_MAP_JOBS: usize, const BATCH_SIZE: usize> Circuit
             start_header_hash,
             end_block_number,
             end_header_hash,
+            ChainConfig::default(),
+            &[],
         );
 
         builder.evm_write(data_commitment);
     }
 
     fn register_generators<L: PlonkParameters<D>, const D: usize>(
-        generator_registry: &mut plonky2x::prelude::HintRegistry<L, D>,
+        generator_registry: &mut HintRegistry<L, D>,
     ) where
     <<L as PlonkParameters<D>>::Config as plonky2x::prelude::plonky2::plonk::config::GenericConfig<D>>::Hasher:
     plonky2x::prelude::plonky2::plonk::config::AlgebraicHasher<L::Field>,
     {
-        generator_registry.register_async_hint::<DataCommitmentOffchainInputs<BATCH_SIZE>>();
-
-        let mr_id = MapReduceGenerator::<
-            L,
-            DataCommitmentSharedCtx,
-            U64Variable,
-            MapReduceSubchainVariable,
-            Self,
-            BATCH_SIZE,
-            D,
-        >::id();
-        generator_registry.register_simple::<MapReduceGenerator<
-            L,
-            DataCommitmentSharedCtx,
-            U64Variable,
-            MapReduceSubchainVariable,
-            Self,
-            BATCH_SIZE,
-            D,
-        >>(mr_id);
+        register_data_commitment_generators::<Self, L, D, BATCH_SIZE>(generator_registry);
+    }
+}
+
+/// Register the hints of a circuit C proving a data commitment with prove_data_commitment.
+fn register_data_commitment_generators<
+    C: Circuit,
+    L: PlonkParameters<D>,
+    const D: usize,
+    const BATCH_SIZE: usize,
+>(
+    generator_registry: &mut HintRegistry<L, D>,
+) where
+    <<L as PlonkParameters<D>>::Config as GenericConfig<D>>::Hasher: AlgebraicHasher<L::Field>,
+{
+    generator_registry.register_async_hint::<DataCommitmentOffchainInputs<BATCH_SIZE>>();
+
+    let mr_id = MapReduceGenerator::<
+        L,
+        DataCommitmentSharedCtx,
+        U64Variable,
+        MapReduceSubchainVariable,
+        C,
+        BATCH_SIZE,
+        D,
+    >::id();
+    generator_registry.register_simple::<MapReduceGenerator<
+        L,
+        DataCommitmentSharedCtx,
+        U64Variable,
+        MapReduceSubchainVariable,
+        C,
+        BATCH_SIZE,
+        D,
+    >>(mr_id);
+}
+
+/// DataCommitmentCircuit with field IO instead of EVM IO, for debugging data commitments locally.
+/// Reads the start block number, start header hash, end block number and end header hash, prints
+/// the data commitment when proving, and writes it.
+#[derive(Debug, Clone)]
+pub struct DataCommitmentDebugCircuit<const NB_MAP_JOBS: usize, const BATCH_SIZE: usize> {
+    _config: usize,
+}
+
+impl<const NB_MAP_JOBS: usize, const BATCH_SIZE: usize> Circuit
+    for DataCommitmentDebugCircuit<NB_MAP_JOBS, BATCH_SIZE>
+{
+    fn define<L: PlonkParameters<D>, const D: usize>(builder: &mut CircuitBuilder<L, D>)
+    where
+        <<L as PlonkParameters<D>>::Config as GenericConfig<D>>::Hasher: AlgebraicHasher<L::Field>,
+    {
+        let start_block_number = builder.read::<U64Variable>();
+        let start_header_hash = builder.read::<Bytes32Variable>();
+        let end_block_number = builder.read::<U64Variable>();
+        let end_header_hash = builder.read::<Bytes32Variable>();
+
+        let data_commitment = builder.prove_data_commitment::<Self, NB_MAP_JOBS, BATCH_SIZE>(
+            start_block_number,
+            start_header_hash,
+            end_block_number,
+            end_header_hash,
+            ChainConfig::default(),
+            &[],
+        );
+        builder.watch(&data_commitment, "data_commitment");
+
+        builder.write(data_commitment);
+    }
+
+    fn register_generators<L: PlonkParameters<D>, const D: usize>(
+        generator_registry: &mut HintRegistry<L, D>,
+    ) where
+        <<L as PlonkParameters<D>>::Config as GenericConfig<D>>::Hasher: AlgebraicHasher<L::Field>,
+    {
+        register_data_commitment_generators::<Self, L, D, BATCH_SIZE>(generator_registry);
+    }
+}
+
+/// Aggregates NB_COMMITMENTS data commitments into a single super-commitment.
+#[derive(Debug, Clone)]
+pub struct AggregateDataCommitmentsCircuit<const NB_COMMITMENTS: usize> {
+    _config: usize,
+}
+
+impl<const NB_COMMITMENTS: usize> Circuit for AggregateDataCommitmentsCircuit<NB_COMMITMENTS> {
+    fn define<L: PlonkParameters<D>, const D: usize>(builder: &mut CircuitBuilder<L, D>) {
+        let commitments = (0..NB_COMMITMENTS)
+            .map(|_| builder.evm_read::<Bytes32Variable>())
+            .collect::<Vec<_>>();
+
+        let aggregate_commitment = builder.aggregate_data_commitments(&commitments);
+
+        builder.evm_write(aggregate_commitment);
     }
 }
 
@@ -101,11 +298,15 @@ impl<const NB_MAP_JOBS: usize, const BATCH_SIZE: usize> Circuit
 mod tests {
     use std::env;
 
-    use plonky2x::prelude::{DefaultBuilder, GateRegistry, HintRegistry};
+    use plonky2x::backend::circuit::DefaultParameters;
+    use plonky2x::prelude::{DefaultBuilder, GateRegistry};
     use subtle_encoding::hex;
+    use tokio::runtime::Runtime;
 
     use super::*;
 
+    type F = <DefaultParameters as PlonkParameters<2>>::Field;
+
     #[test]
     #[cfg_attr(feature = "ci", ignore)]
     fn test_data_commitment_serialization() {
@@ -149,10 +350,15 @@ mod tests {
 
         let mut input = circuit.input();
 
-        input.evm_write::<U64Variable>(start_block as u64);
-        input.evm_write::<Bytes32Variable>(H256::from_slice(start_header_hash.as_slice()));
-        input.evm_write::<U64Variable>(end_block as u64);
-        input.evm_write::<Bytes32Variable>(H256::from_slice(end_header_hash.as_slice()));
+        let range = DataCommitmentRange::new(
+            start_block as u64,
+            H256::from_slice(start_header_hash.as_slice()),
+            end_block as u64,
+            H256::from_slice(end_header_hash.as_slice()),
+            0,
+        )
+        .unwrap();
+        range.write_input(&mut input);
 
         log::debug!("Generating proof");
 
@@ -163,7 +369,7 @@ mod tests {
 
         circuit.verify(&proof, &input, &output);
         let data_commitment = output.evm_read::<Bytes32Variable>();
-        println!("data_commitment {:?}", data_commitment);
+        log::info!("data_commitment {:?}", data_commitment);
     }
 
     #[test]
@@ -239,4 +445,102 @@ mod tests {
             end_header_hash.as_slice().try_into().unwrap(),
         );
     }
+
+    /// Prove the data commitment of [start_block, end_block) with DataCommitmentDebugCircuit, check
+    /// it against the one computed natively, and return it.
+    fn test_data_commitment_debug_template<const NB_MAP_JOBS: usize, const BATCH_SIZE: usize>(
+        start_block: u64,
+        end_block: u64,
+    ) -> H256 {
+        env::set_var("RUST_LOG", "debug");
+        env_logger::try_init().unwrap_or_default();
+        dotenv::dotenv().ok();
+
+        // Only the header hashes and the expected data commitment of the fetched inputs are used.
+        const MAX_LEAVES: usize = 4;
+        assert!(end_block - start_block <= MAX_LEAVES as u64);
+        let rt = Runtime::new().unwrap();
+        let inputs = rt.block_on(async {
+            InputDataFetcher::default()
+                .get_data_commitment_inputs::<MAX_LEAVES, F>(
+                    start_block,
+                    end_block,
+                    ChainConfig::default(),
+                )
+                .await
+        });
+
+        let mut builder = DefaultBuilder::new();
+        DataCommitmentDebugCircuit::<NB_MAP_JOBS, BATCH_SIZE>::define(&mut builder);
+        let circuit = builder.build();
+
+        let mut input = circuit.input();
+        input.write::<U64Variable>(start_block);
+        input.write::<Bytes32Variable>(H256(inputs.start_header_hash));
+        input.write::<U64Variable>(end_block);
+        input.write::<Bytes32Variable>(H256(inputs.end_header_hash));
+
+        let (proof, mut output) = rt.block_on(async { circuit.prove_async(&input).await });
+        circuit.verify(&proof, &input, &output);
+
+        let data_commitment = output.read::<Bytes32Variable>();
+        log::info!(
+            "data commitment of blocks [{}, {}): {:?}",
+            start_block, end_block, data_commitment
+        );
+        assert_eq!(data_commitment, H256(inputs.expected_data_commitment));
+        data_commitment
+    }
+
+    #[test]
+    #[cfg_attr(feature = "ci", ignore)]
+    fn test_data_commitment_debug() {
+        test_data_commitment_debug_template::<2, 2>(3800, 3804);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "ci", ignore)]
+    fn test_data_commitment_nb_sha_gadgets() {
+        // 1, 2 and 4 SHA256 gadgets (map jobs) over the same window of 4 blocks.
+        let data_commitment = test_data_commitment_debug_template::<1, 4>(3800, 3804);
+        assert_eq!(
+            test_data_commitment_debug_template::<2, 2>(3800, 3804),
+            data_commitment
+        );
+        assert_eq!(
+            test_data_commitment_debug_template::<4, 1>(3800, 3804),
+            data_commitment
+        );
+    }
+
+    #[test]
+    fn test_data_commitment_range() {
+        let start_header = H256::from_low_u64_be(1);
+        let end_header = H256::from_low_u64_be(2);
+        let range = DataCommitmentRange::new(10000, start_header, 10004, end_header, 7).unwrap();
+        assert_eq!(range.start_height(), 10000);
+        assert_eq!(range.end_height(), 10004);
+        assert_eq!(range.nonce(), 7);
+
+        assert_eq!(
+            DataCommitmentRange::new(10004, start_header, 10000, end_header, 7),
+            Err(DataCommitmentRangeError::InvalidRange {
+                start_height: 10004,
+                end_height: 10000,
+            })
+        );
+        assert_eq!(
+            DataCommitmentRange::new(10000, start_header, 10000, end_header, 7),
+            Err(DataCommitmentRangeError::InvalidRange {
+                start_height: 10000,
+                end_height: 10000,
+            })
+        );
+        assert_eq!(
+            DataCommitmentRange::new(10000, start_header, 10004, start_header, 7),
+            Err(DataCommitmentRangeError::SameHeader {
+                header: start_header
+            })
+        );
+    }
 }
@@ -0,0 +1,463 @@
+use plonky2x::backend::circuit::Circuit;
+use plonky2x::frontend::merkle::tree::MerkleInclusionProofVariable;
+use plonky2x::frontend::uint::uint64::U64Variable;
+use plonky2x::prelude::{
+    ArrayVariable, BoolVariable, Bytes32Variable, CircuitBuilder, PlonkParameters, U32Variable,
+    Variable,
+};
+
+use crate::consts::*;
+use crate::header::HeaderBuilder;
+use crate::signature::SignatureBuilder;
+use crate::validator::ValidatorBuilder;
+use crate::vars::{EquivocationValidatorVariable, ValidatorVariable};
+use crate::vote::VoteBuilder;
+
+/// Proves two distinct headers at the same height were each signed by more than 1/3 of the voting
+/// power of the validator set both headers commit to, and outputs which validators signed both
+/// (equivocated) and their total voting power. The signature of each counted validator is verified
+/// against its precommit for the header at the height. All inputs are public inputs of the proof,
+/// including the validator set and the signatures. If VERIFY_SIGNATURES is false, the signatures
+/// are not verified, so tests of the voting power and validator set checks don't pay for Ed25519
+/// verification. Such a circuit doesn't prove anyone signed, so only tests should disable it.
+#[derive(Debug, Clone)]
+pub struct EquivocationCircuit<
+    const MAX_VALIDATOR_SET_SIZE: usize,
+    const VERIFY_SIGNATURES: bool = true,
+> {
+    _config: usize,
+}
+
+impl<const MAX_VALIDATOR_SET_SIZE: usize, const VERIFY_SIGNATURES: bool> Circuit
+    for EquivocationCircuit<MAX_VALIDATOR_SET_SIZE, VERIFY_SIGNATURES>
+{
+    fn define<L: PlonkParameters<D>, const D: usize>(builder: &mut CircuitBuilder<L, D>) {
+        let height = builder.read::<U64Variable>();
+        let header_a = builder.read::<Bytes32Variable>();
+        let round_present_a = builder.read::<BoolVariable>();
+        let validators_hash_proof_a = builder
+            .read::<MerkleInclusionProofVariable<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES>>();
+        let header_b = builder.read::<Bytes32Variable>();
+        let round_present_b = builder.read::<BoolVariable>();
+        let validators_hash_proof_b = builder
+            .read::<MerkleInclusionProofVariable<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES>>();
+        let nb_validators = builder.read::<Variable>();
+        let validators =
+            builder.read::<ArrayVariable<EquivocationValidatorVariable, MAX_VALIDATOR_SET_SIZE>>();
+
+        builder.assert_nonzero_hash(&header_a);
+        builder.assert_nonzero_hash(&header_b);
+        builder.assert_distinct_headers(&header_a, &header_b);
+
+        // Both headers commit to the same validator set.
+        let validators_hash =
+            Bytes32Variable::from(&validators_hash_proof_a.leaf[2..2 + HASH_SIZE]);
+        builder.assert_validators_hash_equals(
+            &header_a,
+            &validators_hash_proof_a,
+            &validators_hash,
+        );
+        builder.assert_validators_hash_equals(
+            &header_b,
+            &validators_hash_proof_b,
+            &validators_hash,
+        );
+        let validator_set = validators
+            .as_vec()
+            .iter()
+            .map(|v| ValidatorVariable {
+                pubkey: v.pubkey,
+                voting_power: v.voting_power,
+                enabled: v.enabled,
+            })
+            .collect::<Vec<_>>();
+        builder.verify_validators_hash::<MAX_VALIDATOR_SET_SIZE>(
+            &validators_hash,
+            &ArrayVariable::<ValidatorVariable, MAX_VALIDATOR_SET_SIZE>::from(validator_set),
+            &nb_validators,
+        );
+
+        // Only validators of the set can sign, and their signatures of each header's precommit
+        // must verify.
+        let true_var = builder._true();
+        let pubkeys = validators
+            .as_vec()
+            .iter()
+            .map(|v| v.pubkey)
+            .collect::<Vec<_>>();
+        for (header, round_present, is_a) in [
+            (header_a, round_present_a, true),
+            (header_b, round_present_b, false),
+        ] {
+            let mut signed = Vec::new();
+            let mut signatures = Vec::new();
+            for validator in validators.as_vec().iter() {
+                let (validator_signed, signature) = if is_a {
+                    (validator.signed_a, validator.signature_a.clone())
+                } else {
+                    (validator.signed_b, validator.signature_b.clone())
+                };
+                let not_signed = builder.not(validator_signed);
+                let signed_implies_enabled = builder.or(not_signed, validator.enabled);
+                builder.assert_is_equal(signed_implies_enabled, true_var);
+
+                builder.verify_commit_vote(
+                    &signature.message.0,
+                    &signature.message_byte_length,
+                    &height,
+                    &header,
+                    &round_present,
+                    &validator_signed,
+                );
+                signed.push(validator_signed);
+                signatures.push(signature);
+            }
+            let messages = signatures
+                .iter()
+                .map(|s| s.message.0.to_vec())
+                .collect::<Vec<_>>();
+            builder.verify_votes_round_present(&messages, &signed, &round_present);
+            if VERIFY_SIGNATURES {
+                builder.verify_signatures::<MAX_VALIDATOR_SET_SIZE>(&signed, &pubkeys, &signatures);
+            }
+        }
+
+        // A validator which signed either commit must not be counted twice.
+        let signed_any = validators
+            .as_vec()
+            .iter()
+            .map(|v| builder.or(v.signed_a, v.signed_b))
+            .collect::<Vec<_>>();
+        builder.assert_distinct_pubkeys(&pubkeys, &signed_any);
+
+        let zero = builder.constant::<U64Variable>(0);
+        let mut total_voting_power = zero;
+        let mut signed_a_voting_power = zero;
+        let mut signed_b_voting_power = zero;
+        let mut equivocated_voting_power = zero;
+        let mut equivocated = Vec::new();
+        for validator in validators.as_vec().iter() {
+            let voting_power = builder.select(validator.enabled, validator.voting_power, zero);
+            total_voting_power = builder.add_voting_power(&total_voting_power, &voting_power);
+
+            let power_a = builder.select(validator.signed_a, validator.voting_power, zero);
+            signed_a_voting_power = builder.add_voting_power(&signed_a_voting_power, &power_a);
+            let power_b = builder.select(validator.signed_b, validator.voting_power, zero);
+            signed_b_voting_power = builder.add_voting_power(&signed_b_voting_power, &power_b);
+
+            let signed_both = builder.and(validator.signed_a, validator.signed_b);
+            let power_both = builder.select(signed_both, validator.voting_power, zero);
+            equivocated_voting_power =
+                builder.add_voting_power(&equivocated_voting_power, &power_both);
+            equivocated.push(signed_both);
+        }
+
+        // signed_voting_power * 3 > total_voting_power for both commits.
+        let one = builder.constant::<U32Variable>(1);
+        let three = builder.constant::<U32Variable>(3);
+        for signed_voting_power in [signed_a_voting_power, signed_b_voting_power] {
+            let exceeds_threshold = builder.voting_power_greater_than_threshold(
+                &signed_voting_power,
+                &total_voting_power,
+                &one,
+                &three,
+            );
+            builder.assert_is_equal(exceeds_threshold, true_var);
+        }
+
+        builder.write(ArrayVariable::<BoolVariable, MAX_VALIDATOR_SET_SIZE>::new(
+            equivocated,
+        ));
+        builder.write(equivocated_voting_power);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_consensus::SigningKey;
+    use ethers::types::{H256, U256};
+    use plonky2x::backend::circuit::DefaultParameters;
+    use plonky2x::frontend::curta::ec::point::CompressedEdwardsY;
+    use plonky2x::frontend::ecc::curve25519::ed25519::eddsa::EDDSASignatureVariableValue;
+    use plonky2x::prelude::plonky2::field::types::Field;
+    use plonky2x::prelude::DefaultBuilder;
+    use tendermint::block::{self, Header, Round};
+    use tendermint::validator::Info;
+    use tendermint::vote::{self, Power};
+    use tendermint::{Hash, PublicKey, Signature};
+    use tendermint_proto::Protobuf;
+    use tendermintx::input::InputDataFetcher;
+
+    use super::*;
+    use crate::fixture::get_signed_block;
+    use crate::fixture::tests::FIXTURE_PATH;
+    use crate::input::compute_validators_hash;
+    use crate::signature::to_commit_signature;
+    use crate::vars::{CommitSignature, EquivocationValidator};
+    use crate::vote::CanonicalVote;
+
+    type L = DefaultParameters;
+    type F = <L as PlonkParameters<D>>::Field;
+    const D: usize = 2;
+    const MAX_VALIDATOR_SET_SIZE: usize = 4;
+
+    /// Sign the vote of each validator for header, at round.
+    fn sign_votes(
+        header: &Header,
+        part_set_header: block::parts::Header,
+        vote_type: vote::Type,
+        round: u16,
+        signing_keys: &[SigningKey],
+    ) -> Vec<CommitSignature<F>> {
+        let vote = CanonicalVote {
+            vote_type,
+            height: header.height,
+            round: Round::from(round),
+            block_id: Some(block::Id {
+                hash: header.hash(),
+                part_set_header,
+            }),
+            timestamp: Some(header.time),
+            chain_id: header.chain_id.clone(),
+        };
+        let message = vote.to_sign_bytes();
+        signing_keys
+            .iter()
+            .map(|key| {
+                let signature =
+                    Signature::try_from(key.sign(&message).to_bytes().as_slice()).unwrap();
+                to_commit_signature(&signature, &message)
+            })
+            .collect()
+    }
+
+    /// The signature input of a validator which did not sign a header. It has no precommit for
+    /// the header, so its message and signature are zeroed.
+    fn unsigned_commit_signature() -> CommitSignature<F> {
+        CommitSignature {
+            signature: EDDSASignatureVariableValue {
+                r: CompressedEdwardsY([0u8; 32]),
+                s: U256::zero(),
+            },
+            message: [0u8; VOTE_MESSAGE_MAX_SIZE_BYTES],
+            message_byte_length: 0,
+        }
+    }
+
+    /// Tampering with the inputs of a proof of an equivocation.
+    #[derive(Default)]
+    struct Tamper {
+        /// Sign the first header twice instead of two conflicting headers.
+        same_header: bool,
+        /// The first validator supplies its signature of the first header as its signature of the
+        /// second.
+        forge_b: bool,
+        /// Claim the messages of the second commit encode a round, even if its votes are at round 0.
+        round_present_b: bool,
+        /// Sign the votes for the second header at this round instead of round 0.
+        round_b: u16,
+        /// Sign votes of this type for the second header instead of precommits.
+        vote_type_b: Option<vote::Type>,
+    }
+
+    /// Prove an equivocation by a validator set of 2 validators with equal voting power, which
+    /// sign two conflicting headers at the height of the fixture block: the fixture header and a
+    /// copy with another time, both committing to the validator set. signed_a and signed_b are
+    /// which validators signed each header, and the other slots carry zeroed messages. The inputs are tampered as described by tamper. The
+    /// signatures are only verified if VERIFY_SIGNATURES.
+    fn prove_equivocation<const VERIFY_SIGNATURES: bool>(
+        signed_a: [bool; 2],
+        signed_b: [bool; 2],
+        tamper: Tamper,
+    ) -> (Vec<bool>, u64) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = DefaultBuilder::new();
+        EquivocationCircuit::<MAX_VALIDATOR_SET_SIZE, VERIFY_SIGNATURES>::define(&mut builder);
+        let circuit = builder.build();
+
+        let signing_keys = (0..2)
+            .map(|_| SigningKey::new(rand::thread_rng()))
+            .collect::<Vec<_>>();
+        let validators = signing_keys
+            .iter()
+            .map(|key| {
+                let pubkey =
+                    PublicKey::from_raw_ed25519(key.verification_key().as_bytes()).unwrap();
+                Info::new(pubkey, Power::from(25000000u32))
+            })
+            .collect::<Vec<_>>();
+        let validators_hash = Hash::Sha256(compute_validators_hash(&validators).0);
+
+        let block_number = 10000;
+        let signed_header = get_signed_block(FIXTURE_PATH, block_number);
+        let part_set_header = signed_header.commit.block_id.part_set_header;
+        let mut header_a = signed_header.header;
+        header_a.validators_hash = validators_hash;
+        let mut header_b = header_a.clone();
+        if !tamper.same_header {
+            header_b.time = get_signed_block(FIXTURE_PATH, block_number + 1).header.time;
+        }
+
+        let signatures_a = sign_votes(
+            &header_a,
+            part_set_header,
+            vote::Type::Precommit,
+            0,
+            &signing_keys,
+        );
+        let mut signatures_b = sign_votes(
+            &header_b,
+            part_set_header,
+            tamper.vote_type_b.unwrap_or(vote::Type::Precommit),
+            tamper.round_b,
+            &signing_keys,
+        );
+        if tamper.forge_b {
+            signatures_b[0] = signatures_a[0].clone();
+        }
+
+        let fetcher = InputDataFetcher::default();
+        let mut input = circuit.input();
+        input.write::<U64Variable>(block_number);
+        // Protobuf omits a zero round, so a round is encoded iff it is nonzero.
+        let round_present_b = tamper.round_b != 0 || tamper.round_present_b;
+        for (header, round_present) in [(&header_a, false), (&header_b, round_present_b)] {
+            input.write::<Bytes32Variable>(H256::from_slice(header.hash().as_bytes()));
+            input.write::<BoolVariable>(round_present);
+            input.write::<MerkleInclusionProofVariable<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES>>(
+                fetcher.get_inclusion_proof::<PROTOBUF_HASH_SIZE_BYTES, F>(
+                    header,
+                    VALIDATORS_HASH_INDEX as u64,
+                    header.validators_hash.encode_vec(),
+                ),
+            );
+        }
+        input.write::<Variable>(F::from_canonical_usize(validators.len()));
+        let validator_values = (0..MAX_VALIDATOR_SET_SIZE)
+            .map(|i| {
+                let enabled = i < validators.len();
+                let signed_a = enabled && signed_a[i];
+                let signed_b = enabled && signed_b[i];
+                EquivocationValidator::<F> {
+                    pubkey: if enabled {
+                        H256::from_slice(&validators[i].pub_key.to_bytes())
+                    } else {
+                        H256::zero()
+                    },
+                    voting_power: if enabled {
+                        validators[i].power.value()
+                    } else {
+                        0
+                    },
+                    enabled,
+                    signed_a,
+                    signature_a: if signed_a {
+                        signatures_a[i].clone()
+                    } else {
+                        unsigned_commit_signature()
+                    },
+                    signed_b,
+                    signature_b: if signed_b {
+                        signatures_b[i].clone()
+                    } else {
+                        unsigned_commit_signature()
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+        input.write::<ArrayVariable<EquivocationValidatorVariable, MAX_VALIDATOR_SET_SIZE>>(
+            validator_values,
+        );
+
+        let (proof, mut output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+
+        let equivocated = output.read::<ArrayVariable<BoolVariable, MAX_VALIDATOR_SET_SIZE>>();
+        let equivocated_voting_power = output.read::<U64Variable>();
+        (equivocated, equivocated_voting_power)
+    }
+
+    #[test]
+    fn test_equivocation() {
+        // Each of the 2 validators has half of the voting power, so either alone exceeds 1/3.
+        let (equivocated, equivocated_voting_power) =
+            prove_equivocation::<true>([true, true], [true, false], Tamper::default());
+        assert_eq!(equivocated, vec![true, false, false, false]);
+        assert_eq!(equivocated_voting_power, 25000000);
+    }
+
+    #[test]
+    fn test_equivocation_unsigned_zeroed_messages() {
+        // The second validator signed neither header, so it has no precommit for either and both
+        // of its slots carry zeroed messages.
+        let (equivocated, equivocated_voting_power) =
+            prove_equivocation::<true>([true, false], [true, false], Tamper::default());
+        assert_eq!(equivocated, vec![true, false, false, false]);
+        assert_eq!(equivocated_voting_power, 25000000);
+    }
+
+    #[test]
+    fn test_equivocation_round_present() {
+        // The second header is committed at round 1, so the block hash of its messages follows
+        // the round.
+        let tamper = Tamper {
+            round_b: 1,
+            ..Default::default()
+        };
+        let (equivocated, equivocated_voting_power) =
+            prove_equivocation::<false>([true, true], [true, false], tamper);
+        assert_eq!(equivocated, vec![true, false, false, false]);
+        assert_eq!(equivocated_voting_power, 25000000);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_equivocation_same_header() {
+        let tamper = Tamper {
+            same_header: true,
+            ..Default::default()
+        };
+        prove_equivocation::<false>([true, true], [true, false], tamper);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_equivocation_below_threshold() {
+        prove_equivocation::<false>([true, true], [false, false], Tamper::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_equivocation_forged_signature() {
+        // The first validator only signed the first header, so its equivocation can't be proven
+        // with that signature.
+        let tamper = Tamper {
+            forge_b: true,
+            ..Default::default()
+        };
+        prove_equivocation::<true>([true, true], [true, false], tamper);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_equivocation_inconsistent_round_present() {
+        // The second commit claims its messages encode a round, but they are at round 0.
+        let tamper = Tamper {
+            round_present_b: true,
+            ..Default::default()
+        };
+        prove_equivocation::<false>([true, true], [true, false], tamper);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_equivocation_prevote() {
+        // A prevote for the second header is not a precommit, so it can't count towards a commit.
+        let tamper = Tamper {
+            vote_type_b: Some(vote::Type::Prevote),
+            ..Default::default()
+        };
+        prove_equivocation::<false>([true, true], [true, false], tamper);
+    }
+}
@@ -0,0 +1,470 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::{env, fmt};
+
+use ethers::types::H256;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{info, warn};
+use serde::Deserialize;
+use tendermint::block::signed_header::SignedHeader;
+use tendermint::validator::Info;
+
+use crate::input::{
+    compute_validators_hash, parse_signed_block_validators, verify_total_voting_power,
+    InputDataError,
+};
+
+pub mod adversarial;
+
+#[derive(Debug, Deserialize)]
+pub struct SignedBlockResponse {
+    pub result: SignedHeader,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignedBlockValidatorsResponse {
+    pub result: SignedBlockValidators,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignedBlockValidators {
+    pub validator_set: SignedBlockValidatorSet,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignedBlockValidatorSet {
+    pub validators: Vec<Info>,
+    #[serde(default, with = "tendermint_proto::serializers::optional_from_str")]
+    pub total_voting_power: Option<u64>,
+}
+
+/// Canonical root of the fixtures. Fixtures are stored as `<root>/<chain>/<block>/<file>`.
+pub const FIXTURE_ROOT: &str = "./circuits/fixtures";
+
+/// Deprecated root of the fixtures, written by older versions of the fetch binary. Fixtures are
+/// still read from here if missing from the canonical root, until they are migrated with
+/// `fetch migrate-fixtures`.
+pub const LEGACY_FIXTURE_ROOT: &str = "./fixtures";
+
+/// Whether newly saved fixtures should be gzipped. Read from `COMPRESS_FIXTURES`, defaults to false.
+pub fn compress_fixtures() -> bool {
+    env::var("COMPRESS_FIXTURES")
+        .unwrap_or(String::from("false"))
+        .parse::<bool>()
+        .expect("invalid COMPRESS_FIXTURES")
+}
+
+/// Read a fixture. If a gzipped copy of the fixture (`<file_name>.gz`) exists, it is read
+/// instead of the plain file. Fixtures under the canonical root that have not been migrated yet
+/// are read from the legacy root.
+pub fn read_fixture(file_name: &str) -> String {
+    if let Some(legacy_file_name) = legacy_fixture_file_name(file_name) {
+        warn!(
+            "Reading fixture from deprecated path {}, migrate it with `fetch migrate-fixtures`",
+            legacy_file_name
+        );
+        return read_fixture(&legacy_file_name);
+    }
+
+    let gz_file_name = format!("{}.gz", file_name);
+    info!("Fixture name: {}", file_name);
+    if Path::new(&gz_file_name).exists() {
+        let file = File::open(gz_file_name.as_str()).expect("Unable to open file");
+        let mut file_content = String::new();
+        GzDecoder::new(file)
+            .read_to_string(&mut file_content)
+            .expect("Unable to decompress file");
+        file_content
+    } else {
+        fs::read_to_string(file_name).expect("Unable to read file")
+    }
+}
+
+/// Get the legacy path of a fixture under the canonical root, if the fixture only exists there.
+fn legacy_fixture_file_name(file_name: &str) -> Option<String> {
+    let fixture_exists =
+        |name: &str| Path::new(name).exists() || Path::new(&format!("{}.gz", name)).exists();
+    let relative_path = file_name.strip_prefix(FIXTURE_ROOT)?;
+    let legacy_file_name = format!("{}{}", LEGACY_FIXTURE_ROOT, relative_path);
+    if !fixture_exists(file_name) && fixture_exists(&legacy_file_name) {
+        Some(legacy_file_name)
+    } else {
+        None
+    }
+}
+
+/// Move all fixtures under `from` into `to`, preserving their `<block>/<file>` layout. Fixtures
+/// already present in `to` with identical contents are removed from `from`, so the migration is
+/// idempotent. Returns the number of fixtures moved. Fails without moving the fixture if a
+/// different fixture already exists at its destination.
+pub fn migrate_fixtures(from: &Path, to: &Path) -> io::Result<usize> {
+    if !from.exists() {
+        return Ok(0);
+    }
+
+    let mut nb_moved = 0;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let src = entry.path();
+        let dst = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            nb_moved += migrate_fixtures(&src, &dst)?;
+            // Only remove directories that have been fully migrated.
+            if fs::read_dir(&src)?.next().is_none() {
+                fs::remove_dir(&src)?;
+            }
+            continue;
+        }
+
+        if dst.exists() {
+            if fs::read(&src)? != fs::read(&dst)? {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("fixture {} conflicts with {}", src.display(), dst.display()),
+                ));
+            }
+        } else {
+            fs::create_dir_all(to)?;
+            // Copy rather than rename, as from and to may be on different filesystems.
+            fs::copy(&src, &dst)?;
+            nb_moved += 1;
+        }
+        fs::remove_file(&src)?;
+    }
+
+    Ok(nb_moved)
+}
+
+/// Write a fixture, creating its parent directory if needed. If compress is set, the fixture is
+/// gzipped and written to `<file_name>.gz`.
+pub fn write_fixture(file_name: &str, content: &str, compress: bool) {
+    // Ensure the directory exists
+    if let Some(parent) = Path::new(file_name).parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    if compress {
+        let file = File::create(format!("{}.gz", file_name)).expect("Unable to create file");
+        let mut encoder = GzEncoder::new(file, Compression::best());
+        encoder
+            .write_all(content.as_bytes())
+            .expect("Unable to write file");
+        encoder.finish().expect("Unable to write file");
+    } else {
+        fs::write(file_name, content.as_bytes()).expect("Unable to write file");
+    }
+}
+
+/// Get the signed header of a block from its `signed_block.json` fixture (or
+/// `signed_block.json.gz` if present).
+pub fn get_signed_block(fixture_path: &str, block_number: u64) -> SignedHeader {
+    let file_name = format!("{}/{}/signed_block.json", fixture_path, block_number);
+    let v: SignedBlockResponse =
+        serde_json::from_str(&read_fixture(&file_name)).expect("Failed to parse JSON");
+    v.result
+}
+
+/// Get the validator set of a block from its `signed_block.json` fixture (or
+/// `signed_block.json.gz` if present).
+pub fn get_signed_block_validators(fixture_path: &str, block_number: u64) -> Vec<Info> {
+    try_get_signed_block_validators(fixture_path, block_number).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Get the validator set of a block from its `signed_block.json` fixture (or
+/// `signed_block.json.gz` if present), verified by parse_signed_block_validators.
+pub fn try_get_signed_block_validators(
+    fixture_path: &str,
+    block_number: u64,
+) -> Result<Vec<Info>, InputDataError> {
+    let file_name = format!("{}/{}/signed_block.json", fixture_path, block_number);
+    parse_signed_block_validators(&read_fixture(&file_name))
+}
+
+/// A fixture which doesn't match the hashes committed to in it.
+#[derive(Debug)]
+pub enum FixtureError {
+    /// The fixture could not be parsed.
+    InvalidJson { file_name: String, reason: String },
+    /// The validator set is invalid, e.g. its total voting power is wrong.
+    InvalidValidators(InputDataError),
+    /// The validator set does not hash to the header's validators_hash.
+    ValidatorsHashMismatch {
+        block_number: u64,
+        expected_hash: H256,
+        actual_hash: H256,
+    },
+    /// The header does not hash to the commit's block ID.
+    HeaderHashMismatch {
+        block_number: u64,
+        expected_hash: H256,
+        actual_hash: H256,
+    },
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixtureError::InvalidJson { file_name, reason } => {
+                write!(f, "invalid fixture {}: {}", file_name, reason)
+            }
+            FixtureError::InvalidValidators(e) => write!(f, "invalid validator set: {}", e),
+            FixtureError::ValidatorsHashMismatch {
+                block_number,
+                expected_hash,
+                actual_hash,
+            } => write!(
+                f,
+                "validator set of block {} hashes to {:?}, expected validators_hash {:?}",
+                block_number, actual_hash, expected_hash
+            ),
+            FixtureError::HeaderHashMismatch {
+                block_number,
+                expected_hash,
+                actual_hash,
+            } => write!(
+                f,
+                "header of block {} hashes to {:?}, expected commit block ID {:?}",
+                block_number, actual_hash, expected_hash
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FixtureError {}
+
+/// Check the `signed_block.json` fixture of a block is consistent: its validator set hashes to the
+/// header's validators_hash, and its header hashes to the block ID its commit signs.
+pub fn validate_fixture(fixture_path: &str, block_number: u64) -> Result<(), FixtureError> {
+    let file_name = format!("{}/{}/signed_block.json", fixture_path, block_number);
+    let invalid_json = |e: serde_json::Error| FixtureError::InvalidJson {
+        file_name: file_name.clone(),
+        reason: e.to_string(),
+    };
+    let content = read_fixture(&file_name);
+    let signed_header = serde_json::from_str::<SignedBlockResponse>(&content)
+        .map_err(invalid_json)?
+        .result;
+    let validator_set = serde_json::from_str::<SignedBlockValidatorsResponse>(&content)
+        .map_err(invalid_json)?
+        .result
+        .validator_set;
+    verify_total_voting_power(&validator_set.validators, validator_set.total_voting_power)
+        .map_err(FixtureError::InvalidValidators)?;
+
+    let header = &signed_header.header;
+    let expected_validators_hash = H256::from_slice(header.validators_hash.as_bytes());
+    let validators_hash = compute_validators_hash(&validator_set.validators);
+    if validators_hash != expected_validators_hash {
+        return Err(FixtureError::ValidatorsHashMismatch {
+            block_number,
+            expected_hash: expected_validators_hash,
+            actual_hash: validators_hash,
+        });
+    }
+
+    let expected_header_hash = H256::from_slice(signed_header.commit.block_id.hash.as_bytes());
+    let header_hash = H256::from_slice(header.hash().as_bytes());
+    if header_hash != expected_header_hash {
+        return Err(FixtureError::HeaderHashMismatch {
+            block_number,
+            expected_hash: expected_header_hash,
+            actual_hash: header_hash,
+        });
+    }
+    Ok(())
+}
+
+/// Get the block numbers of the fixtures under fixture_path, in increasing order.
+pub fn list_fixture_blocks(fixture_path: &str) -> io::Result<Vec<u64>> {
+    let mut block_numbers = Vec::new();
+    for entry in fs::read_dir(fixture_path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if let Some(block_number) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<u64>().ok())
+        {
+            block_numbers.push(block_number);
+        }
+    }
+    block_numbers.sort();
+    Ok(block_numbers)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) const FIXTURE_PATH: &str = "./circuits/fixtures/mocha-4";
+    // Mocha-4 fixtures with one of the validators' keys replaced by a secp256k1 key.
+    pub(crate) const MIXED_KEY_FIXTURE_PATH: &str = "./circuits/fixtures/mixed-key";
+    // Mocha-4 fixtures whose validator set reports the wrong total voting power.
+    pub(crate) const WRONG_TOTAL_POWER_FIXTURE_PATH: &str = "./circuits/fixtures/wrong-total-power";
+
+    /// Get the signed headers of [start_block, end_block] from the fixtures, rewritten so the
+    /// validator set rotates at rotation_block: blocks from rotation_block on have the
+    /// validators_hash of the set without its first validator, as if it left the set, and the
+    /// block before it commits to the new set as its next_validators_hash. The headers are
+    /// relinked by last_block_id, so the range is a valid chain of headers. The commits are not
+    /// re-signed.
+    pub(crate) fn get_signed_blocks_with_validator_rotation(
+        fixture_path: &str,
+        start_block: u64,
+        end_block: u64,
+        rotation_block: u64,
+    ) -> Vec<SignedHeader> {
+        assert!(start_block < rotation_block && rotation_block <= end_block);
+        let validators = get_signed_block_validators(fixture_path, rotation_block);
+        let rotated_validators_hash =
+            tendermint::Hash::Sha256(compute_validators_hash(&validators[1..]).0);
+
+        let mut signed_headers = (start_block..end_block + 1)
+            .map(|block_number| get_signed_block(fixture_path, block_number))
+            .collect::<Vec<_>>();
+        for i in 0..signed_headers.len() {
+            let block_number = start_block + i as u64;
+            let header = &mut signed_headers[i].header;
+            if block_number + 1 >= rotation_block {
+                header.next_validators_hash = rotated_validators_hash;
+            }
+            if block_number >= rotation_block {
+                header.validators_hash = rotated_validators_hash;
+            }
+            if i > 0 {
+                let prev_header_hash = signed_headers[i - 1].header.hash();
+                let header = &mut signed_headers[i].header;
+                let mut last_block_id = header.last_block_id.unwrap();
+                last_block_id.hash = prev_header_hash;
+                header.last_block_id = Some(last_block_id);
+            }
+        }
+        signed_headers
+    }
+
+    // Ensure a gzipped fixture loads identically to its plain counterpart.
+    #[test]
+    fn test_compressed_fixture_round_trip() {
+        let block_number = 10000;
+        let plain_signed_block = get_signed_block(FIXTURE_PATH, block_number);
+
+        let content = read_fixture(&format!(
+            "{}/{}/signed_block.json",
+            FIXTURE_PATH, block_number
+        ));
+        let compressed_fixture_path = env::temp_dir().join("blobstreamx-fixtures");
+        let compressed_file_name = format!(
+            "{}/{}/signed_block.json",
+            compressed_fixture_path.display(),
+            block_number
+        );
+        write_fixture(&compressed_file_name, &content, true);
+        assert!(Path::new(&format!("{}.gz", compressed_file_name)).exists());
+        assert!(!Path::new(&compressed_file_name).exists());
+
+        let compressed_signed_block =
+            get_signed_block(compressed_fixture_path.to_str().unwrap(), block_number);
+        assert_eq!(compressed_signed_block, plain_signed_block);
+
+        fs::remove_dir_all(compressed_fixture_path).unwrap();
+    }
+
+    // Ensure migrated fixtures load identically to the originals, and that migrating again is a
+    // no-op.
+    #[test]
+    fn test_migrate_fixtures() {
+        let block_number = 10000;
+        let signed_block = get_signed_block(FIXTURE_PATH, block_number);
+
+        let tmp_path = env::temp_dir().join("blobstreamx-migrate-fixtures");
+        let legacy_path = tmp_path.join("legacy");
+        let canonical_path = tmp_path.join("canonical");
+        let content = read_fixture(&format!(
+            "{}/{}/signed_block.json",
+            FIXTURE_PATH, block_number
+        ));
+        write_fixture(
+            &format!(
+                "{}/{}/signed_block.json",
+                legacy_path.display(),
+                block_number
+            ),
+            &content,
+            false,
+        );
+
+        assert_eq!(migrate_fixtures(&legacy_path, &canonical_path).unwrap(), 1);
+        assert!(!legacy_path.join(block_number.to_string()).exists());
+        let migrated_signed_block =
+            get_signed_block(canonical_path.to_str().unwrap(), block_number);
+        assert_eq!(migrated_signed_block, signed_block);
+
+        assert_eq!(migrate_fixtures(&legacy_path, &canonical_path).unwrap(), 0);
+        assert_eq!(
+            get_signed_block(canonical_path.to_str().unwrap(), block_number),
+            signed_block
+        );
+
+        fs::remove_dir_all(tmp_path).unwrap();
+    }
+
+    #[test]
+    fn test_validate_fixture() {
+        let block_numbers = list_fixture_blocks(FIXTURE_PATH).unwrap();
+        assert_eq!(block_numbers, (10000..10005).collect::<Vec<_>>());
+        for block_number in block_numbers {
+            validate_fixture(FIXTURE_PATH, block_number).unwrap();
+        }
+    }
+
+    /// Write the fixture of block_number with corrupt applied to its JSON to a temporary fixture
+    /// path, and validate it.
+    fn validate_corrupted_fixture(
+        name: &str,
+        block_number: u64,
+        corrupt: impl FnOnce(&mut serde_json::Value),
+    ) -> Result<(), FixtureError> {
+        let content = read_fixture(&format!(
+            "{}/{}/signed_block.json",
+            FIXTURE_PATH, block_number
+        ));
+        let mut v: serde_json::Value = serde_json::from_str(&content).unwrap();
+        corrupt(&mut v);
+
+        let tmp_path = env::temp_dir().join(name);
+        write_fixture(
+            &format!("{}/{}/signed_block.json", tmp_path.display(), block_number),
+            &v.to_string(),
+            false,
+        );
+        let res = validate_fixture(tmp_path.to_str().unwrap(), block_number);
+        fs::remove_dir_all(tmp_path).unwrap();
+        res
+    }
+
+    #[test]
+    fn test_validate_corrupted_fixture() {
+        let block_number = 10000;
+        let next_time = get_signed_block(FIXTURE_PATH, block_number + 1)
+            .header
+            .time
+            .to_rfc3339();
+        let res = validate_corrupted_fixture("blobstreamx-corrupted-header", block_number, |v| {
+            v["result"]["header"]["time"] = serde_json::Value::String(next_time)
+        });
+        assert!(matches!(res, Err(FixtureError::HeaderHashMismatch { .. })));
+
+        let res =
+            validate_corrupted_fixture("blobstreamx-corrupted-validators", block_number, |v| {
+                v["result"]["validator_set"]["validators"][0]["voting_power"] = "1".into()
+            });
+        assert!(matches!(
+            res,
+            Err(FixtureError::ValidatorsHashMismatch { .. })
+        ));
+    }
+}
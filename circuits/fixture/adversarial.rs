@@ -0,0 +1,130 @@
+//! Tampered copies of block fixtures for security tests. Each function takes a base fixture and
+//! returns a copy with a single flaw, which the circuits and `verify_commit` must reject.
+
+use ed25519_consensus::SigningKey;
+use tendermint::block::signed_header::SignedHeader;
+use tendermint::block::CommitSig;
+use tendermint::validator::Info;
+use tendermint::{PublicKey, Signature};
+
+use crate::fixture::{get_signed_block, get_signed_block_validators};
+
+/// A signed block and the validator set which signed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockFixture {
+    pub signed_header: SignedHeader,
+    pub validators: Vec<Info>,
+}
+
+impl BlockFixture {
+    /// Load the fixture of block_number from fixture_path.
+    pub fn load(fixture_path: &str, block_number: u64) -> Self {
+        Self {
+            signed_header: get_signed_block(fixture_path, block_number),
+            validators: get_signed_block_validators(fixture_path, block_number),
+        }
+    }
+}
+
+/// Mark the signatures of the commit as absent, from the last, until at most 2/3 of the voting
+/// power signed.
+pub fn with_insufficient_power(fixture: &BlockFixture) -> BlockFixture {
+    let mut tampered = fixture.clone();
+    let total_voting_power = fixture
+        .validators
+        .iter()
+        .map(|v| v.power.value())
+        .sum::<u64>();
+    let power_of = |signature: &CommitSig| match signature {
+        CommitSig::BlockIdFlagCommit {
+            validator_address, ..
+        } => fixture
+            .validators
+            .iter()
+            .find(|v| v.address == *validator_address)
+            .map_or(0, |v| v.power.value()),
+        _ => 0,
+    };
+
+    let signatures = &mut tampered.signed_header.commit.signatures;
+    let mut signed_voting_power = signatures.iter().map(power_of).sum::<u64>();
+    for signature in signatures.iter_mut().rev() {
+        if signed_voting_power as u128 * 3 <= total_voting_power as u128 * 2 {
+            break;
+        }
+        signed_voting_power -= power_of(signature);
+        *signature = CommitSig::BlockIdFlagAbsent;
+    }
+    tampered
+}
+
+/// Flip the lowest bit of the S scalar of the index-th signature of the commit. S stays below the
+/// group order, so the signature is well formed but doesn't verify. Panics if the index-th
+/// signature is not a commit signature.
+pub fn with_corrupted_signature(fixture: &BlockFixture, index: usize) -> BlockFixture {
+    let mut tampered = fixture.clone();
+    match &mut tampered.signed_header.commit.signatures[index] {
+        CommitSig::BlockIdFlagCommit {
+            signature: Some(signature),
+            ..
+        } => {
+            let mut sig_bytes = signature.as_bytes().to_vec();
+            sig_bytes[32] ^= 1;
+            *signature = Signature::try_from(sig_bytes.as_slice()).unwrap();
+        }
+        _ => panic!("signature {} is not a commit signature", index),
+    }
+    tampered
+}
+
+/// Replace the pubkey of the first validator with another key, keeping its address and voting
+/// power. The validator set no longer hashes to the header's validators_hash, and the validator's
+/// signature doesn't verify against the new key.
+pub fn with_wrong_validator_hash(fixture: &BlockFixture) -> BlockFixture {
+    let mut tampered = fixture.clone();
+    let signing_key = SigningKey::from([0x42; 32]);
+    tampered.validators[0].pub_key =
+        PublicKey::from_raw_ed25519(signing_key.verification_key().as_bytes()).unwrap();
+    tampered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::tests::FIXTURE_PATH;
+    use crate::input::{compute_validators_hash, verify_commit};
+
+    #[test]
+    fn test_tampered_fixtures_fail_verify_commit() {
+        let fixture = BlockFixture::load(FIXTURE_PATH, 10000);
+        let validators_hash = fixture.signed_header.header.validators_hash.as_bytes();
+        assert!(verify_commit(&fixture.signed_header, &fixture.validators).is_valid());
+
+        let insufficient_power = with_insufficient_power(&fixture);
+        let verification = verify_commit(
+            &insufficient_power.signed_header,
+            &insufficient_power.validators,
+        );
+        assert!(verification.invalid_signers.is_empty());
+        assert!(!verification.is_valid());
+
+        let corrupted_signature = with_corrupted_signature(&fixture, 0);
+        let verification = verify_commit(
+            &corrupted_signature.signed_header,
+            &corrupted_signature.validators,
+        );
+        assert_eq!(verification.invalid_signers.len(), 1);
+        assert!(!verification.is_valid());
+
+        let wrong_validator_hash = with_wrong_validator_hash(&fixture);
+        assert_ne!(
+            compute_validators_hash(&wrong_validator_hash.validators).as_bytes(),
+            validators_hash
+        );
+        assert!(!verify_commit(
+            &wrong_validator_hash.signed_header,
+            &wrong_validator_hash.validators
+        )
+        .is_valid());
+    }
+}
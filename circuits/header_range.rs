@@ -1,19 +1,267 @@
+use std::fmt;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use ethers::types::H256;
 use plonky2x::backend::circuit::Circuit;
+use plonky2x::frontend::hint::asynchronous::hint::AsyncHint;
 use plonky2x::frontend::mapreduce::generator::MapReduceGenerator;
 use plonky2x::frontend::uint::uint64::U64Variable;
-use plonky2x::prelude::{Bytes32Variable, CircuitBuilder, PlonkParameters};
+use plonky2x::prelude::{
+    Bytes32Variable, CircuitBuilder, PlonkParameters, RichField, ValueStream, VariableStream,
+};
+use serde::{Deserialize, Serialize};
+use tendermint::block::signed_header::SignedHeader;
+use tendermint::block::Header;
+use tendermint::validator::Info;
 use tendermintx::config::TendermintConfig;
+use tendermintx::input::InputDataFetcher;
 use tendermintx::skip::{SkipOffchainInputs, TendermintSkipCircuit};
 
 use crate::builder::{DataCommitmentBuilder, DataCommitmentSharedCtx};
+use crate::config::{BlockVersionConfig, ChainConfig, CheckpointConfig, HeaderLayoutConfig};
 use crate::data_commitment::DataCommitmentOffchainInputs;
-use crate::vars::MapReduceSubchainVariable;
+use crate::fixture::{get_signed_block, get_signed_block_validators};
+use crate::header::HeaderBuilder;
+use crate::input::{verify_commit, CommitVerification, DataCommitmentInputFetcher};
+use crate::validator::{to_validator_values, ValidatorBuilder};
+use crate::vars::{MapReduceSubchainVariable, SkipHeaderInputs, SkipHeaderInputsVariable};
+
+/// Fetches the inputs of the header checks of a skip from trusted_block to target_block in one
+/// hint, instead of one hint per check. The trusted and target headers and the next validator set
+/// of the target block are requested concurrently.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkipHeaderOffchainInputs<const MAX_VALIDATOR_SET_SIZE: usize> {
+    pub chain_config: ChainConfig,
+}
+
+#[async_trait]
+impl<const MAX_VALIDATOR_SET_SIZE: usize, L: PlonkParameters<D>, const D: usize> AsyncHint<L, D>
+    for SkipHeaderOffchainInputs<MAX_VALIDATOR_SET_SIZE>
+{
+    async fn hint(
+        &self,
+        input_stream: &mut ValueStream<L, D>,
+        output_stream: &mut ValueStream<L, D>,
+    ) {
+        let trusted_block = input_stream.read_value::<U64Variable>();
+        let target_block = input_stream.read_value::<U64Variable>();
+
+        // The next validator set of the target block is the validator set of the block after it.
+        let data_fetcher = InputDataFetcher::default();
+        let (signed_headers, next_validators) = futures::join!(
+            data_fetcher.get_signed_headers(&[trusted_block, target_block]),
+            data_fetcher.get_validator_set_from_number(target_block + 1),
+        );
+
+        output_stream.write_value::<SkipHeaderInputsVariable<MAX_VALIDATOR_SET_SIZE>>(
+            skip_header_inputs::<L::Field, MAX_VALIDATOR_SET_SIZE>(
+                &data_fetcher,
+                &signed_headers[0].header,
+                &signed_headers[1].header,
+                &next_validators,
+                self.chain_config,
+            ),
+        );
+    }
+}
+
+/// The inputs of the header checks of a skip from trusted_header to target_header, where
+/// next_validators is the next validator set of the target block.
+pub fn skip_header_inputs<F: RichField, const MAX_VALIDATOR_SET_SIZE: usize>(
+    data_fetcher: &InputDataFetcher,
+    trusted_header: &Header,
+    target_header: &Header,
+    next_validators: &[Info],
+    chain_config: ChainConfig,
+) -> SkipHeaderInputs<MAX_VALIDATOR_SET_SIZE, F> {
+    SkipHeaderInputs {
+        trusted_version_proof: data_fetcher.get_version_proof::<F>(trusted_header, chain_config),
+        target_version_proof: data_fetcher.get_version_proof::<F>(target_header, chain_config),
+        next_validators_hash_proof: data_fetcher
+            .get_next_validators_hash_proof::<F>(target_header, chain_config),
+        next_validators: to_validator_values::<F, MAX_VALIDATOR_SET_SIZE>(next_validators),
+    }
+}
+
+/// The trusting period of Celestia, 2 weeks.
+pub const CELESTIA_TRUSTING_PERIOD: Duration = Duration::from_secs(2 * 7 * 24 * 60 * 60);
+
+/// Inputs of a skip from trusted_block to target_block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkipInputs {
+    pub trusted_block: u64,
+    pub trusted_header_hash: H256,
+    pub target_block: u64,
+    pub target_signed_header: SignedHeader,
+    pub trusted_validators: Vec<Info>,
+    pub target_validators: Vec<Info>,
+}
+
+impl SkipInputs {
+    /// The EVM input of the skip circuit, abi.encodePacked(trusted_block, trusted_header_hash,
+    /// target_block).
+    pub fn evm_input(&self) -> Vec<u8> {
+        let mut input = self.trusted_block.to_be_bytes().to_vec();
+        input.extend_from_slice(self.trusted_header_hash.as_bytes());
+        input.extend_from_slice(&self.target_block.to_be_bytes());
+        input
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipInputsError {
+    /// The target block is not after the trusted block.
+    InvalidRange {
+        trusted_block: u64,
+        target_block: u64,
+    },
+    /// The target header's time is more than the trusting period after the trusted header's time,
+    /// so the trusted validator set may no longer be bonded.
+    OutsideTrustingPeriod {
+        target_block: u64,
+        elapsed: Duration,
+        trusting_period: Duration,
+    },
+    /// The target block's commit does not verify against its validator set.
+    InvalidTargetCommit(CommitVerification),
+    /// At most 1/3 of the trusted validator set's voting power signed the target block's commit,
+    /// so the skip can't be proven from the trusted block.
+    InsufficientTrustedOverlap {
+        target_block: u64,
+        overlap_voting_power: u64,
+        trusted_voting_power: u64,
+    },
+}
+
+impl fmt::Display for SkipInputsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipInputsError::InvalidRange {
+                trusted_block,
+                target_block,
+            } => write!(
+                f,
+                "target block {} is not after trusted block {}",
+                target_block, trusted_block
+            ),
+            SkipInputsError::OutsideTrustingPeriod {
+                target_block,
+                elapsed,
+                trusting_period,
+            } => write!(
+                f,
+                "target block {} is {}s after the trusted block, outside the trusting period of {}s",
+                target_block,
+                elapsed.as_secs(),
+                trusting_period.as_secs()
+            ),
+            SkipInputsError::InvalidTargetCommit(verification) => {
+                write!(f, "invalid target commit: {}", verification)
+            }
+            SkipInputsError::InsufficientTrustedOverlap {
+                target_block,
+                overlap_voting_power,
+                trusted_voting_power,
+            } => write!(
+                f,
+                "trusted validators with {} of {} voting power signed target block {}, at most 1/3",
+                overlap_voting_power, trusted_voting_power, target_block
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SkipInputsError {}
+
+/// Build the inputs of a skip from trusted_block to target_block from their signed_block fixtures,
+/// including fixtures in the legacy fixture layout. See skip_inputs for the checks.
+pub fn skip_inputs_from_fixtures(
+    fixture_path: &str,
+    trusted_block: u64,
+    target_block: u64,
+    trusting_period: Duration,
+) -> Result<SkipInputs, SkipInputsError> {
+    skip_inputs(
+        get_signed_block(fixture_path, trusted_block),
+        get_signed_block_validators(fixture_path, trusted_block),
+        get_signed_block(fixture_path, target_block),
+        get_signed_block_validators(fixture_path, target_block),
+        trusting_period,
+    )
+}
+
+/// Build the inputs of a skip from the trusted block to the target block. Fails if:
+/// - The target header's time is more than trusting_period after the trusted header's time, as the
+///   1/3 trust assumption on the trusted validator set only holds within the trusting period.
+/// - The target block's commit is not signed by more than 2/3 of its own validator set.
+/// - The trusted validators which signed the target block's commit hold at most 1/3 of the trusted
+///   validator set's voting power. The validator set can change between the blocks, so signers of
+///   the target block which aren't trusted validators don't count towards this overlap.
+pub fn skip_inputs(
+    trusted_signed_header: SignedHeader,
+    trusted_validators: Vec<Info>,
+    target_signed_header: SignedHeader,
+    target_validators: Vec<Info>,
+    trusting_period: Duration,
+) -> Result<SkipInputs, SkipInputsError> {
+    let trusted_block = trusted_signed_header.header.height.value();
+    let target_block = target_signed_header.header.height.value();
+    if target_block <= trusted_block {
+        return Err(SkipInputsError::InvalidRange {
+            trusted_block,
+            target_block,
+        });
+    }
+
+    // A target header with a time before the trusted header's is within the period.
+    let elapsed = target_signed_header
+        .header
+        .time
+        .duration_since(trusted_signed_header.header.time)
+        .unwrap_or_default();
+    if elapsed > trusting_period {
+        return Err(SkipInputsError::OutsideTrustingPeriod {
+            target_block,
+            elapsed,
+            trusting_period,
+        });
+    }
+
+    let verification = verify_commit(&target_signed_header, &target_validators);
+    if !verification.is_valid() {
+        return Err(SkipInputsError::InvalidTargetCommit(verification));
+    }
+
+    // Every signature of the target commit verifies, so the signers which aren't trusted
+    // validators are the only ones verify_commit reports as invalid against the trusted set.
+    let overlap = verify_commit(&target_signed_header, &trusted_validators);
+    if overlap.signed_voting_power as u128 * 3 <= overlap.total_voting_power as u128 {
+        return Err(SkipInputsError::InsufficientTrustedOverlap {
+            target_block,
+            overlap_voting_power: overlap.signed_voting_power,
+            trusted_voting_power: overlap.total_voting_power,
+        });
+    }
+
+    Ok(SkipInputs {
+        trusted_block,
+        trusted_header_hash: H256::from_slice(trusted_signed_header.header.hash().as_bytes()),
+        target_block,
+        target_signed_header,
+        trusted_validators,
+        target_validators,
+    })
+}
 
 #[derive(Debug, Clone)]
 pub struct CombinedSkipCircuit<
     const MAX_VALIDATOR_SET_SIZE: usize,
     const CHAIN_ID_SIZE_BYTES: usize,
-    C: TendermintConfig<CHAIN_ID_SIZE_BYTES>,
+    C: TendermintConfig<CHAIN_ID_SIZE_BYTES>
+        + HeaderLayoutConfig
+        + CheckpointConfig
+        + BlockVersionConfig,
     const NB_MAP_JOBS: usize,
     const BATCH_SIZE: usize,
 > {
@@ -23,7 +271,10 @@ pub struct CombinedSkipCircuit<
 impl<
         const MAX_VALIDATOR_SET_SIZE: usize,
         const CHAIN_ID_SIZE_BYTES: usize,
-        C: TendermintConfig<CHAIN_ID_SIZE_BYTES>,
+        C: TendermintConfig<CHAIN_ID_SIZE_BYTES>
+            + HeaderLayoutConfig
+            + CheckpointConfig
+            + BlockVersionConfig,
         const NB_MAP_JOBS: usize,
         const BATCH_SIZE: usize,
     > Circuit
@@ -32,12 +283,16 @@ impl<
     fn define<L: PlonkParameters<D>, const D: usize>(builder: &mut CircuitBuilder<L, D>)  where <<L as plonky2x::prelude::PlonkParameters<D>>::Config as plonky2x::prelude::plonky2::plonk::config::GenericConfig<D>>::Hasher: plonky2x::prelude::plonky2::plonk::config::AlgebraicHasher<<L as plonky2x::prelude::PlonkParameters<D>>::Field>{
         let trusted_block = builder.evm_read::<U64Variable>();
         let trusted_header_hash = builder.evm_read::<Bytes32Variable>();
+        builder.assert_nonzero_hash(&trusted_header_hash);
         let target_block = builder.evm_read::<U64Variable>();
 
         assert!(
             NB_MAP_JOBS * BATCH_SIZE <= C::SKIP_MAX,
             "NB_MAP_JOBS * BATCH_SIZE must be <= than SKIP_MAX"
         );
+        if let Err(e) = C::CHAIN_CONFIG.checked() {
+            panic!("invalid header layout: {}", e);
+        }
 
         let target_header_hash = builder.skip::<MAX_VALIDATOR_SET_SIZE, CHAIN_ID_SIZE_BYTES>(
             C::CHAIN_ID_BYTES,
@@ -46,16 +301,63 @@ impl<
             trusted_header_hash,
             target_block,
         );
+        builder.assert_nonzero_hash(&target_header_hash);
+        builder.assert_distinct_headers(&trusted_header_hash, &target_header_hash);
+
+        let mut input_stream = VariableStream::new();
+        input_stream.write(&trusted_block);
+        input_stream.write(&target_block);
+        let header_inputs_fetcher = SkipHeaderOffchainInputs::<MAX_VALIDATOR_SET_SIZE> {
+            chain_config: C::CHAIN_CONFIG,
+        };
+        let output_stream = builder.async_hint(input_stream, header_inputs_fetcher);
+        let header_inputs =
+            output_stream.read::<SkipHeaderInputsVariable<MAX_VALIDATOR_SET_SIZE>>(builder);
+
+        if C::ASSERT_SAME_BLOCK_VERSION {
+            builder.assert_same_block_version(
+                &trusted_header_hash,
+                &header_inputs.trusted_version_proof,
+                &target_header_hash,
+                &header_inputs.target_version_proof,
+                C::CHAIN_CONFIG,
+            );
+        }
+        if C::ASSERT_APP_VERSION_NOT_REGRESSED {
+            builder.assert_app_version_not_regressed(
+                &trusted_header_hash,
+                &header_inputs.trusted_version_proof,
+                &target_header_hash,
+                &header_inputs.target_version_proof,
+                C::CHAIN_CONFIG,
+            );
+        }
+
+        // Prove the next validator set of the target block, so trust can be handed off to it by
+        // the next skip. A next validator set without voting power would halt the chain after the
+        // handoff.
+        let next_validators_hash = builder.verify_next_validators_hash(
+            &target_header_hash,
+            &header_inputs.next_validators_hash_proof,
+            C::CHAIN_CONFIG,
+        );
+        builder.assert_next_validators_power_positive(
+            &next_validators_hash,
+            &header_inputs.next_validators,
+        );
 
         let data_commitment = builder.prove_data_commitment::<Self, NB_MAP_JOBS, BATCH_SIZE>(
             trusted_block,
             trusted_header_hash,
             target_block,
             target_header_hash,
+            C::CHAIN_CONFIG,
+            C::CHECKPOINTS,
         );
 
         builder.evm_write(target_header_hash);
         builder.evm_write(data_commitment);
+        builder.evm_write(next_validators_hash);
     }
 
     fn register_generators<L: PlonkParameters<D>, const D: usize>(
@@ -65,6 +367,8 @@ impl<
         plonky2x::prelude::plonky2::plonk::config::AlgebraicHasher<L::Field>,
     {
         generator_registry.register_async_hint::<SkipOffchainInputs<MAX_VALIDATOR_SET_SIZE>>();
+        generator_registry
+            .register_async_hint::<SkipHeaderOffchainInputs<MAX_VALIDATOR_SET_SIZE>>();
 
         generator_registry.register_async_hint::<DataCommitmentOffchainInputs<BATCH_SIZE>>();
         let mr_id = MapReduceGenerator::<
@@ -92,12 +396,164 @@ impl<
 mod tests {
     use std::env;
 
-    use ethers::types::H256;
     use plonky2x::prelude::{DefaultBuilder, GateRegistry, HintRegistry};
     use subtle_encoding::hex;
     use tendermintx::config::{Mocha4Config, MOCHA_4_CHAIN_ID_SIZE_BYTES};
 
     use super::*;
+    use crate::fixture::adversarial::{
+        with_corrupted_signature, with_insufficient_power, with_wrong_validator_hash, BlockFixture,
+    };
+    use crate::fixture::tests::FIXTURE_PATH;
+    use crate::validator::tests::make_test_validator;
+
+    #[test]
+    fn test_skip_inputs_from_fixtures() {
+        let trusted_block = 10000;
+        let target_block = 10004;
+        let inputs = skip_inputs_from_fixtures(
+            FIXTURE_PATH,
+            trusted_block,
+            target_block,
+            CELESTIA_TRUSTING_PERIOD,
+        )
+        .unwrap();
+
+        let trusted_header_hash = H256::from_slice(
+            get_signed_block(FIXTURE_PATH, trusted_block)
+                .header
+                .hash()
+                .as_bytes(),
+        );
+        assert_eq!(inputs.trusted_header_hash, trusted_header_hash);
+        assert_eq!(
+            inputs.target_signed_header,
+            get_signed_block(FIXTURE_PATH, target_block)
+        );
+        assert_eq!(
+            inputs.trusted_validators,
+            get_signed_block_validators(FIXTURE_PATH, trusted_block)
+        );
+        assert_eq!(
+            inputs.target_validators,
+            get_signed_block_validators(FIXTURE_PATH, target_block)
+        );
+
+        let evm_input = inputs.evm_input();
+        assert_eq!(evm_input.len(), 48);
+        assert_eq!(evm_input[..8], trusted_block.to_be_bytes());
+        assert_eq!(evm_input[8..40], *trusted_header_hash.as_bytes());
+        assert_eq!(evm_input[40..], target_block.to_be_bytes());
+    }
+
+    #[test]
+    fn test_skip_inputs_from_fixtures_same_block() {
+        assert_eq!(
+            skip_inputs_from_fixtures(FIXTURE_PATH, 10000, 10000, CELESTIA_TRUSTING_PERIOD),
+            Err(SkipInputsError::InvalidRange {
+                trusted_block: 10000,
+                target_block: 10000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_skip_inputs_validator_set_change() {
+        let trusted_signed_header = get_signed_block(FIXTURE_PATH, 10000);
+        let target_signed_header = get_signed_block(FIXTURE_PATH, 10004);
+        let target_validators = get_signed_block_validators(FIXTURE_PATH, 10004);
+        let skip = |trusted_validators: Vec<Info>| {
+            skip_inputs(
+                trusted_signed_header.clone(),
+                trusted_validators,
+                target_signed_header.clone(),
+                target_validators.clone(),
+                CELESTIA_TRUSTING_PERIOD,
+            )
+        };
+
+        // A trusted set with only one of the target's validators, which signed the target commit.
+        // The other target signer isn't trusted, and doesn't count towards the overlap.
+        let inputs = skip(vec![target_validators[0].clone()]).unwrap();
+        assert_eq!(inputs.target_validators, target_validators);
+
+        // A trusted set in which a validator which left the set holds 2/3 of the voting power, so
+        // the target signers hold 1/3 of the trusted voting power.
+        let mut trusted_validators = target_validators.clone();
+        trusted_validators.push(make_test_validator(100000000));
+        assert_eq!(
+            skip(trusted_validators),
+            Err(SkipInputsError::InsufficientTrustedOverlap {
+                target_block: 10004,
+                overlap_voting_power: 50000000,
+                trusted_voting_power: 150000000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_skip_inputs_invalid_target_commit() {
+        // The target commit is checked against the target validator set, so another set's commit
+        // doesn't verify.
+        let validators = get_signed_block_validators(FIXTURE_PATH, 10000);
+        let other_validators = vec![make_test_validator(1)];
+        let result = skip_inputs(
+            get_signed_block(FIXTURE_PATH, 10000),
+            validators,
+            get_signed_block(FIXTURE_PATH, 10004),
+            other_validators,
+            CELESTIA_TRUSTING_PERIOD,
+        );
+        assert!(matches!(
+            result,
+            Err(SkipInputsError::InvalidTargetCommit(verification))
+                if verification.invalid_signers.len() == 2
+        ));
+    }
+
+    #[test]
+    fn test_skip_inputs_adversarial_target() {
+        let trusted = BlockFixture::load(FIXTURE_PATH, 10000);
+        let target = BlockFixture::load(FIXTURE_PATH, 10004);
+        let skip = |target: BlockFixture| {
+            skip_inputs(
+                trusted.signed_header.clone(),
+                trusted.validators.clone(),
+                target.signed_header,
+                target.validators,
+                CELESTIA_TRUSTING_PERIOD,
+            )
+        };
+        assert!(skip(target.clone()).is_ok());
+
+        for tampered in [
+            with_insufficient_power(&target),
+            with_corrupted_signature(&target, 0),
+            with_wrong_validator_hash(&target),
+        ] {
+            assert!(matches!(
+                skip(tampered),
+                Err(SkipInputsError::InvalidTargetCommit(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_skip_inputs_from_fixtures_outside_trusting_period() {
+        // Block 10004 is 46.65 seconds after block 10000.
+        let trusting_period = Duration::from_secs(46);
+        assert_eq!(
+            skip_inputs_from_fixtures(FIXTURE_PATH, 10000, 10004, trusting_period),
+            Err(SkipInputsError::OutsideTrustingPeriod {
+                target_block: 10004,
+                elapsed: Duration::new(46, 652_440_673),
+                trusting_period,
+            })
+        );
+        assert!(
+            skip_inputs_from_fixtures(FIXTURE_PATH, 10000, 10004, Duration::from_secs(47)).is_ok()
+        );
+    }
 
     #[test]
     #[cfg_attr(feature = "ci", ignore)]
@@ -141,6 +597,61 @@ mod tests {
         circuit.test_serializers(&gate_registry, &hint_registry);
     }
 
+    #[test]
+    fn test_skip_header_inputs() {
+        const MAX_VALIDATOR_SET_SIZE: usize = 4;
+        env_logger::try_init().unwrap_or_default();
+
+        let chain_config = ChainConfig::default();
+        let mut builder = DefaultBuilder::new();
+        let trusted_header_hash = builder.read::<Bytes32Variable>();
+        let target_header_hash = builder.read::<Bytes32Variable>();
+        let header_inputs = builder.read::<SkipHeaderInputsVariable<MAX_VALIDATOR_SET_SIZE>>();
+        builder.assert_same_block_version(
+            &trusted_header_hash,
+            &header_inputs.trusted_version_proof,
+            &target_header_hash,
+            &header_inputs.target_version_proof,
+            chain_config,
+        );
+        let next_validators_hash = builder.verify_next_validators_hash(
+            &target_header_hash,
+            &header_inputs.next_validators_hash_proof,
+            chain_config,
+        );
+        builder.assert_next_validators_power_positive(
+            &next_validators_hash,
+            &header_inputs.next_validators,
+        );
+        builder.write(next_validators_hash);
+        let circuit = builder.build();
+
+        // The next validator set of block 10003 is the validator set of block 10004.
+        let trusted_header = get_signed_block(FIXTURE_PATH, 10000).header;
+        let target_header = get_signed_block(FIXTURE_PATH, 10003).header;
+        let next_validators = get_signed_block_validators(FIXTURE_PATH, 10004);
+        let mut input = circuit.input();
+        input.write::<Bytes32Variable>(H256::from_slice(trusted_header.hash().as_bytes()));
+        input.write::<Bytes32Variable>(H256::from_slice(target_header.hash().as_bytes()));
+        input.write::<SkipHeaderInputsVariable<MAX_VALIDATOR_SET_SIZE>>(skip_header_inputs::<
+            _,
+            MAX_VALIDATOR_SET_SIZE,
+        >(
+            &InputDataFetcher::default(),
+            &trusted_header,
+            &target_header,
+            &next_validators,
+            chain_config,
+        ));
+        let (proof, mut output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+
+        assert_eq!(
+            output.read::<Bytes32Variable>().as_bytes(),
+            target_header.next_validators_hash.as_bytes()
+        );
+    }
+
     fn test_header_range_template<
         const MAX_VALIDATOR_SET_SIZE: usize,
         const NB_MAP_JOBS: usize,
@@ -184,10 +695,22 @@ mod tests {
 
         circuit.verify(&proof, &input, &output);
         let target_header_hash = output.evm_read::<Bytes32Variable>();
-        println!("target_header_hash {:?}", target_header_hash);
+        log::info!("target_header_hash {:?}", target_header_hash);
 
         let data_commitment = output.evm_read::<Bytes32Variable>();
-        println!("data_commitment {:?}", data_commitment);
+        log::info!("data_commitment {:?}", data_commitment);
+
+        let next_validators_hash = output.evm_read::<Bytes32Variable>();
+        log::info!("next_validators_hash {:?}", next_validators_hash);
+        let target_signed_header = rt.block_on(async {
+            InputDataFetcher::default()
+                .get_signed_header_from_number(end_block as u64)
+                .await
+        });
+        assert_eq!(
+            next_validators_hash.as_bytes(),
+            target_signed_header.header.next_validators_hash.as_bytes()
+        );
     }
 
     #[test]
@@ -1,20 +1,34 @@
-use std::fs;
-use std::path::Path;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::{env, fmt};
 
 use async_trait::async_trait;
 use ethers::types::H256;
-use log::info;
+use log::warn;
 use plonky2x::frontend::merkle::tree::InclusionProof;
 use plonky2x::prelude::RichField;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use subtle_encoding::hex;
 use tendermint::block::signed_header::SignedHeader;
+use tendermint::block::{CommitSig, Header, Id as BlockId};
+use tendermint::merkle::proof::Proof;
+use tendermint::validator::{Info, Set as ValidatorSet};
+use tendermint::vote::{SignedVote, Type as VoteType, ValidatorIndex, Vote};
+use tendermint::{PublicKey, Signature};
 use tendermint_proto::types::BlockId as RawBlockId;
 use tendermint_proto::Protobuf;
-use tendermintx::input::tendermint_utils::CommitResponse;
 use tendermintx::input::{InputDataFetcher, InputDataMode};
 
+use crate::config::ChainConfig;
 use crate::consts::*;
+use crate::fixture::{
+    compress_fixtures, read_fixture, write_fixture, SignedBlockValidatorsResponse,
+};
+use crate::rpc::{RestRpc, TendermintRpc};
+use crate::signature::{is_small_order_encoding, Ed25519Verifier, SignatureVerifier};
+use crate::validator::select_minimal_signing_set;
 
 #[derive(Debug, Deserialize)]
 pub struct DataCommitmentResponse {
@@ -26,6 +40,21 @@ pub struct DataCommitment {
     pub data_commitment: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct BlockByHashResponse {
+    pub result: BlockByHash,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlockByHash {
+    pub block: BlockHeaderWrapper,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlockHeaderWrapper {
+    pub header: Header,
+}
+
 #[derive(Debug, Clone)]
 pub struct DataCommitmentInputs<F: RichField> {
     pub start_header_hash: [u8; 32],
@@ -36,6 +65,709 @@ pub struct DataCommitmentInputs<F: RichField> {
     pub expected_data_commitment: [u8; 32],
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputDataError {
+    /// The block's commit does not yet have more than 2/3 of the voting power signed.
+    BlockNotFinalized {
+        block_number: u64,
+        signed_voting_power: u64,
+        total_voting_power: u64,
+    },
+    /// The total voting power reported by the RPC does not equal the sum of the validators' powers.
+    TotalPowerMismatch {
+        reported_total_voting_power: u64,
+        computed_total_voting_power: u64,
+    },
+    /// A signature in the block's commit has an R point of small order.
+    SmallOrderSignature {
+        block_number: u64,
+        validator_address: String,
+    },
+    /// Validators in the set use a key type other than Ed25519, which the circuits do not support.
+    UnsupportedKeyType {
+        validators: Vec<UnsupportedValidator>,
+    },
+    /// The validator set is empty. A block always has at least one validator, and the circuits
+    /// and the validator set's merkle tree assume so.
+    EmptyValidatorSet,
+    /// A validator's voting power exceeds i64::MAX, so it is not a valid Tendermint voting power
+    /// and would overflow the circuits' voting power arithmetic.
+    VotingPowerOverflow {
+        validator_address: String,
+        voting_power: u64,
+    },
+    /// The block's header hash changed since its inputs were fetched.
+    Reorg {
+        block_number: u64,
+        expected_hash: H256,
+        actual_hash: H256,
+    },
+    /// The headers are not consecutive: block_number does not follow previous_block_number.
+    NonConsecutiveHeaders {
+        previous_block_number: u64,
+        block_number: u64,
+    },
+    /// The RPC's response is not valid JSON, or doesn't match the expected schema.
+    InvalidJson { reason: String },
+}
+
+/// A validator whose public key is not an Ed25519 key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedValidator {
+    pub address: String,
+    pub key_type: String,
+}
+
+impl fmt::Display for InputDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputDataError::BlockNotFinalized {
+                block_number,
+                signed_voting_power,
+                total_voting_power,
+            } => write!(
+                f,
+                "block {} is not finalized: {} of {} voting power signed",
+                block_number, signed_voting_power, total_voting_power
+            ),
+            InputDataError::TotalPowerMismatch {
+                reported_total_voting_power,
+                computed_total_voting_power,
+            } => write!(
+                f,
+                "reported total voting power {} does not match the validators' total voting power {}",
+                reported_total_voting_power, computed_total_voting_power
+            ),
+            InputDataError::SmallOrderSignature {
+                block_number,
+                validator_address,
+            } => write!(
+                f,
+                "signature of validator {} in block {} has a small order R",
+                validator_address, block_number
+            ),
+            InputDataError::UnsupportedKeyType { validators } => {
+                write!(f, "unsupported validator key type:")?;
+                for validator in validators {
+                    write!(f, " {} ({})", validator.address, validator.key_type)?;
+                }
+                Ok(())
+            }
+            InputDataError::EmptyValidatorSet => write!(f, "validator set is empty"),
+            InputDataError::VotingPowerOverflow {
+                validator_address,
+                voting_power,
+            } => write!(
+                f,
+                "voting power {} of validator {} exceeds i64::MAX",
+                voting_power, validator_address
+            ),
+            InputDataError::Reorg {
+                block_number,
+                expected_hash,
+                actual_hash,
+            } => write!(
+                f,
+                "block {} was reorged: expected header hash {:?}, got {:?}",
+                block_number, expected_hash, actual_hash
+            ),
+            InputDataError::NonConsecutiveHeaders {
+                previous_block_number,
+                block_number,
+            } => write!(
+                f,
+                "headers are not consecutive: block {} follows block {}",
+                block_number, previous_block_number
+            ),
+            InputDataError::InvalidJson { reason } => write!(f, "invalid JSON response: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for InputDataError {}
+
+/// Verify the total voting power reported by the RPC, if present, equals the sum of the
+/// validators' voting powers.
+pub fn verify_total_voting_power(
+    validators: &[Info],
+    reported_total_voting_power: Option<u64>,
+) -> Result<(), InputDataError> {
+    let computed_total_voting_power = validators.iter().map(|v| v.power.value()).sum::<u64>();
+    match reported_total_voting_power {
+        Some(reported_total_voting_power)
+            if reported_total_voting_power != computed_total_voting_power =>
+        {
+            Err(InputDataError::TotalPowerMismatch {
+                reported_total_voting_power,
+                computed_total_voting_power,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Verify the validator set has at least one validator. Returns EmptyValidatorSet otherwise.
+pub fn verify_nonempty_validator_set(validators: &[Info]) -> Result<(), InputDataError> {
+    if validators.is_empty() {
+        return Err(InputDataError::EmptyValidatorSet);
+    }
+    Ok(())
+}
+
+/// Compute the validators_hash committed in a header from its validator set.
+/// The hash only covers each validator's public key and voting power. Proposer priorities are
+/// excluded, so they may be missing, stale or reordered in the RPC's response.
+pub fn compute_validators_hash(validators: &[Info]) -> H256 {
+    let validator_set = ValidatorSet::without_proposer(validators.to_vec());
+    H256::from_slice(validator_set.hash().as_bytes())
+}
+
+/// The key type of an Ed25519 public key in the RPC's JSON encoding.
+pub const ED25519_KEY_TYPE: &str = "tendermint/PubKeyEd25519";
+
+/// A validator as returned by the RPC, with only the fields needed to check its key type and
+/// voting power. Validators with unsupported key types or voting powers above i64::MAX can't be
+/// deserialized into an Info, so they are checked on the raw response first.
+#[derive(Debug, Deserialize)]
+pub struct RawValidatorInfo {
+    pub address: String,
+    pub pub_key: RawPublicKey,
+    #[serde(with = "tendermint_proto::serializers::from_str")]
+    pub voting_power: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RawPublicKey {
+    #[serde(rename = "type")]
+    pub key_type: String,
+}
+
+/// Verify every validator in the set uses an Ed25519 key. Returns UnsupportedKeyType listing the
+/// offending validators otherwise.
+pub fn verify_validator_key_types(validators: &[RawValidatorInfo]) -> Result<(), InputDataError> {
+    let unsupported = validators
+        .iter()
+        .filter(|v| v.pub_key.key_type != ED25519_KEY_TYPE)
+        .map(|v| UnsupportedValidator {
+            address: v.address.clone(),
+            key_type: v.pub_key.key_type.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    if !unsupported.is_empty() {
+        return Err(InputDataError::UnsupportedKeyType {
+            validators: unsupported,
+        });
+    }
+
+    Ok(())
+}
+
+/// Verify every validator's voting power is at most i64::MAX, as the circuits treat voting powers as
+/// non-negative i64s. Returns VotingPowerOverflow for the first offending validator otherwise.
+pub fn verify_validator_voting_powers(
+    validators: &[RawValidatorInfo],
+) -> Result<(), InputDataError> {
+    match validators.iter().find(|v| v.voting_power > i64::MAX as u64) {
+        Some(v) => Err(InputDataError::VotingPowerOverflow {
+            validator_address: v.address.clone(),
+            voting_power: v.voting_power,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Parse the validator set of a block from the RPC's `signed_block` response, and verify it. Returns
+/// UnsupportedKeyType if any validator does not use an Ed25519 key, VotingPowerOverflow if a voting
+/// power exceeds i64::MAX, EmptyValidatorSet if the set is empty, TotalPowerMismatch if the
+/// reported total voting power is wrong, and InvalidJson if the response can't be parsed.
+pub fn parse_signed_block_validators(response: &str) -> Result<Vec<Info>, InputDataError> {
+    // Key types and voting powers are checked on the raw response, as validators failing them
+    // can't be deserialized into an Info.
+    let invalid_json = |e: serde_json::Error| InputDataError::InvalidJson {
+        reason: e.to_string(),
+    };
+    let v: serde_json::Value = serde_json::from_str(response).map_err(invalid_json)?;
+    let raw_validators: Vec<RawValidatorInfo> =
+        serde_json::from_value(v["result"]["validator_set"]["validators"].clone())
+            .map_err(invalid_json)?;
+    verify_validator_key_types(&raw_validators)?;
+    verify_validator_voting_powers(&raw_validators)?;
+
+    let v: SignedBlockValidatorsResponse = serde_json::from_str(response).map_err(invalid_json)?;
+    let validator_set = v.result.validator_set;
+    verify_nonempty_validator_set(&validator_set.validators)?;
+    verify_total_voting_power(&validator_set.validators, validator_set.total_voting_power)?;
+    Ok(validator_set.validators)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetchError {
+    /// The RPC response did not match the expected schema.
+    InvalidResponse { route: String, reason: String },
+    /// The RPC did not respond within the request timeout.
+    Timeout { route: String },
+    /// The request to the RPC failed, e.g. the connection was refused.
+    RequestFailed { route: String, reason: String },
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::InvalidResponse { route, reason } => {
+                write!(f, "invalid response from {}: {}", route, reason)
+            }
+            FetchError::Timeout { route } => write!(f, "request to {} timed out", route),
+            FetchError::RequestFailed { route, reason } => {
+                write!(f, "request to {} failed: {}", route, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+/// Timeout of a single RPC request. Read from `RPC_TIMEOUT_SECS`, defaults to 30 seconds.
+pub fn rpc_timeout() -> Duration {
+    let secs = env::var("RPC_TIMEOUT_SECS")
+        .unwrap_or(String::from("30"))
+        .parse::<u64>()
+        .expect("invalid RPC_TIMEOUT_SECS");
+    Duration::from_secs(secs)
+}
+
+/// Authentication sent with every RPC request, for RPC providers which require an API key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcAuth {
+    /// An `Authorization: Bearer <token>` header.
+    Bearer(String),
+    /// An `Authorization: Basic` header.
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+}
+
+/// Authentication of RPC requests. A bearer token is read from `RPC_AUTH_TOKEN`, and basic auth
+/// credentials from `RPC_AUTH_USERNAME` and `RPC_AUTH_PASSWORD`. Requests are unauthenticated if
+/// neither is set. Only requests sent by this crate's RPC client are authenticated, not those
+/// tendermintx's InputDataFetcher sends itself, e.g. get_signed_header_from_number.
+pub fn rpc_auth() -> Option<RpcAuth> {
+    if let Ok(token) = env::var("RPC_AUTH_TOKEN") {
+        return Some(RpcAuth::Bearer(token));
+    }
+    env::var("RPC_AUTH_USERNAME")
+        .ok()
+        .map(|username| RpcAuth::Basic {
+            username,
+            password: env::var("RPC_AUTH_PASSWORD").ok(),
+        })
+}
+
+// Idle connections kept open per RPC host, sized for the concurrent header range requests.
+const MAX_IDLE_CONNECTIONS_PER_HOST: usize = 200;
+
+/// Client for the Tendermint RPC. Connections are pooled and reused across requests, and a request
+/// which doesn't complete within the timeout fails with FetchError::Timeout instead of hanging.
+/// If auth is set, it's sent with every request.
+#[derive(Debug, Clone)]
+pub struct RpcClient {
+    client: reqwest::Client,
+    auth: Option<RpcAuth>,
+}
+
+impl RpcClient {
+    pub fn new(timeout: Duration, auth: Option<RpcAuth>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .pool_max_idle_per_host(MAX_IDLE_CONNECTIONS_PER_HOST)
+            .build()
+            .expect("failed to build RPC client");
+        Self { client, auth }
+    }
+
+    /// Request route from the RPC at url, and return the response body.
+    pub async fn request(&self, url: &str, route: &str) -> Result<String, FetchError> {
+        let to_fetch_error = |e: reqwest::Error| {
+            if e.is_timeout() {
+                FetchError::Timeout {
+                    route: route.to_string(),
+                }
+            } else {
+                FetchError::RequestFailed {
+                    route: route.to_string(),
+                    reason: e.to_string(),
+                }
+            }
+        };
+        let mut request = self.client.get(format!("{}/{}", url, route));
+        request = match &self.auth {
+            Some(RpcAuth::Bearer(token)) => request.bearer_auth(token),
+            Some(RpcAuth::Basic { username, password }) => {
+                request.basic_auth(username, password.as_ref())
+            }
+            None => request,
+        };
+        request
+            .send()
+            .await
+            .map_err(to_fetch_error)?
+            .text()
+            .await
+            .map_err(to_fetch_error)
+    }
+
+    /// Request route from each of urls in turn until one responds, retrying the whole list up to
+    /// max_retries times. Returns the last error if every request fails.
+    pub async fn request_with_retries(
+        &self,
+        urls: &[String],
+        route: &str,
+        max_retries: usize,
+    ) -> Result<String, FetchError> {
+        let mut result = Err(FetchError::RequestFailed {
+            route: route.to_string(),
+            reason: String::from("no RPC urls"),
+        });
+        for _ in 0..max_retries {
+            for url in urls.iter() {
+                result = self.request(url, route).await;
+                match &result {
+                    Ok(_) => return result,
+                    Err(e) => warn!("{} from {}", e, url),
+                }
+            }
+        }
+        result
+    }
+}
+
+/// The RPC client shared by all fetchers, so connections are pooled across fetchers and requests.
+pub fn rpc_client() -> &'static RpcClient {
+    static RPC_CLIENT: OnceLock<RpcClient> = OnceLock::new();
+    RPC_CLIENT.get_or_init(|| RpcClient::new(rpc_timeout(), rpc_auth()))
+}
+
+/// Parse the header from a `commit` response. Validates the response has a
+/// `result.signed_header.header` object before deserializing it.
+pub fn parse_commit_header(route: &str, res: &str) -> Result<Header, FetchError> {
+    let invalid_response = |reason: String| FetchError::InvalidResponse {
+        route: route.to_string(),
+        reason,
+    };
+
+    let v: serde_json::Value =
+        serde_json::from_str(res).map_err(|e| invalid_response(e.to_string()))?;
+    let header = &v["result"]["signed_header"]["header"];
+    if !header.is_object() {
+        return Err(invalid_response(String::from(
+            "missing result.signed_header.header",
+        )));
+    }
+    serde_json::from_value(header.clone()).map_err(|e| invalid_response(e.to_string()))
+}
+
+/// Verify more than 2/3 of the validator set's voting power signed the commit of the signed header.
+pub fn verify_commit_finalized(
+    signed_header: &SignedHeader,
+    validators: &[Info],
+) -> Result<(), InputDataError> {
+    let total_voting_power = validators.iter().map(|v| v.power.value()).sum::<u64>();
+
+    let mut signed_voting_power = 0u64;
+    for signature in signed_header.commit.signatures.iter() {
+        if let CommitSig::BlockIdFlagCommit {
+            validator_address, ..
+        } = signature
+        {
+            if let Some(validator) = validators.iter().find(|v| v.address == *validator_address) {
+                signed_voting_power += validator.power.value();
+            }
+        }
+    }
+
+    if signed_voting_power as u128 * 3 <= total_voting_power as u128 * 2 {
+        return Err(InputDataError::BlockNotFinalized {
+            block_number: signed_header.header.height.value(),
+            signed_voting_power,
+            total_voting_power,
+        });
+    }
+
+    Ok(())
+}
+
+/// Verify no signature in the commit of the signed header has an R point of small order.
+pub fn verify_commit_signatures_not_small_order(
+    signed_header: &SignedHeader,
+) -> Result<(), InputDataError> {
+    for signature in signed_header.commit.signatures.iter() {
+        if let CommitSig::BlockIdFlagCommit {
+            validator_address,
+            signature: Some(signature),
+            ..
+        } = signature
+        {
+            let sig_r: [u8; 32] = signature.as_bytes()[..32].try_into().unwrap();
+            if is_small_order_encoding(&sig_r) {
+                return Err(InputDataError::SmallOrderSignature {
+                    block_number: signed_header.header.height.value(),
+                    validator_address: validator_address.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Result of natively verifying the commit of a signed header against its validator set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitVerification {
+    pub block_number: u64,
+    /// Voting power of the validators with a valid signature on the commit.
+    pub signed_voting_power: u64,
+    pub total_voting_power: u64,
+    /// Addresses of the validators whose signature on the commit does not verify, or which signed
+    /// the commit more than once.
+    pub invalid_signers: Vec<String>,
+}
+
+impl CommitVerification {
+    /// Whether every signature verifies and more than 2/3 of the voting power signed.
+    pub fn is_valid(&self) -> bool {
+        self.invalid_signers.is_empty()
+            && self.signed_voting_power as u128 * 3 > self.total_voting_power as u128 * 2
+    }
+}
+
+impl fmt::Display for CommitVerification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block {}: {} ({}/{} voting power signed, {:.2}%)",
+            self.block_number,
+            if self.is_valid() { "pass" } else { "fail" },
+            self.signed_voting_power,
+            self.total_voting_power,
+            self.signed_voting_power as f64 * 100.0 / self.total_voting_power.max(1) as f64
+        )?;
+        for validator_address in self.invalid_signers.iter() {
+            write!(
+                f,
+                "\ninvalid signature from validator {}",
+                validator_address
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Verify the signatures on the commit of the signed header natively, and tally the voting power
+/// of the validators whose signature verifies. This is the check the circuits prove, so a block
+/// which fails it can't be proven.
+pub fn verify_commit(signed_header: &SignedHeader, validators: &[Info]) -> CommitVerification {
+    verify_commit_with_verifier(signed_header, validators, &Ed25519Verifier)
+}
+
+/// verify_commit with the signatures checked by verifier. Everything but the signature check, i.e.
+/// matching signers to validators, building the signed messages and tallying the voting power, is
+/// the same as verify_commit.
+pub fn verify_commit_with_verifier(
+    signed_header: &SignedHeader,
+    validators: &[Info],
+    verifier: &impl SignatureVerifier,
+) -> CommitVerification {
+    let commit = &signed_header.commit;
+    let total_voting_power = validators.iter().map(|v| v.power.value()).sum::<u64>();
+
+    let mut signed_voting_power = 0u64;
+    let mut invalid_signers = Vec::new();
+    let mut seen_signers = HashSet::new();
+    for (i, signature) in commit.signatures.iter().enumerate() {
+        // Absent and nil votes don't count towards the voting power which signed the block.
+        let validator_address = match signature {
+            CommitSig::BlockIdFlagCommit {
+                validator_address,
+                signature: Some(_),
+                ..
+            } => validator_address,
+            _ => continue,
+        };
+        // The signed message doesn't include the validator's index or address, so a signature
+        // copied into another slot still verifies. Count each validator's power at most once.
+        if !seen_signers.insert(*validator_address) {
+            invalid_signers.push(validator_address.to_string());
+            continue;
+        }
+        let validator = match validators.iter().find(|v| v.address == *validator_address) {
+            Some(validator) => validator,
+            None => {
+                invalid_signers.push(validator_address.to_string());
+                continue;
+            }
+        };
+
+        let (signature, message) =
+            commit_signature(signed_header, i).expect("validator signed the block");
+        if verifier.verify(
+            validator.pub_key.to_bytes().as_slice(),
+            signature.as_bytes(),
+            &message,
+        ) {
+            signed_voting_power += validator.power.value();
+        } else {
+            invalid_signers.push(validator_address.to_string());
+        }
+    }
+
+    CommitVerification {
+        block_number: signed_header.header.height.value(),
+        signed_voting_power,
+        total_voting_power,
+        invalid_signers,
+    }
+}
+
+/// The signature of the i-th validator of the commit of signed_header, and the message it signed:
+/// the length-prefixed CanonicalVote of the commit's block. Returns None if the validator did not
+/// sign the block, i.e. it is absent or voted nil.
+pub fn commit_signature(signed_header: &SignedHeader, i: usize) -> Option<(Signature, Vec<u8>)> {
+    let commit = &signed_header.commit;
+    let (validator_address, timestamp, signature) = match commit.signatures.get(i)? {
+        CommitSig::BlockIdFlagCommit {
+            validator_address,
+            timestamp,
+            signature: Some(signature),
+        } => (validator_address, timestamp, signature),
+        _ => return None,
+    };
+
+    let vote = Vote {
+        vote_type: VoteType::Precommit,
+        height: commit.height,
+        round: commit.round,
+        block_id: Some(commit.block_id),
+        timestamp: Some(*timestamp),
+        validator_address: *validator_address,
+        validator_index: ValidatorIndex::try_from(i as u32).unwrap(),
+        signature: Some(signature.clone()),
+        extension: Vec::new(),
+        extension_signature: None,
+    };
+    let message = SignedVote::from_vote(vote, signed_header.header.chain_id.clone())
+        .expect("vote has a signature")
+        .sign_bytes();
+    Some((signature.clone(), message))
+}
+
+/// How a validator voted in the commit of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignerFlag {
+    /// Signed the block.
+    Commit,
+    /// Voted nil.
+    Nil,
+    /// Did not vote.
+    Absent,
+}
+
+/// A validator's participation in the commit of a block. pubkey is only set for Ed25519 keys, the
+/// only key type the circuits support, and key_type is the key's type in the RPC's JSON encoding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SignerStatus {
+    pub pubkey: Option<H256>,
+    pub key_type: String,
+    pub power: u64,
+    pub flag: SignerFlag,
+}
+
+/// Report how each validator of the block's validator set voted in its commit. The signatures of
+/// a commit are ordered as the validator set, so validators are matched to them by index.
+/// Validators with a key type other than Ed25519 are reported without a pubkey.
+pub fn signer_report(signed_header: &SignedHeader, validators: &[Info]) -> Vec<SignerStatus> {
+    validators
+        .iter()
+        .enumerate()
+        .map(|(i, validator)| {
+            let flag = match signed_header.commit.signatures.get(i) {
+                Some(CommitSig::BlockIdFlagCommit { .. }) => SignerFlag::Commit,
+                Some(CommitSig::BlockIdFlagNil { .. }) => SignerFlag::Nil,
+                Some(CommitSig::BlockIdFlagAbsent) | None => SignerFlag::Absent,
+            };
+            let (pubkey, key_type) = match validator.pub_key {
+                PublicKey::Ed25519(key) => (
+                    Some(H256::from_slice(key.as_bytes())),
+                    String::from(ED25519_KEY_TYPE),
+                ),
+                // PublicKey is serialized tagged with its type in the RPC's JSON encoding.
+                _ => (
+                    None,
+                    serde_json::to_value(validator.pub_key)
+                        .ok()
+                        .and_then(|v| v["type"].as_str().map(String::from))
+                        .unwrap_or_default(),
+                ),
+            };
+            SignerStatus {
+                pubkey,
+                key_type,
+                power: validator.power.value(),
+                flag,
+            }
+        })
+        .collect()
+}
+
+/// Trim the commit of signed_header to a light commit, which only keeps the signatures of the
+/// minimal set of signers whose voting power exceeds 2/3 of the total voting power. The other
+/// signatures are replaced by absent votes, which carry no signature.
+/// Note: The light commit still has one entry per validator, and verifying it still needs the
+/// full validator set: the validators_hash of the header commits to every validator, whether or
+/// not its signature is in the commit, and the total voting power is over the full set.
+pub fn to_light_commit(signed_header: &SignedHeader, validators: &[Info]) -> SignedHeader {
+    let signed = signed_header
+        .commit
+        .signatures
+        .iter()
+        .map(|signature| matches!(signature, CommitSig::BlockIdFlagCommit { .. }))
+        .collect::<Vec<_>>();
+    let selected = select_minimal_signing_set(validators, &signed, 2, 3);
+
+    let mut light_signed_header = signed_header.clone();
+    for (i, signature) in light_signed_header.commit.signatures.iter_mut().enumerate() {
+        if !selected.contains(&i) {
+            *signature = CommitSig::BlockIdFlagAbsent;
+        }
+    }
+    light_signed_header
+}
+
+/// Heights in headers at which the validator set changed, i.e. whose validators_hash differs from
+/// the validators_hash of the previous header. headers must be consecutive and in order, otherwise
+/// returns NonConsecutiveHeaders.
+/// Note: A header's validators_hash always equals the next_validators_hash of the previous header,
+/// so a change is announced one block early by a next_validators_hash different from the
+/// validators_hash.
+pub fn validator_set_changes(headers: &[Header]) -> Result<Vec<u64>, InputDataError> {
+    let mut changes = Vec::new();
+    for pair in headers.windows(2) {
+        let previous_block_number = pair[0].height.value();
+        let block_number = pair[1].height.value();
+        if block_number != previous_block_number + 1 {
+            return Err(InputDataError::NonConsecutiveHeaders {
+                previous_block_number,
+                block_number,
+            });
+        }
+        if pair[1].validators_hash != pair[0].validators_hash {
+            changes.push(block_number);
+        }
+    }
+    Ok(changes)
+}
+
 #[async_trait]
 pub trait DataCommitmentInputFetcher {
     async fn get_data_commitment(&mut self, start_block: u64, end_block: u64) -> [u8; 32];
@@ -43,6 +775,10 @@ pub trait DataCommitmentInputFetcher {
     /// Get the latest block number.
     async fn get_latest_block_number(&self) -> u64;
 
+    /// Get the header of the latest block. Retries if the RPC returns a response that doesn't
+    /// match the expected schema, and returns the last error if all retries fail.
+    async fn get_latest_header(&self) -> Result<Header, FetchError>;
+
     /// Get signed headers in the range [start_block_number, end_block_number] inclusive.
     /// Note: Assumes start_block_number and end_block_number are less than or equal to the latest
     /// block number.
@@ -52,15 +788,223 @@ pub trait DataCommitmentInputFetcher {
         end_block_number: u64,
     ) -> Vec<SignedHeader>;
 
+    /// Get the signed headers of block_numbers, in the same order. The requests are sent
+    /// concurrently in batches, like get_signed_header_range.
+    async fn get_signed_headers(&self, block_numbers: &[u64]) -> Vec<SignedHeader>;
+
+    /// Get the signed header of the block with the given header hash. Queries the RPC's
+    /// hash-indexed endpoint for the block's height, then fetches the signed header at that
+    /// height and validates that it hashes to `hash`.
+    async fn get_signed_header_by_hash(&self, hash: H256) -> SignedHeader;
+
+    /// Get the heights in (start_block_number, end_block_number] at which the validator set
+    /// changed, which are the candidate targets of a skip. The headers are fetched like
+    /// get_signed_header_range, so they are saved as fixtures when the fetcher saves.
+    async fn find_validator_set_changes(
+        &self,
+        start_block_number: u64,
+        end_block_number: u64,
+    ) -> Result<Vec<u64>, InputDataError>;
+
+    /// Get the validator set of a block, verified by parse_signed_block_validators.
+    async fn get_verified_validator_set_from_number(
+        &self,
+        block_number: u64,
+    ) -> Result<Vec<Info>, InputDataError>;
+
+    /// Get the signed header of a block, verifying more than 2/3 of the voting power has signed its
+    /// commit. Returns BlockNotFinalized if the commit is incomplete, so the caller can retry, and
+    /// SmallOrderSignature if a signature in the commit has a small order R.
+    async fn get_finalized_signed_header_from_number(
+        &mut self,
+        block_number: u64,
+    ) -> Result<SignedHeader, InputDataError>;
+
     /// start_block_number and end_block_number are not guaranteed to be less than the latest_block.
     async fn get_data_commitment_inputs<const MAX_LEAVES: usize, F: RichField>(
         &mut self,
         start_block_number: u64,
         end_block_number: u64,
+        chain_config: ChainConfig,
     ) -> DataCommitmentInputs<F>;
+
+    /// Get the proof of the data_hash of header against the header.
+    fn get_data_hash_proof<F: RichField>(
+        &self,
+        header: &Header,
+        chain_config: ChainConfig,
+    ) -> InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES, F>;
+
+    /// Get the proof of the last_block_id of header against the header.
+    fn get_last_block_id_proof<F: RichField>(
+        &self,
+        header: &Header,
+        chain_config: ChainConfig,
+    ) -> InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_BLOCK_ID_SIZE_BYTES, F>;
+
+    /// Get the proof of the version of header against the header.
+    fn get_version_proof<F: RichField>(
+        &self,
+        header: &Header,
+        chain_config: ChainConfig,
+    ) -> InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_VERSION_SIZE_BYTES, F>;
+
+    /// Get the proof of the proposer_address of header against the header.
+    fn get_proposer_address_proof<F: RichField>(
+        &self,
+        header: &Header,
+        chain_config: ChainConfig,
+    ) -> InclusionProof<PROPOSER_ADDRESS_PROOF_DEPTH, PROTOBUF_ADDRESS_SIZE_BYTES, F>;
+
+    /// Get the proof of the next_validators_hash of header against the header.
+    fn get_next_validators_hash_proof<F: RichField>(
+        &self,
+        header: &Header,
+        chain_config: ChainConfig,
+    ) -> InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES, F>;
+
+    /// Get the data_hash proofs of the blocks in [start_block_number, end_block_number - 1] and the
+    /// last_block_id proofs of the blocks in [start_block_number + 1, end_block_number], padded to
+    /// MAX_LEAVES. signed_headers holds the blocks in [start_block_number, end_block_number].
+    fn get_data_commitment_proofs<const MAX_LEAVES: usize, F: RichField>(
+        &self,
+        signed_headers: &[SignedHeader],
+        start_block_number: u64,
+        end_block_number: u64,
+        chain_config: ChainConfig,
+    ) -> DataCommitmentProofs<F>;
 }
 
-const MAX_NUM_RETRIES: usize = 3;
+/// Protobuf-encode block_id as it is in the last_block_id leaf of a header. Panics if the encoding
+/// is not PROTOBUF_BLOCK_ID_SIZE_BYTES long: the 32-byte hash and the part set header's total and
+/// 32-byte hash, each with their protobuf tags and lengths.
+pub fn encode_block_id(block_id: BlockId) -> Vec<u8> {
+    let encoded_block_id = Protobuf::<RawBlockId>::encode_vec(block_id);
+    assert_eq!(
+        encoded_block_id.len(),
+        PROTOBUF_BLOCK_ID_SIZE_BYTES,
+        "encoded block id is not PROTOBUF_BLOCK_ID_SIZE_BYTES long"
+    );
+    encoded_block_id
+}
+
+/// The data_hash and last_block_id proofs of a data commitment range.
+pub type DataCommitmentProofs<F> = (
+    Vec<InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES, F>>,
+    Vec<InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_BLOCK_ID_SIZE_BYTES, F>>,
+);
+
+/// Extend data_hash_proofs and last_block_id_proofs with dummy proofs to length MAX_LEAVES.
+pub fn pad_data_commitment_proofs<const MAX_LEAVES: usize, F: RichField>(
+    data_hash_proofs: &mut Vec<InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES, F>>,
+    last_block_id_proofs: &mut Vec<
+        InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_BLOCK_ID_SIZE_BYTES, F>,
+    >,
+) {
+    for _ in data_hash_proofs.len()..MAX_LEAVES {
+        data_hash_proofs.push(
+            InclusionProof::<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES, F> {
+                proof: [H256::zero(); HEADER_PROOF_DEPTH].to_vec(),
+                leaf: [0u8; PROTOBUF_HASH_SIZE_BYTES],
+            },
+        );
+    }
+    for _ in last_block_id_proofs.len()..MAX_LEAVES {
+        last_block_id_proofs.push(InclusionProof::<
+            HEADER_PROOF_DEPTH,
+            PROTOBUF_BLOCK_ID_SIZE_BYTES,
+            F,
+        > {
+            proof: [H256::zero(); HEADER_PROOF_DEPTH].to_vec(),
+            leaf: [0u8; PROTOBUF_BLOCK_ID_SIZE_BYTES],
+        });
+    }
+}
+
+/// Assert a proof against a header is over chain_config.nb_header_fields leaves (14 for Celestia).
+/// The circuits verify header proofs with constant paths, derived from each field's index and the
+/// number of header fields by `get_path_indices`. A header with another number of fields places
+/// its leaves at other paths and depths, so its proofs must be rejected before reaching the
+/// circuit rather than be verified against the wrong path.
+pub fn assert_header_proof_total(total: u64, chain_config: ChainConfig) {
+    assert_eq!(
+        total, chain_config.nb_header_fields as u64,
+        "header proof total does not match the number of header fields"
+    );
+}
+
+/// Convert a native merkle proof of leaf against a header into the value of a
+/// MerkleInclusionProofVariable. Tendermint orders the aunts of a proof from the leaf to the root,
+/// which is the order of the path from `get_path_indices(proof.index, proof.total)` the circuit
+/// hashes the proof with.
+pub fn to_merkle_inclusion_proof<const LEAF_SIZE_BYTES: usize, F: RichField>(
+    proof: &Proof,
+    leaf: [u8; LEAF_SIZE_BYTES],
+    chain_config: ChainConfig,
+) -> InclusionProof<HEADER_PROOF_DEPTH, LEAF_SIZE_BYTES, F> {
+    assert_header_proof_total(proof.total, chain_config);
+    assert!(proof.index < proof.total);
+    assert_eq!(
+        proof.aunts.len(),
+        HEADER_PROOF_DEPTH,
+        "leaf {} of {} is not at depth HEADER_PROOF_DEPTH",
+        proof.index,
+        proof.total
+    );
+
+    let mut hasher = Sha256::new();
+    hasher.update([MERKLE_LEAF_PREFIX]);
+    hasher.update(leaf);
+    assert_eq!(
+        hasher.finalize().as_slice(),
+        proof.leaf_hash.as_bytes(),
+        "leaf does not match the leaf hash of the proof"
+    );
+
+    InclusionProof::<HEADER_PROOF_DEPTH, LEAF_SIZE_BYTES, F> {
+        proof: proof
+            .aunts
+            .iter()
+            .map(|aunt| H256::from_slice(aunt.as_bytes()))
+            .collect(),
+        leaf,
+    }
+}
+
+/// A source of the canonical header hashes of blocks.
+#[async_trait]
+pub trait HeaderHashSource: Sync {
+    async fn get_header_hash(&self, block_number: u64) -> H256;
+
+    /// Re-query the header hash of block and confirm it is still expected_hash. Call this just
+    /// before proving, as the chain may have reorged since the inputs were fetched.
+    async fn verify_block_still_canonical(
+        &self,
+        block: u64,
+        expected_hash: H256,
+    ) -> Result<(), InputDataError> {
+        let actual_hash = self.get_header_hash(block).await;
+        if actual_hash != expected_hash {
+            return Err(InputDataError::Reorg {
+                block_number: block,
+                expected_hash,
+                actual_hash,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HeaderHashSource for InputDataFetcher {
+    async fn get_header_hash(&self, block_number: u64) -> H256 {
+        let signed_header = self.get_signed_header_from_number(block_number).await;
+        H256::from_slice(signed_header.header.hash().as_bytes())
+    }
+}
+
+/// How many times a request is retried across every url before giving up.
+pub(crate) const MAX_NUM_RETRIES: usize = 3;
 
 #[async_trait]
 impl DataCommitmentInputFetcher for InputDataFetcher {
@@ -84,21 +1028,16 @@ impl DataCommitmentInputFetcher for InputDataFetcher {
         );
         let fetched_result = match &self.mode {
             InputDataMode::Rpc => {
-                let res = self.request_from_rpc(&route, MAX_NUM_RETRIES).await;
+                let res = RestRpc::new(self.urls.clone())
+                    .request(&route)
+                    .await
+                    .unwrap_or_else(|e| panic!("{}", e));
                 if self.save {
-                    // Ensure the directory exists
-                    if let Some(parent) = Path::new(&file_name).parent() {
-                        fs::create_dir_all(parent).unwrap();
-                    }
-                    fs::write(file_name.as_str(), res.as_bytes()).expect("Unable to write file");
+                    write_fixture(file_name.as_str(), &res, compress_fixtures());
                 }
                 res
             }
-            InputDataMode::Fixture => {
-                let file_content = fs::read_to_string(file_name.as_str());
-                info!("Fixture name: {}", file_name.as_str());
-                file_content.unwrap()
-            }
+            InputDataMode::Fixture => read_fixture(file_name.as_str()),
         };
         let v: DataCommitmentResponse =
             serde_json::from_str(&fetched_result).expect("Failed to parse JSON");
@@ -110,10 +1049,30 @@ impl DataCommitmentInputFetcher for InputDataFetcher {
     }
 
     async fn get_latest_block_number(&self) -> u64 {
+        let header = self
+            .get_latest_header()
+            .await
+            .unwrap_or_else(|e| panic!("{}", e));
+        header.height.into()
+    }
+
+    async fn get_latest_header(&self) -> Result<Header, FetchError> {
         let route = "commit";
-        let res = self.request_from_rpc(route, MAX_NUM_RETRIES).await;
-        let v: CommitResponse = serde_json::from_str(&res).expect("Failed to parse JSON");
-        v.result.signed_header.header.height.into()
+        let mut result = Err(FetchError::InvalidResponse {
+            route: route.to_string(),
+            reason: String::from("no response"),
+        });
+        for _ in 0..MAX_NUM_RETRIES {
+            result = RestRpc::new(self.urls.clone()).latest_header().await;
+            match &result {
+                Ok(_) => break,
+                Err(e) => {
+                    warn!("{}, retrying", e);
+                    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                }
+            }
+        }
+        result
     }
 
     // Assumes start_block_number and end_block_number are less than or equal to the latest block number.
@@ -122,40 +1081,128 @@ impl DataCommitmentInputFetcher for InputDataFetcher {
         start_block_number: u64,
         end_block_number: u64,
     ) -> Vec<SignedHeader> {
+        let block_numbers = (start_block_number..end_block_number + 1).collect::<Vec<_>>();
+        self.get_signed_headers(&block_numbers).await
+    }
+
+    async fn get_signed_headers(&self, block_numbers: &[u64]) -> Vec<SignedHeader> {
         // Note: Tested with 500+ concurrent requests, but monitor for any issues.
         const MAX_BATCH_SIZE: usize = 200;
 
         let mut signed_headers = Vec::new();
-        let mut curr_block = start_block_number;
-        while curr_block <= end_block_number {
-            let batch_end_block =
-                std::cmp::min(curr_block + MAX_BATCH_SIZE as u64, end_block_number + 1);
-            // Batch request the headers in the range [curr_block, batch_end_block).
-            let batch_signed_header_futures = (curr_block..batch_end_block)
-                .map(|i| self.get_signed_header_from_number(i))
+        for batch in block_numbers.chunks(MAX_BATCH_SIZE) {
+            // Batch request the headers of the blocks in the batch.
+            let batch_signed_header_futures = batch
+                .iter()
+                .map(|block_number| self.get_signed_header_from_number(*block_number))
                 .collect::<Vec<_>>();
             let batch_signed_headers: Vec<SignedHeader> =
                 futures::future::join_all(batch_signed_header_futures).await;
             signed_headers.extend(batch_signed_headers);
-
-            curr_block += MAX_BATCH_SIZE as u64;
         }
 
         signed_headers
     }
 
+    async fn find_validator_set_changes(
+        &self,
+        start_block_number: u64,
+        end_block_number: u64,
+    ) -> Result<Vec<u64>, InputDataError> {
+        let headers = self
+            .get_signed_header_range(start_block_number, end_block_number)
+            .await
+            .into_iter()
+            .map(|signed_header| signed_header.header)
+            .collect::<Vec<_>>();
+        validator_set_changes(&headers)
+    }
+
+    async fn get_signed_header_by_hash(&self, hash: H256) -> SignedHeader {
+        let hash_hex = String::from_utf8(hex::encode_upper(hash.as_bytes())).unwrap();
+        let file_name = format!(
+            "{}/{}/block_by_hash.json",
+            self.fixture_path,
+            hash_hex.as_str()
+        );
+        let route = format!("block_by_hash?hash=0x{}", hash_hex.as_str());
+        let fetched_result = match &self.mode {
+            InputDataMode::Rpc => {
+                let res = RestRpc::new(self.urls.clone())
+                    .request(&route)
+                    .await
+                    .unwrap_or_else(|e| panic!("{}", e));
+                if self.save {
+                    write_fixture(file_name.as_str(), &res, compress_fixtures());
+                }
+                res
+            }
+            InputDataMode::Fixture => read_fixture(file_name.as_str()),
+        };
+        let v: BlockByHashResponse =
+            serde_json::from_str(&fetched_result).expect("Failed to parse JSON");
+
+        // The block endpoint doesn't include the block's own commit, so fetch the signed header
+        // by the height of the returned block.
+        let signed_header = self
+            .get_signed_header_from_number(v.result.block.header.height.value())
+            .await;
+
+        // Validate the signed header hashes to the requested hash.
+        assert_eq!(
+            signed_header.header.hash().as_bytes(),
+            hash.as_bytes(),
+            "signed header hash does not match the requested hash"
+        );
+
+        signed_header
+    }
+
+    async fn get_verified_validator_set_from_number(
+        &self,
+        block_number: u64,
+    ) -> Result<Vec<Info>, InputDataError> {
+        let file_name = format!("{}/{}/signed_block.json", self.fixture_path, block_number);
+        let route = format!("signed_block?height={}", block_number);
+        let fetched_result = match &self.mode {
+            InputDataMode::Rpc => {
+                let res = RestRpc::new(self.urls.clone())
+                    .request(&route)
+                    .await
+                    .unwrap_or_else(|e| panic!("{}", e));
+                if self.save {
+                    write_fixture(file_name.as_str(), &res, compress_fixtures());
+                }
+                res
+            }
+            InputDataMode::Fixture => read_fixture(file_name.as_str()),
+        };
+        parse_signed_block_validators(&fetched_result)
+    }
+
+    async fn get_finalized_signed_header_from_number(
+        &mut self,
+        block_number: u64,
+    ) -> Result<SignedHeader, InputDataError> {
+        let signed_header = self.get_signed_header_from_number(block_number).await;
+        let validators = self
+            .get_verified_validator_set_from_number(block_number)
+            .await?;
+        verify_commit_finalized(&signed_header, &validators)?;
+        verify_commit_signatures_not_small_order(&signed_header)?;
+        Ok(signed_header)
+    }
+
     // start_block_number and end_block_number are not guaranteed to be less than the latest_block.
     // Fetch the latest block number, and use it to determine the actual range of signed headers to fetch.
     async fn get_data_commitment_inputs<const MAX_LEAVES: usize, F: RichField>(
         &mut self,
         start_block_number: u64,
         end_block_number: u64,
+        chain_config: ChainConfig,
     ) -> DataCommitmentInputs<F> {
         assert!(end_block_number - start_block_number <= MAX_LEAVES as u64);
 
-        let mut data_hash_proofs = Vec::new();
-        let mut last_block_id_proofs = Vec::new();
-
         // Only request up to latest_block_number - 2 (avoid RPC inconsistency).
         let latest_block_number = self.get_latest_block_number().await;
         let latest_safe_block_number = latest_block_number - 2;
@@ -164,79 +1211,13 @@ impl DataCommitmentInputFetcher for InputDataFetcher {
             .get_signed_header_range(start_block_number, request_end_block_number)
             .await;
 
-        for i in start_block_number..request_end_block_number + 1 {
-            let signed_header = &signed_headers[(i - start_block_number) as usize];
-
-            // Don't include the data hash and corresponding proof of end_block, as the circuit's
-            // data_commitment is computed over the range [start_block, end_block - 1].
-            if i < request_end_block_number {
-                let data_hash = signed_header.header.data_hash.unwrap();
-
-                let data_hash_proof = self.get_inclusion_proof::<PROTOBUF_HASH_SIZE_BYTES, F>(
-                    &signed_header.header,
-                    DATA_HASH_INDEX as u64,
-                    data_hash.encode_vec(),
-                );
-                data_hash_proofs.push(data_hash_proof);
-            }
-
-            // Don't include last_block_id of start, as the data_commitment circuit only requires
-            // the last block id's of blocks in the range [start_block + 1, end_block]. Specifically,
-            // the circuit needs the last_block_id proofs of data_commitment range shifted by one
-            // block to the right.
-            if i > start_block_number {
-                let last_block_id_proof = self
-                    .get_inclusion_proof::<PROTOBUF_BLOCK_ID_SIZE_BYTES, F>(
-                        &signed_header.header,
-                        LAST_BLOCK_ID_INDEX as u64,
-                        Protobuf::<RawBlockId>::encode_vec(
-                            signed_header.header.last_block_id.unwrap_or_default(),
-                        ),
-                    );
-                last_block_id_proofs.push(last_block_id_proof);
-            }
-        }
-
-        let mut data_hash_proofs_formatted = data_hash_proofs
-            .into_iter()
-            .map(
-                |proof| InclusionProof::<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES, F> {
-                    proof: proof.proof,
-                    leaf: proof.leaf,
-                },
-            )
-            .collect::<Vec<_>>();
-
-        let mut last_block_id_proofs_formatted = last_block_id_proofs
-            .into_iter()
-            .map(
-                |proof| InclusionProof::<HEADER_PROOF_DEPTH, PROTOBUF_BLOCK_ID_SIZE_BYTES, F> {
-                    proof: proof.proof,
-                    leaf: proof.leaf,
-                },
-            )
-            .collect::<Vec<_>>();
-
-        let num_so_far = data_hash_proofs_formatted.len();
-        // Extend data_hash_proofs and last_block_id_proofs to length MAX_LEAVES.
-        for _ in num_so_far..MAX_LEAVES {
-            data_hash_proofs_formatted.push(InclusionProof::<
-                HEADER_PROOF_DEPTH,
-                PROTOBUF_HASH_SIZE_BYTES,
-                F,
-            > {
-                proof: [H256::zero(); HEADER_PROOF_DEPTH].to_vec(),
-                leaf: [0u8; PROTOBUF_HASH_SIZE_BYTES],
-            });
-            last_block_id_proofs_formatted.push(InclusionProof::<
-                HEADER_PROOF_DEPTH,
-                PROTOBUF_BLOCK_ID_SIZE_BYTES,
-                F,
-            > {
-                proof: [H256::zero(); HEADER_PROOF_DEPTH].to_vec(),
-                leaf: [0u8; PROTOBUF_BLOCK_ID_SIZE_BYTES],
-            });
-        }
+        let (data_hash_proofs, last_block_id_proofs) = self
+            .get_data_commitment_proofs::<MAX_LEAVES, F>(
+                &signed_headers,
+                start_block_number,
+                request_end_block_number,
+                chain_config,
+            );
 
         // Fetch the expected data commitment.
         let expected_data_commitment = self
@@ -264,20 +1245,150 @@ impl DataCommitmentInputFetcher for InputDataFetcher {
         DataCommitmentInputs {
             start_header_hash: start_header,
             end_header_hash: end_header,
-            data_hash_proofs: data_hash_proofs_formatted,
-            last_block_id_proofs: last_block_id_proofs_formatted,
+            data_hash_proofs,
+            last_block_id_proofs,
             expected_data_commitment,
         }
     }
+
+    fn get_data_hash_proof<F: RichField>(
+        &self,
+        header: &Header,
+        chain_config: ChainConfig,
+    ) -> InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES, F> {
+        let proof = self.get_inclusion_proof::<PROTOBUF_HASH_SIZE_BYTES, F>(
+            header,
+            chain_config.data_hash_index as u64,
+            header.data_hash.unwrap().encode_vec(),
+        );
+        InclusionProof::<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES, F> {
+            proof: proof.proof,
+            leaf: proof.leaf,
+        }
+    }
+
+    fn get_last_block_id_proof<F: RichField>(
+        &self,
+        header: &Header,
+        chain_config: ChainConfig,
+    ) -> InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_BLOCK_ID_SIZE_BYTES, F> {
+        let proof = self.get_inclusion_proof::<PROTOBUF_BLOCK_ID_SIZE_BYTES, F>(
+            header,
+            chain_config.last_block_id_index as u64,
+            encode_block_id(header.last_block_id.unwrap_or_default()),
+        );
+        InclusionProof::<HEADER_PROOF_DEPTH, PROTOBUF_BLOCK_ID_SIZE_BYTES, F> {
+            proof: proof.proof,
+            leaf: proof.leaf,
+        }
+    }
+
+    fn get_version_proof<F: RichField>(
+        &self,
+        header: &Header,
+        chain_config: ChainConfig,
+    ) -> InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_VERSION_SIZE_BYTES, F> {
+        let proof = self.get_inclusion_proof::<PROTOBUF_VERSION_SIZE_BYTES, F>(
+            header,
+            chain_config.version_index as u64,
+            header.version.encode_vec(),
+        );
+        InclusionProof::<HEADER_PROOF_DEPTH, PROTOBUF_VERSION_SIZE_BYTES, F> {
+            proof: proof.proof,
+            leaf: proof.leaf,
+        }
+    }
+
+    fn get_proposer_address_proof<F: RichField>(
+        &self,
+        header: &Header,
+        chain_config: ChainConfig,
+    ) -> InclusionProof<PROPOSER_ADDRESS_PROOF_DEPTH, PROTOBUF_ADDRESS_SIZE_BYTES, F> {
+        let proof = self.get_inclusion_proof::<PROTOBUF_ADDRESS_SIZE_BYTES, F>(
+            header,
+            chain_config.proposer_address_index as u64,
+            header.proposer_address.encode_vec(),
+        );
+        InclusionProof::<PROPOSER_ADDRESS_PROOF_DEPTH, PROTOBUF_ADDRESS_SIZE_BYTES, F> {
+            proof: proof.proof,
+            leaf: proof.leaf,
+        }
+    }
+
+    fn get_next_validators_hash_proof<F: RichField>(
+        &self,
+        header: &Header,
+        chain_config: ChainConfig,
+    ) -> InclusionProof<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES, F> {
+        let proof = self.get_inclusion_proof::<PROTOBUF_HASH_SIZE_BYTES, F>(
+            header,
+            chain_config.next_validators_hash_index as u64,
+            header.next_validators_hash.encode_vec(),
+        );
+        InclusionProof::<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES, F> {
+            proof: proof.proof,
+            leaf: proof.leaf,
+        }
+    }
+
+    fn get_data_commitment_proofs<const MAX_LEAVES: usize, F: RichField>(
+        &self,
+        signed_headers: &[SignedHeader],
+        start_block_number: u64,
+        end_block_number: u64,
+        chain_config: ChainConfig,
+    ) -> DataCommitmentProofs<F> {
+        let mut data_hash_proofs = Vec::new();
+        let mut last_block_id_proofs = Vec::new();
+
+        for i in start_block_number..end_block_number + 1 {
+            let signed_header = &signed_headers[(i - start_block_number) as usize];
+
+            // Don't include the data hash and corresponding proof of end_block, as the circuit's
+            // data_commitment is computed over the range [start_block, end_block - 1].
+            if i < end_block_number {
+                data_hash_proofs
+                    .push(self.get_data_hash_proof(&signed_header.header, chain_config));
+            }
+
+            // Don't include last_block_id of start, as the data_commitment circuit only requires
+            // the last block id's of blocks in the range [start_block + 1, end_block]. Specifically,
+            // the circuit needs the last_block_id proofs of data_commitment range shifted by one
+            // block to the right.
+            if i > start_block_number {
+                last_block_id_proofs
+                    .push(self.get_last_block_id_proof(&signed_header.header, chain_config));
+            }
+        }
+
+        pad_data_commitment_proofs::<MAX_LEAVES, F>(
+            &mut data_hash_proofs,
+            &mut last_block_id_proofs,
+        );
+
+        (data_hash_proofs, last_block_id_proofs)
+    }
 }
 #[cfg(test)]
 mod tests {
 
     use std::env;
 
+    use ed25519_consensus::SigningKey;
     use plonky2x::backend::circuit::{DefaultParameters, PlonkParameters};
+    use tendermint::validator::ProposerPriority;
+    use tendermint::vote::Power;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
 
     use super::*;
+    use crate::fixture::tests::{
+        FIXTURE_PATH, MIXED_KEY_FIXTURE_PATH, WRONG_TOTAL_POWER_FIXTURE_PATH,
+    };
+    use crate::fixture::{
+        get_signed_block, get_signed_block_validators, try_get_signed_block_validators,
+    };
+    use crate::signature::AcceptAllVerifier;
 
     const D: usize = 2;
     type L = DefaultParameters;
@@ -294,7 +1405,741 @@ mod tests {
         let start_block = 3000000;
         let end_block = 3000010;
         let _ = fetcher
-            .get_data_commitment_inputs::<32, F>(start_block, end_block)
+            .get_data_commitment_inputs::<32, F>(start_block, end_block, ChainConfig::default())
             .await;
     }
+
+    // Ensure a block fetched by its header hash matches the block fetched by its height.
+    #[cfg_attr(feature = "ci", ignore)]
+    #[tokio::test]
+    async fn test_get_signed_header_by_hash() {
+        env::set_var("RUST_LOG", "debug");
+        env_logger::try_init().unwrap_or_default();
+        dotenv::dotenv().ok();
+        let fetcher = InputDataFetcher::default();
+        let block = 3000000;
+
+        let signed_header_by_number = fetcher.get_signed_header_from_number(block).await;
+        let hash = H256::from_slice(signed_header_by_number.header.hash().as_bytes());
+
+        let signed_header_by_hash = fetcher.get_signed_header_by_hash(hash).await;
+        assert_eq!(signed_header_by_hash, signed_header_by_number);
+    }
+
+    #[test]
+    fn test_verify_commit_finalized() {
+        let block_number = 10000;
+        let mut signed_header = get_signed_block(FIXTURE_PATH, block_number);
+        let validators = get_signed_block_validators(FIXTURE_PATH, block_number);
+        assert_eq!(verify_commit_finalized(&signed_header, &validators), Ok(()));
+
+        // With one of the two equal-power validators' signatures missing, only 1/2 of the voting
+        // power has signed.
+        signed_header.commit.signatures[1] = CommitSig::BlockIdFlagAbsent;
+        assert_eq!(
+            verify_commit_finalized(&signed_header, &validators),
+            Err(InputDataError::BlockNotFinalized {
+                block_number,
+                signed_voting_power: 25000000,
+                total_voting_power: 50000000,
+            })
+        );
+    }
+
+    /// Returns hashes[i] on the i-th query, and the last hash afterwards.
+    struct MockHeaderHashSource {
+        hashes: Vec<H256>,
+        nb_queries: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl HeaderHashSource for MockHeaderHashSource {
+        async fn get_header_hash(&self, _block_number: u64) -> H256 {
+            let i = self
+                .nb_queries
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.hashes[i.min(self.hashes.len() - 1)]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_block_still_canonical() {
+        let block = 10000;
+        let fetched_hash = H256::from_low_u64_be(1);
+        let reorged_hash = H256::from_low_u64_be(2);
+        let source = MockHeaderHashSource {
+            hashes: vec![fetched_hash, reorged_hash],
+            nb_queries: Default::default(),
+        };
+
+        assert_eq!(
+            source
+                .verify_block_still_canonical(block, fetched_hash)
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            source
+                .verify_block_still_canonical(block, fetched_hash)
+                .await,
+            Err(InputDataError::Reorg {
+                block_number: block,
+                expected_hash: fetched_hash,
+                actual_hash: reorged_hash,
+            })
+        );
+    }
+
+    // Ensure the validators_hash doesn't depend on the validators' proposer priorities.
+    #[test]
+    fn test_compute_validators_hash_ignores_proposer_priority() {
+        let block_number = 10000;
+        let expected_validators_hash = get_signed_block(FIXTURE_PATH, block_number)
+            .header
+            .validators_hash;
+        let validators = get_signed_block_validators(FIXTURE_PATH, block_number);
+        assert_eq!(
+            compute_validators_hash(&validators).as_bytes(),
+            expected_validators_hash.as_bytes()
+        );
+
+        // Shuffled proposer priorities.
+        let mut shuffled_validators = validators.clone();
+        let mut priorities = validators
+            .iter()
+            .map(|v| v.proposer_priority)
+            .collect::<Vec<_>>();
+        priorities.rotate_left(1);
+        for (validator, priority) in shuffled_validators.iter_mut().zip(priorities) {
+            validator.proposer_priority = priority;
+        }
+        assert_ne!(
+            shuffled_validators[0].proposer_priority,
+            validators[0].proposer_priority
+        );
+        assert_eq!(
+            compute_validators_hash(&shuffled_validators).as_bytes(),
+            expected_validators_hash.as_bytes()
+        );
+
+        // Proposer priorities absent from the RPC's response.
+        let content = read_fixture(&format!(
+            "{}/{}/signed_block.json",
+            FIXTURE_PATH, block_number
+        ));
+        let mut v: serde_json::Value = serde_json::from_str(&content).unwrap();
+        for validator in v["result"]["validator_set"]["validators"]
+            .as_array_mut()
+            .unwrap()
+        {
+            validator
+                .as_object_mut()
+                .unwrap()
+                .remove("proposer_priority");
+        }
+        let validators_without_priority: Vec<Info> =
+            serde_json::from_value(v["result"]["validator_set"]["validators"].clone()).unwrap();
+        for validator in validators_without_priority.iter() {
+            assert_eq!(validator.proposer_priority, ProposerPriority::default());
+        }
+        assert_eq!(
+            compute_validators_hash(&validators_without_priority).as_bytes(),
+            expected_validators_hash.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_verify_validator_key_types() {
+        let block_number = 10000;
+        assert!(try_get_signed_block_validators(FIXTURE_PATH, block_number).is_ok());
+
+        // The second validator in the mixed-key fixture uses a secp256k1 key.
+        assert_eq!(
+            try_get_signed_block_validators(MIXED_KEY_FIXTURE_PATH, block_number),
+            Err(InputDataError::UnsupportedKeyType {
+                validators: vec![UnsupportedValidator {
+                    address: String::from("762CBA617226A799D898F134DD12661C7F1129EB"),
+                    key_type: String::from("tendermint/PubKeySecp256k1"),
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_validator_voting_powers() {
+        let content = read_fixture(&format!("{}/{}/signed_block.json", FIXTURE_PATH, 10000));
+        let mut v: serde_json::Value = serde_json::from_str(&content).unwrap();
+        let validators = &mut v["result"]["validator_set"]["validators"];
+        let raw_validators: Vec<RawValidatorInfo> =
+            serde_json::from_value(validators.clone()).unwrap();
+        assert_eq!(verify_validator_voting_powers(&raw_validators), Ok(()));
+
+        // A voting power with the high bit set would be negative as an i64.
+        let voting_power = 1u64 << 63;
+        validators[1]["voting_power"] = voting_power.to_string().into();
+        let raw_validators: Vec<RawValidatorInfo> =
+            serde_json::from_value(validators.clone()).unwrap();
+        let expected_error = InputDataError::VotingPowerOverflow {
+            validator_address: raw_validators[1].address.clone(),
+            voting_power,
+        };
+        assert_eq!(
+            verify_validator_voting_powers(&raw_validators),
+            Err(expected_error.clone())
+        );
+        assert_eq!(
+            parse_signed_block_validators(&v.to_string()),
+            Err(expected_error)
+        );
+    }
+
+    // Ensure the fetch path verifies the validator set it fetches.
+    #[tokio::test]
+    async fn test_get_verified_validator_set_from_number() {
+        let block_number = 10000;
+        let mut fetcher = InputDataFetcher::default();
+        fetcher.mode = InputDataMode::Fixture;
+        fetcher.fixture_path = String::from(FIXTURE_PATH);
+        assert_eq!(
+            fetcher
+                .get_verified_validator_set_from_number(block_number)
+                .await,
+            Ok(get_signed_block_validators(FIXTURE_PATH, block_number))
+        );
+
+        fetcher.fixture_path = String::from(MIXED_KEY_FIXTURE_PATH);
+        assert!(matches!(
+            fetcher
+                .get_verified_validator_set_from_number(block_number)
+                .await,
+            Err(InputDataError::UnsupportedKeyType { .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_nonempty_validator_set() {
+        let validators = get_signed_block_validators(FIXTURE_PATH, 10000);
+        assert_eq!(verify_nonempty_validator_set(&validators), Ok(()));
+        assert_eq!(
+            verify_nonempty_validator_set(&[]),
+            Err(InputDataError::EmptyValidatorSet)
+        );
+    }
+
+    #[test]
+    fn test_parse_signed_block_validators_invalid_json() {
+        assert!(matches!(
+            parse_signed_block_validators("Internal Server Error"),
+            Err(InputDataError::InvalidJson { .. })
+        ));
+        assert!(matches!(
+            parse_signed_block_validators(r#"{"error": {"code": -32603}}"#),
+            Err(InputDataError::InvalidJson { .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_total_voting_power() {
+        let block_number = 10000;
+        assert!(try_get_signed_block_validators(FIXTURE_PATH, block_number).is_ok());
+
+        assert_eq!(
+            try_get_signed_block_validators(WRONG_TOTAL_POWER_FIXTURE_PATH, block_number),
+            Err(InputDataError::TotalPowerMismatch {
+                reported_total_voting_power: 75000000,
+                computed_total_voting_power: 50000000,
+            })
+        );
+
+        let validators = get_signed_block_validators(FIXTURE_PATH, block_number);
+        assert_eq!(
+            verify_total_voting_power(&validators, Some(50000000)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_parse_commit_header() {
+        let route = "commit";
+        let header_response = read_fixture(&format!("{}/{}/header.json", FIXTURE_PATH, 10000));
+        let v: serde_json::Value = serde_json::from_str(&header_response).unwrap();
+        let commit_response = serde_json::json!({
+            "result": { "signed_header": { "header": v["result"]["header"] } }
+        })
+        .to_string();
+        let header = parse_commit_header(route, &commit_response).unwrap();
+        assert_eq!(header.height.value(), 10000);
+
+        // A malformed body returns an error instead of panicking.
+        assert!(matches!(
+            parse_commit_header(route, "<html>502 Bad Gateway</html>"),
+            Err(FetchError::InvalidResponse { .. })
+        ));
+
+        // A body with the wrong shape returns an error.
+        assert_eq!(
+            parse_commit_header(route, &header_response),
+            Err(FetchError::InvalidResponse {
+                route: route.to_string(),
+                reason: String::from("missing result.signed_header.header"),
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_commit_signatures_not_small_order() {
+        let block_number = 10000;
+        let mut signed_header = get_signed_block(FIXTURE_PATH, block_number);
+        assert_eq!(
+            verify_commit_signatures_not_small_order(&signed_header),
+            Ok(())
+        );
+
+        // Replace the R of the second signature with the identity point.
+        let validator_address = match &mut signed_header.commit.signatures[1] {
+            CommitSig::BlockIdFlagCommit {
+                validator_address,
+                signature: Some(signature),
+                ..
+            } => {
+                let mut sig_bytes = signature.as_bytes().to_vec();
+                sig_bytes[..32].copy_from_slice(&[0u8; 32]);
+                sig_bytes[0] = 1;
+                *signature = tendermint::Signature::try_from(sig_bytes.as_slice()).unwrap();
+                validator_address.to_string()
+            }
+            _ => panic!("expected a commit signature"),
+        };
+        assert_eq!(
+            verify_commit_signatures_not_small_order(&signed_header),
+            Err(InputDataError::SmallOrderSignature {
+                block_number,
+                validator_address,
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_commit() {
+        let block_number = 10000;
+        let mut signed_header = get_signed_block(FIXTURE_PATH, block_number);
+        let validators = get_signed_block_validators(FIXTURE_PATH, block_number);
+
+        let verification = verify_commit(&signed_header, &validators);
+        assert!(verification.is_valid());
+        assert_eq!(verification.signed_voting_power, 50000000);
+        assert_eq!(
+            verification.to_string(),
+            "block 10000: pass (50000000/50000000 voting power signed, 100.00%)"
+        );
+
+        // Tamper with the second signature.
+        let validator_address = match &mut signed_header.commit.signatures[1] {
+            CommitSig::BlockIdFlagCommit {
+                validator_address,
+                signature: Some(signature),
+                ..
+            } => {
+                let mut sig_bytes = signature.as_bytes().to_vec();
+                sig_bytes[63] ^= 1;
+                *signature = tendermint::Signature::try_from(sig_bytes.as_slice()).unwrap();
+                validator_address.to_string()
+            }
+            _ => panic!("expected a commit signature"),
+        };
+        let verification = verify_commit(&signed_header, &validators);
+        assert!(!verification.is_valid());
+        assert_eq!(
+            verification.invalid_signers,
+            vec![validator_address.clone()]
+        );
+        assert_eq!(
+            verification.to_string(),
+            format!(
+                "block 10000: fail (25000000/50000000 voting power signed, 50.00%)\ninvalid signature from validator {}",
+                validator_address
+            )
+        );
+    }
+
+    #[test]
+    fn test_verify_commit_duplicate_signer() {
+        let block_number = 10000;
+        let mut signed_header = get_signed_block(FIXTURE_PATH, block_number);
+        let validators = get_signed_block_validators(FIXTURE_PATH, block_number);
+
+        // Copy the first validator's signature into the second slot. The copied signature still
+        // verifies, but the validator's power must only be counted once.
+        signed_header.commit.signatures[1] = signed_header.commit.signatures[0].clone();
+        let validator_address = match &signed_header.commit.signatures[0] {
+            CommitSig::BlockIdFlagCommit {
+                validator_address, ..
+            } => validator_address.to_string(),
+            _ => panic!("expected a commit signature"),
+        };
+        let verification = verify_commit(&signed_header, &validators);
+        assert!(!verification.is_valid());
+        assert_eq!(verification.signed_voting_power, 25000000);
+        assert_eq!(verification.invalid_signers, vec![validator_address]);
+    }
+
+    #[tokio::test]
+    async fn test_rpc_client_timeout() {
+        // A mock RPC which responds to the first request immediately, and to later requests only
+        // after a delay.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut delay = Duration::ZERO;
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let _ = stream.read(&mut buf).await;
+                    tokio::time::sleep(delay).await;
+                    let _ = stream
+                        .write_all(
+                            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}",
+                        )
+                        .await;
+                });
+                delay = Duration::from_secs(5);
+            }
+        });
+
+        let client = RpcClient::new(Duration::from_millis(500), None);
+        assert_eq!(client.request(&url, "commit").await, Ok(String::from("{}")));
+        assert_eq!(
+            client.request(&url, "commit").await,
+            Err(FetchError::Timeout {
+                route: String::from("commit"),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rpc_client_auth() {
+        // A mock RPC which rejects requests without the bearer token.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let n = stream.read(&mut buf).await.unwrap();
+                    let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                    let response: &[u8] = if request.contains("authorization: bearer secret\r\n") {
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}"
+                    } else {
+                        b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 12\r\nConnection: close\r\n\r\nunauthorized"
+                    };
+                    let _ = stream.write_all(response).await;
+                });
+            }
+        });
+
+        let client = RpcClient::new(
+            Duration::from_secs(5),
+            Some(RpcAuth::Bearer(String::from("secret"))),
+        );
+        assert_eq!(client.request(&url, "commit").await, Ok(String::from("{}")));
+
+        for auth in [None, Some(RpcAuth::Bearer(String::from("wrong")))] {
+            let client = RpcClient::new(Duration::from_secs(5), auth);
+            assert_eq!(
+                client.request(&url, "commit").await,
+                Ok(String::from("unauthorized"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_signer_report() {
+        let block_number = 10000;
+        let mut signed_header = get_signed_block(FIXTURE_PATH, block_number);
+        let validators = get_signed_block_validators(FIXTURE_PATH, block_number);
+
+        let report = signer_report(&signed_header, &validators);
+        assert_eq!(report.len(), 2);
+        for (status, validator) in report.iter().zip(validators.iter()) {
+            assert_eq!(status.flag, SignerFlag::Commit);
+            assert_eq!(status.power, validator.power.value());
+            assert_eq!(
+                status.pubkey,
+                Some(H256::from_slice(&validator.pub_key.to_bytes()))
+            );
+            assert_eq!(status.key_type, ED25519_KEY_TYPE);
+        }
+
+        // A secp256k1 key is reported by its type, without a pubkey.
+        let mut mixed_validators = validators.clone();
+        let secp256k1_key =
+            hex::decode_upper("0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798")
+                .unwrap();
+        mixed_validators[1].pub_key = PublicKey::from_raw_secp256k1(&secp256k1_key).unwrap();
+        let report = signer_report(&signed_header, &mixed_validators);
+        assert_eq!(
+            report[0].pubkey,
+            Some(H256::from_slice(&validators[0].pub_key.to_bytes()))
+        );
+        assert_eq!(report[1].pubkey, None);
+        assert_eq!(report[1].key_type, "tendermint/PubKeySecp256k1");
+        assert_eq!(report[1].flag, SignerFlag::Commit);
+
+        // The second validator voted nil, then didn't vote.
+        let nil_vote = match &signed_header.commit.signatures[1] {
+            CommitSig::BlockIdFlagCommit {
+                validator_address,
+                timestamp,
+                signature,
+            } => CommitSig::BlockIdFlagNil {
+                validator_address: *validator_address,
+                timestamp: *timestamp,
+                signature: signature.clone(),
+            },
+            _ => panic!("expected a commit signature"),
+        };
+        signed_header.commit.signatures[1] = nil_vote;
+        let flags = signer_report(&signed_header, &validators)
+            .iter()
+            .map(|status| status.flag)
+            .collect::<Vec<_>>();
+        assert_eq!(flags, vec![SignerFlag::Commit, SignerFlag::Nil]);
+
+        signed_header.commit.signatures[1] = CommitSig::BlockIdFlagAbsent;
+        let flags = signer_report(&signed_header, &validators)
+            .iter()
+            .map(|status| status.flag)
+            .collect::<Vec<_>>();
+        assert_eq!(flags, vec![SignerFlag::Commit, SignerFlag::Absent]);
+    }
+
+    #[test]
+    fn test_encode_block_id() {
+        let fetcher = InputDataFetcher::default();
+        for block_number in 10001..10005 {
+            let header = get_signed_block(FIXTURE_PATH, block_number).header;
+            let last_block_id = header.last_block_id.unwrap();
+
+            let encoded_block_id = encode_block_id(last_block_id);
+            assert_eq!(encoded_block_id.len(), PROTOBUF_BLOCK_ID_SIZE_BYTES);
+            // The header hash of the previous block follows the tag and length of the hash.
+            assert_eq!(
+                encoded_block_id[2..2 + HASH_SIZE],
+                *last_block_id.hash.as_bytes()
+            );
+
+            // The native encoding is the leaf proven against the header.
+            let proof = fetcher.get_last_block_id_proof::<F>(&header, ChainConfig::default());
+            assert_eq!(proof.leaf.to_vec(), encoded_block_id);
+            let mut root: [u8; 32] = Sha256::new()
+                .chain_update([MERKLE_LEAF_PREFIX])
+                .chain_update(&encoded_block_id)
+                .finalize()
+                .into();
+            let path = ChainConfig::default().last_block_id_path();
+            for (aunt, is_right) in proof.proof.iter().zip(path) {
+                let (left, right) = if is_right {
+                    (aunt.as_bytes(), &root[..])
+                } else {
+                    (&root[..], aunt.as_bytes())
+                };
+                root = Sha256::new()
+                    .chain_update([MERKLE_INNER_PREFIX])
+                    .chain_update(left)
+                    .chain_update(right)
+                    .finalize()
+                    .into();
+            }
+            assert_eq!(root, header.hash().as_bytes());
+        }
+    }
+
+    fn test_to_merkle_inclusion_proof_total_template(total: u64) {
+        let leaf = [1u8; PROTOBUF_HASH_SIZE_BYTES];
+        let leaf_hash = Sha256::new()
+            .chain_update([MERKLE_LEAF_PREFIX])
+            .chain_update(leaf)
+            .finalize();
+        let proof = Proof {
+            total,
+            index: DATA_HASH_INDEX as u64,
+            leaf_hash: tendermint::Hash::Sha256(leaf_hash.into()),
+            aunts: vec![tendermint::Hash::Sha256([2u8; 32]); HEADER_PROOF_DEPTH],
+        };
+        let inclusion_proof = to_merkle_inclusion_proof::<PROTOBUF_HASH_SIZE_BYTES, F>(
+            &proof,
+            leaf,
+            ChainConfig::default(),
+        );
+        assert_eq!(inclusion_proof.leaf, leaf);
+        assert_eq!(
+            inclusion_proof.proof,
+            vec![H256([2u8; 32]); HEADER_PROOF_DEPTH]
+        );
+    }
+
+    #[test]
+    fn test_to_merkle_inclusion_proof_total() {
+        assert_eq!(ChainConfig::default().nb_header_fields, 14);
+        test_to_merkle_inclusion_proof_total_template(14);
+    }
+
+    #[test]
+    #[should_panic(expected = "header proof total does not match the number of header fields")]
+    fn test_to_merkle_inclusion_proof_total_15_fields() {
+        test_to_merkle_inclusion_proof_total_template(15);
+    }
+
+    /// The fixture block re-signed by a new validator set with powers, in the order of powers.
+    /// The header commits to the new set and every validator signs, so the commit is valid.
+    fn make_signed_header(powers: &[u64]) -> (SignedHeader, Vec<Info>) {
+        let signing_keys = powers
+            .iter()
+            .map(|_| SigningKey::new(rand::thread_rng()))
+            .collect::<Vec<_>>();
+        let validators = signing_keys
+            .iter()
+            .zip(powers)
+            .map(|(key, power)| {
+                let pubkey =
+                    PublicKey::from_raw_ed25519(key.verification_key().as_bytes()).unwrap();
+                Info::new(pubkey, Power::try_from(*power).unwrap())
+            })
+            .collect::<Vec<_>>();
+
+        let mut signed_header = get_signed_block(FIXTURE_PATH, 10000);
+        signed_header.header.validators_hash =
+            tendermint::Hash::Sha256(compute_validators_hash(&validators).0);
+        signed_header.commit.block_id.hash = signed_header.header.hash();
+        let timestamp = signed_header.header.time;
+        signed_header.commit.signatures = validators
+            .iter()
+            .map(|validator| CommitSig::BlockIdFlagCommit {
+                validator_address: validator.address,
+                timestamp,
+                signature: Some(Signature::try_from([0u8; 64].as_slice()).unwrap()),
+            })
+            .collect();
+        for (i, key) in signing_keys.iter().enumerate() {
+            let (_, message) = commit_signature(&signed_header, i).unwrap();
+            let signature = Signature::try_from(key.sign(&message).to_bytes().as_slice()).unwrap();
+            if let CommitSig::BlockIdFlagCommit { signature: sig, .. } =
+                &mut signed_header.commit.signatures[i]
+            {
+                *sig = Some(signature);
+            }
+        }
+        (signed_header, validators)
+    }
+
+    #[test]
+    fn test_to_light_commit() {
+        // The fixture validators have equal voting power, so both signatures are needed.
+        let signed_header = get_signed_block(FIXTURE_PATH, 10000);
+        let validators = get_signed_block_validators(FIXTURE_PATH, 10000);
+        assert_eq!(to_light_commit(&signed_header, &validators), signed_header);
+
+        // The two largest validators hold 80 of the 100 voting power, which exceeds 2/3.
+        let (signed_header, validators) = make_signed_header(&[50, 30, 20]);
+        assert_eq!(
+            compute_validators_hash(&validators).as_bytes(),
+            signed_header.header.validators_hash.as_bytes()
+        );
+        let light_signed_header = to_light_commit(&signed_header, &validators);
+        assert_eq!(
+            light_signed_header.commit.signatures[..2],
+            signed_header.commit.signatures[..2]
+        );
+        assert_eq!(
+            light_signed_header.commit.signatures[2],
+            CommitSig::BlockIdFlagAbsent
+        );
+        assert_eq!(light_signed_header.header, signed_header.header);
+
+        // The full and light commits both verify against the full validator set.
+        let full_verification = verify_commit(&signed_header, &validators);
+        let light_verification = verify_commit(&light_signed_header, &validators);
+        assert!(full_verification.is_valid());
+        assert!(light_verification.is_valid());
+        assert_eq!(full_verification.signed_voting_power, 100);
+        assert_eq!(light_verification.signed_voting_power, 80);
+        assert_eq!(light_verification.total_voting_power, 100);
+
+        // Without the trimmed validator, the validator set no longer hashes to the header's.
+        assert_ne!(
+            compute_validators_hash(&validators[..2]).as_bytes(),
+            light_signed_header.header.validators_hash.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_validator_set_changes() {
+        let mut headers = (10000..=10004)
+            .map(|block_number| get_signed_block(FIXTURE_PATH, block_number).header)
+            .collect::<Vec<_>>();
+        // The validator set of the fixture range does not change.
+        assert!(headers
+            .windows(2)
+            .all(|pair| pair[1].validators_hash == pair[0].next_validators_hash));
+        assert_eq!(validator_set_changes(&headers), Ok(Vec::new()));
+
+        // Change the validator set from block 10003, announced by block 10002.
+        let new_validators_hash =
+            tendermint::Hash::Sha256(Sha256::digest(b"new validator set").into());
+        headers[2].next_validators_hash = new_validators_hash;
+        headers[3].validators_hash = new_validators_hash;
+        headers[3].next_validators_hash = new_validators_hash;
+        headers[4].validators_hash = new_validators_hash;
+        assert_eq!(validator_set_changes(&headers), Ok(vec![10003]));
+
+        // A gap in the headers is an error, not a change.
+        headers.remove(2);
+        assert_eq!(
+            validator_set_changes(&headers),
+            Err(InputDataError::NonConsecutiveHeaders {
+                previous_block_number: 10001,
+                block_number: 10003,
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_commit_with_accept_all_verifier() {
+        let block_number = 10000;
+        let mut signed_header = get_signed_block(FIXTURE_PATH, block_number);
+        let mut validators = get_signed_block_validators(FIXTURE_PATH, block_number);
+
+        // Tampered signatures are accepted, so the voting power of both signers is counted.
+        for signature in signed_header.commit.signatures.iter_mut() {
+            if let CommitSig::BlockIdFlagCommit {
+                signature: Some(signature),
+                ..
+            } = signature
+            {
+                *signature = tendermint::Signature::try_from([0xffu8; 64].as_slice()).unwrap();
+            }
+        }
+        assert!(!verify_commit(&signed_header, &validators).is_valid());
+        let verification =
+            verify_commit_with_verifier(&signed_header, &validators, &AcceptAllVerifier);
+        assert!(verification.is_valid());
+        assert_eq!(verification.signed_voting_power, 50000000);
+
+        // The voting power threshold is still checked.
+        signed_header.commit.signatures[1] = CommitSig::BlockIdFlagAbsent;
+        let verification =
+            verify_commit_with_verifier(&signed_header, &validators, &AcceptAllVerifier);
+        assert!(!verification.is_valid());
+        assert_eq!(verification.signed_voting_power, 25000000);
+
+        // Signers are still matched to the validator set.
+        let validator_address = validators[0].address.to_string();
+        validators.remove(0);
+        let verification =
+            verify_commit_with_verifier(&signed_header, &validators, &AcceptAllVerifier);
+        assert!(!verification.is_valid());
+        assert_eq!(verification.invalid_signers, vec![validator_address]);
+    }
 }
@@ -1,11 +1,29 @@
 #![allow(clippy::needless_range_loop)]
 #![allow(clippy::too_many_arguments)]
 
+pub mod accumulator;
 pub mod builder;
 pub mod config;
 pub mod consts;
 pub mod data_commitment;
+pub mod equivocation;
+pub mod fixture;
+pub mod header;
 pub mod header_range;
 pub mod input;
+pub mod logging;
 pub mod next_header;
+pub mod nmt;
+pub mod operator;
+pub mod output;
+pub mod profiling;
+pub mod rpc;
+pub mod sequential_chain;
+pub mod serialize;
+pub mod signature;
+pub mod stats;
+pub mod transition;
+pub mod validator;
+pub mod validator_signed;
 pub mod vars;
+pub mod vote;
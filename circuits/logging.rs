@@ -0,0 +1,89 @@
+//! Shared logging initialization for the BlobstreamX binaries.
+//!
+//! The log level is read from `LOG_LEVEL` (e.g. `debug`, `info`, `blobstreamx=debug`) and the
+//! output format is read from `LOG_FORMAT` (`text` or `json`).
+
+use std::env;
+use std::io::Write;
+
+use log::Level;
+use serde_json::json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    /// Read the log format from `LOG_FORMAT`. Defaults to `Text` if unset or unrecognized.
+    pub fn from_env() -> Self {
+        match env::var("LOG_FORMAT") {
+            Ok(format) if format.eq_ignore_ascii_case("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        }
+    }
+}
+
+/// Format a single log record as a JSON line.
+pub fn format_json_record(timestamp: &str, level: Level, target: &str, message: &str) -> String {
+    json!({
+        "timestamp": timestamp,
+        "level": level.as_str(),
+        "target": target,
+        "message": message,
+    })
+    .to_string()
+}
+
+/// Initialize the global logger. `default_level` is used if `LOG_LEVEL` is not set.
+pub fn init_logger(default_level: &str) {
+    let level = env::var("LOG_LEVEL").unwrap_or(String::from(default_level));
+
+    let mut builder = env_logger::Builder::new();
+    builder.parse_filters(&level);
+
+    if LogFormat::from_env() == LogFormat::Json {
+        builder.format(|buf, record| {
+            let timestamp = buf.timestamp().to_string();
+            writeln!(
+                buf,
+                "{}",
+                format_json_record(
+                    &timestamp,
+                    record.level(),
+                    record.target(),
+                    &record.args().to_string(),
+                )
+            )
+        });
+    }
+
+    builder.init();
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_format_json_record() {
+        let messages = [
+            "Starting BlobstreamX operator",
+            "Relay failed: \"timeout\"\n",
+        ];
+
+        for message in messages {
+            let line =
+                format_json_record("2024-01-01T00:00:00Z", Level::Info, "blobstreamx", message);
+            // Each record must be a single parseable JSON line.
+            assert!(!line.contains('\n'));
+            let v: Value = serde_json::from_str(&line).expect("Failed to parse JSON");
+            assert_eq!(v["level"], "INFO");
+            assert_eq!(v["target"], "blobstreamx");
+            assert_eq!(v["message"], message);
+        }
+    }
+}
@@ -1,17 +1,27 @@
-use plonky2x::backend::circuit::Circuit;
+use std::fmt;
+
+use ethers::types::H256;
+use plonky2x::backend::circuit::{Circuit, CircuitBuild, DefaultParameters};
 use plonky2x::frontend::uint::uint64::U64Variable;
-use plonky2x::prelude::{Bytes32Variable, CircuitBuilder, PlonkParameters};
+use plonky2x::prelude::plonky2::field::types::PrimeField64;
+use plonky2x::prelude::plonky2::plonk::proof::ProofWithPublicInputs;
+use plonky2x::prelude::{Bytes32Variable, CircuitBuilder, DefaultBuilder, PlonkParameters};
+use tendermint::block::signed_header::SignedHeader;
 use tendermintx::config::TendermintConfig;
+use tendermintx::input::InputDataFetcher;
 use tendermintx::step::{StepOffchainInputs, TendermintStepCircuit};
 
 use crate::builder::DataCommitmentBuilder;
+use crate::config::HeaderLayoutConfig;
 use crate::data_commitment::DataCommitmentOffchainInputs;
+use crate::header::HeaderBuilder;
+use crate::input::{DataCommitmentInputFetcher, HeaderHashSource, InputDataError};
 
 #[derive(Debug, Clone)]
 pub struct CombinedStepCircuit<
     const MAX_VALIDATOR_SET_SIZE: usize,
     const CHAIN_ID_SIZE_BYTES: usize,
-    C: TendermintConfig<CHAIN_ID_SIZE_BYTES>,
+    C: TendermintConfig<CHAIN_ID_SIZE_BYTES> + HeaderLayoutConfig,
 > {
     _phantom: std::marker::PhantomData<C>,
 }
@@ -19,12 +29,16 @@ pub struct CombinedStepCircuit<
 impl<
         const MAX_VALIDATOR_SET_SIZE: usize,
         const CHAIN_ID_SIZE_BYTES: usize,
-        C: TendermintConfig<CHAIN_ID_SIZE_BYTES>,
+        C: TendermintConfig<CHAIN_ID_SIZE_BYTES> + HeaderLayoutConfig,
     > Circuit for CombinedStepCircuit<MAX_VALIDATOR_SET_SIZE, CHAIN_ID_SIZE_BYTES, C>
 {
     fn define<L: PlonkParameters<D>, const D: usize>(builder: &mut CircuitBuilder<L, D>) {
         let prev_block_number = builder.evm_read::<U64Variable>();
         let prev_header_hash = builder.evm_read::<Bytes32Variable>();
+        builder.assert_nonzero_hash(&prev_header_hash);
+        if let Err(e) = C::CHAIN_CONFIG.checked() {
+            panic!("invalid header layout: {}", e);
+        }
 
         let one = builder.constant::<U64Variable>(1u64);
         let next_block_number = builder.add(prev_block_number, one);
@@ -34,12 +48,14 @@ impl<
             prev_block_number,
             prev_header_hash,
         );
+        builder.assert_nonzero_hash(&next_header_hash);
 
         // Prove the data commitment (which only includes the prev_block_number's data hash).
         let data_commitment = builder.prove_next_header_data_commitment(
             prev_block_number,
             prev_header_hash,
             next_block_number,
+            C::CHAIN_CONFIG,
         );
 
         builder.evm_write(next_header_hash);
@@ -57,16 +73,258 @@ impl<
     }
 }
 
+type L = DefaultParameters;
+const D: usize = 2;
+
+/// A proof of a step from prev_block to prev_block + 1, and its public outputs.
+#[derive(Debug, Clone)]
+pub struct StepProof {
+    pub proof: ProofWithPublicInputs<
+        <L as PlonkParameters<D>>::Field,
+        <L as PlonkParameters<D>>::Config,
+        D,
+    >,
+    pub next_header_hash: H256,
+    pub data_commitment: H256,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepProveError {
+    /// The signed header of a block of the step could not be fetched, or its commit is not
+    /// finalized.
+    Fetch { block: u64, reason: String },
+    /// The next block's header hash changed between fetching the inputs and proving, as the chain
+    /// reorged.
+    Reorg { block: u64, reason: String },
+    /// The proof of the step did not verify.
+    ProofVerification { prev_block: u64, reason: String },
+}
+
+impl fmt::Display for StepProveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepProveError::Fetch { block, reason } => {
+                write!(f, "failed to fetch block {}: {}", block, reason)
+            }
+            StepProveError::Reorg { block, reason } => {
+                write!(f, "block {} is no longer canonical: {}", block, reason)
+            }
+            StepProveError::ProofVerification { prev_block, reason } => write!(
+                f,
+                "proof of the step from block {} failed to verify: {}",
+                prev_block, reason
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StepProveError {}
+
+// The step circuit reads abi.encodePacked(prev_block, prev_header_hash) and writes
+// abi.encodePacked(next_header_hash, data_commitment).
+const STEP_INPUT_SIZE_BYTES: usize = 8 + 32;
+const STEP_OUTPUT_SIZE_BYTES: usize = 32 + 32;
+
+/// The public inputs of a step proof, decoded from its field elements, and whether the round of the
+/// next block's commit is nonzero, so it's present in the signed votes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepPublicInputs {
+    pub prev_block: u64,
+    pub prev_header_hash: H256,
+    pub next_header_hash: H256,
+    pub data_commitment: H256,
+    pub round_present: bool,
+}
+
+impl fmt::Display for StepPublicInputs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "prev_block: {}", self.prev_block)?;
+        writeln!(f, "prev_header_hash: {:?}", self.prev_header_hash)?;
+        writeln!(f, "next_header_hash: {:?}", self.next_header_hash)?;
+        writeln!(f, "data_commitment: {:?}", self.data_commitment)?;
+        write!(f, "round_present: {}", self.round_present)
+    }
+}
+
+/// Decode the public inputs of a step proof. The EVM input bytes, then the EVM output bytes, of
+/// the circuit are its public inputs, one field element per bit, most significant bit first.
+///
+/// Unlike the hashes and block number, round_present is not decoded from the field elements: the
+/// step circuit's EVM output is fixed to abi.encodePacked(next_header_hash, data_commitment) for
+/// the contract, so it doesn't commit to the round. round_present is read from the commit of
+/// next_signed_header instead, which must be the signed header of the proven next header.
+pub fn decode_public_inputs(
+    proof: &ProofWithPublicInputs<
+        <L as PlonkParameters<D>>::Field,
+        <L as PlonkParameters<D>>::Config,
+        D,
+    >,
+    next_signed_header: &SignedHeader,
+) -> StepPublicInputs {
+    assert_eq!(
+        proof.public_inputs.len(),
+        (STEP_INPUT_SIZE_BYTES + STEP_OUTPUT_SIZE_BYTES) * 8,
+        "not a step proof"
+    );
+    let bytes = proof
+        .public_inputs
+        .chunks(8)
+        .map(|bits| {
+            bits.iter().fold(0u8, |byte, bit| {
+                let bit = bit.to_canonical_u64();
+                assert!(bit <= 1, "public input is not a bit");
+                (byte << 1) | bit as u8
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let next_header_hash = H256::from_slice(&bytes[40..72]);
+    let commit = &next_signed_header.commit;
+    assert!(
+        next_signed_header.header.hash().as_bytes() == next_header_hash.as_bytes()
+            && commit.block_id.hash.as_bytes() == next_header_hash.as_bytes(),
+        "signed header is not the proven next header"
+    );
+
+    StepPublicInputs {
+        prev_block: u64::from_be_bytes(bytes[..8].try_into().unwrap()),
+        prev_header_hash: H256::from_slice(&bytes[8..40]),
+        next_header_hash,
+        data_commitment: H256::from_slice(&bytes[72..104]),
+        round_present: commit.round.value() != 0,
+    }
+}
+
+/// Builds the step circuit for config C and proves steps, fetching the inputs from the RPC. The
+/// example proves a step over RPC, so it is only compiled by the doctests.
+///
+/// ```no_run
+/// use blobstreamx::next_header::StepProver;
+/// use tendermintx::config::{Mocha4Config, MOCHA_4_CHAIN_ID_SIZE_BYTES};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// # dotenv::dotenv().ok();
+/// let mut prover = StepProver::<4, MOCHA_4_CHAIN_ID_SIZE_BYTES, Mocha4Config>::new();
+/// prover.build();
+/// let step_proof = prover.prove(500).await.unwrap();
+/// assert!(!step_proof.next_header_hash.is_zero());
+/// # }
+/// ```
+pub struct StepProver<
+    const MAX_VALIDATOR_SET_SIZE: usize,
+    const CHAIN_ID_SIZE_BYTES: usize,
+    C: TendermintConfig<CHAIN_ID_SIZE_BYTES> + HeaderLayoutConfig,
+> {
+    circuit: Option<CircuitBuild<L, D>>,
+    _phantom: std::marker::PhantomData<C>,
+}
+
+impl<
+        const MAX_VALIDATOR_SET_SIZE: usize,
+        const CHAIN_ID_SIZE_BYTES: usize,
+        C: TendermintConfig<CHAIN_ID_SIZE_BYTES> + HeaderLayoutConfig,
+    > Default for StepProver<MAX_VALIDATOR_SET_SIZE, CHAIN_ID_SIZE_BYTES, C>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<
+        const MAX_VALIDATOR_SET_SIZE: usize,
+        const CHAIN_ID_SIZE_BYTES: usize,
+        C: TendermintConfig<CHAIN_ID_SIZE_BYTES> + HeaderLayoutConfig,
+    > StepProver<MAX_VALIDATOR_SET_SIZE, CHAIN_ID_SIZE_BYTES, C>
+{
+    pub fn new() -> Self {
+        Self {
+            circuit: None,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Build the step circuit. Building is slow, so the circuit is reused across proofs.
+    pub fn build(&mut self) {
+        let mut builder = DefaultBuilder::new();
+        CombinedStepCircuit::<MAX_VALIDATOR_SET_SIZE, CHAIN_ID_SIZE_BYTES, C>::define(&mut builder);
+        self.circuit = Some(builder.build());
+    }
+
+    /// Prove the step from prev_block to prev_block + 1. Builds the circuit if it hasn't been
+    /// built yet. The commits of both blocks must be finalized.
+    pub async fn prove(&mut self, prev_block: u64) -> Result<StepProof, StepProveError> {
+        // Fetch the blocks before building the circuit, so a step which can't be proven fails
+        // fast.
+        let mut data_fetcher = InputDataFetcher::default();
+        let fetch_error = |block: u64| {
+            move |e: InputDataError| StepProveError::Fetch {
+                block,
+                reason: e.to_string(),
+            }
+        };
+        let next_block = prev_block + 1;
+        let prev_signed_header = data_fetcher
+            .get_finalized_signed_header_from_number(prev_block)
+            .await
+            .map_err(fetch_error(prev_block))?;
+        let next_signed_header = data_fetcher
+            .get_finalized_signed_header_from_number(next_block)
+            .await
+            .map_err(fetch_error(next_block))?;
+        let prev_header_hash = H256::from_slice(prev_signed_header.header.hash().as_bytes());
+
+        if self.circuit.is_none() {
+            self.build();
+        }
+        let circuit = self.circuit.as_ref().unwrap();
+
+        // Building the circuit is slow, so the chain may have reorged since the blocks were
+        // fetched.
+        let next_header_hash = H256::from_slice(next_signed_header.header.hash().as_bytes());
+        data_fetcher
+            .verify_block_still_canonical(next_block, next_header_hash)
+            .await
+            .map_err(|e| StepProveError::Reorg {
+                block: next_block,
+                reason: e.to_string(),
+            })?;
+
+        let mut input = circuit.input();
+        input.evm_write::<U64Variable>(prev_block);
+        input.evm_write::<Bytes32Variable>(prev_header_hash);
+
+        let (proof, mut output) = circuit.prove_async(&input).await;
+        circuit
+            .data
+            .verify(proof.clone())
+            .map_err(|e| StepProveError::ProofVerification {
+                prev_block,
+                reason: e.to_string(),
+            })?;
+
+        let next_header_hash = output.evm_read::<Bytes32Variable>();
+        let data_commitment = output.evm_read::<Bytes32Variable>();
+
+        Ok(StepProof {
+            proof,
+            next_header_hash,
+            data_commitment,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::env;
 
-    use ethers::types::H256;
-    use plonky2x::prelude::{DefaultBuilder, GateRegistry, HintRegistry};
+    use plonky2x::prelude::{GateRegistry, HintRegistry};
     use subtle_encoding::hex;
     use tendermintx::config::{Mocha4Config, MOCHA_4_CHAIN_ID_SIZE_BYTES};
 
     use super::*;
+    use crate::fixture::get_signed_block;
+    use crate::fixture::tests::FIXTURE_PATH;
 
     #[test]
     #[cfg_attr(feature = "ci", ignore)]
@@ -90,41 +348,139 @@ mod tests {
         circuit.test_serializers(&gate_registry, &hint_registry);
     }
 
-    fn test_next_header_template<const MAX_VALIDATOR_SET_SIZE: usize>(
-        prev_block: usize,
-        prev_header_hash: [u8; 32],
-    ) {
-        env::set_var("RUST_LOG", "debug");
+    fn test_decode_public_inputs_template(next_signed_header: &SignedHeader) -> StepPublicInputs {
         env_logger::try_init().unwrap_or_default();
 
-        let mut builder = DefaultBuilder::new();
-
-        log::debug!("Defining circuit");
-        CombinedStepCircuit::<MAX_VALIDATOR_SET_SIZE, MOCHA_4_CHAIN_ID_SIZE_BYTES, Mocha4Config>::define(&mut builder);
+        let prev_block = 10000u64;
+        let prev_header_hash = H256::from_slice(
+            get_signed_block(FIXTURE_PATH, prev_block)
+                .header
+                .hash()
+                .as_bytes(),
+        );
+        let next_header_hash = H256::from_slice(
+            get_signed_block(FIXTURE_PATH, prev_block + 1)
+                .header
+                .hash()
+                .as_bytes(),
+        );
+        let data_commitment = H256::from_low_u64_be(42);
 
-        log::debug!("Building circuit");
+        // A circuit with the EVM inputs and outputs of the step circuit.
+        let mut builder = DefaultBuilder::new();
+        builder.evm_read::<U64Variable>();
+        builder.evm_read::<Bytes32Variable>();
+        let next_header_hash_var = builder.constant::<Bytes32Variable>(next_header_hash);
+        let data_commitment_var = builder.constant::<Bytes32Variable>(data_commitment);
+        builder.evm_write(next_header_hash_var);
+        builder.evm_write(data_commitment_var);
         let circuit = builder.build();
-        log::debug!("Done building circuit");
 
         let mut input = circuit.input();
+        input.evm_write::<U64Variable>(prev_block);
+        input.evm_write::<Bytes32Variable>(prev_header_hash);
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+
+        let public_inputs = decode_public_inputs(&proof, next_signed_header);
+        assert_eq!(public_inputs.prev_block, prev_block);
+        assert_eq!(public_inputs.prev_header_hash, prev_header_hash);
+        assert_eq!(public_inputs.next_header_hash, next_header_hash);
+        assert_eq!(public_inputs.data_commitment, data_commitment);
+        public_inputs
+    }
 
-        input.evm_write::<U64Variable>(prev_block as u64);
-        input.evm_write::<Bytes32Variable>(H256::from_slice(prev_header_hash.as_slice()));
+    #[test]
+    fn test_decode_public_inputs() {
+        // The fixture blocks are all committed at round 0.
+        let next_signed_header = get_signed_block(FIXTURE_PATH, 10001);
+        assert!(!test_decode_public_inputs_template(&next_signed_header).round_present);
+    }
 
-        log::debug!("Generating proof");
+    #[tokio::test]
+    #[cfg_attr(feature = "ci", ignore)]
+    async fn test_decode_public_inputs_step_proof() {
+        env_logger::try_init().unwrap_or_default();
+        dotenv::dotenv().ok();
 
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        let (proof, mut output) = rt.block_on(async { circuit.prove_async(&input).await });
+        // This block is on Mocha-4 testnet.
+        let prev_block = 11000u64;
+        let mut prover = StepProver::<4, MOCHA_4_CHAIN_ID_SIZE_BYTES, Mocha4Config>::new();
+        let step_proof = prover.prove(prev_block).await.unwrap();
+
+        let data_fetcher = InputDataFetcher::default();
+        let prev_signed_header = data_fetcher.get_signed_header_from_number(prev_block).await;
+        let next_signed_header = data_fetcher
+            .get_signed_header_from_number(prev_block + 1)
+            .await;
+        assert_eq!(
+            decode_public_inputs(&step_proof.proof, &next_signed_header),
+            StepPublicInputs {
+                prev_block,
+                prev_header_hash: H256::from_slice(prev_signed_header.header.hash().as_bytes()),
+                next_header_hash: H256::from_slice(next_signed_header.header.hash().as_bytes()),
+                data_commitment: step_proof.data_commitment,
+                round_present: next_signed_header.commit.round.value() != 0,
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "signed header is not the proven next header")]
+    fn test_decode_public_inputs_wrong_next_header() {
+        test_decode_public_inputs_template(&get_signed_block(FIXTURE_PATH, 10002));
+    }
+
+    fn test_step_template<const MAX_VALIDATOR_SET_SIZE: usize>(
+        prev_block: u64,
+        prev_header_hash: H256,
+    ) {
+        env_logger::try_init().unwrap_or_default();
+
+        // Only the step, so a mismatch between the next header's last_block_id and
+        // prev_header_hash isn't also caught by the data commitment's data_hash proof.
+        let mut builder = DefaultBuilder::new();
+        let prev_block_number = builder.evm_read::<U64Variable>();
+        let prev_header_hash_var = builder.evm_read::<Bytes32Variable>();
+        let next_header_hash = builder.step::<MAX_VALIDATOR_SET_SIZE, MOCHA_4_CHAIN_ID_SIZE_BYTES>(
+            Mocha4Config::CHAIN_ID_BYTES,
+            prev_block_number,
+            prev_header_hash_var,
+        );
+        builder.evm_write(next_header_hash);
+        let circuit = builder.build();
 
-        log::debug!("Done generating proof");
+        let mut input = circuit.input();
+        input.evm_write::<U64Variable>(prev_block);
+        input.evm_write::<Bytes32Variable>(prev_header_hash);
 
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (proof, output) = rt.block_on(async { circuit.prove_async(&input).await });
         circuit.verify(&proof, &input, &output);
+    }
 
-        let next_header_hash = output.evm_read::<Bytes32Variable>();
-        println!("next_header_hash {:?}", next_header_hash);
+    #[test]
+    #[cfg_attr(feature = "ci", ignore)]
+    fn test_step_last_block_id() {
+        // This block is on Mocha-4 testnet.
+        let prev_header_hash = H256::from_slice(
+            &hex::decode_upper("46604E5FF15811D674CBAF2067DE6479A381EEC1BA046B90508939A685B40AE7")
+                .unwrap(),
+        );
+        test_step_template::<4>(500, prev_header_hash);
+    }
 
-        let data_commitment = output.evm_read::<Bytes32Variable>();
-        println!("data_commitment {:?}", data_commitment);
+    #[test]
+    #[cfg_attr(feature = "ci", ignore)]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_step_last_block_id_mismatch() {
+        // The hash of block 500 with its last byte flipped, so block 501's last_block_id doesn't
+        // match it.
+        let mut prev_header_hash =
+            hex::decode_upper("46604E5FF15811D674CBAF2067DE6479A381EEC1BA046B90508939A685B40AE7")
+                .unwrap();
+        prev_header_hash[31] ^= 1;
+        test_step_template::<4>(500, H256::from_slice(&prev_header_hash));
     }
 
     #[test]
@@ -134,14 +490,12 @@ mod tests {
 
         // This block is on Mocha-4 testnet.
         let start_block = 500u64;
-        let start_header_hash =
-            hex::decode_upper("46604E5FF15811D674CBAF2067DE6479A381EEC1BA046B90508939A685B40AE7")
-                .unwrap();
-
-        test_next_header_template::<MAX_VALIDATOR_SET_SIZE>(
-            start_block as usize,
-            start_header_hash.as_slice().try_into().unwrap(),
+        let start_header_hash = H256::from_slice(
+            &hex::decode_upper("46604E5FF15811D674CBAF2067DE6479A381EEC1BA046B90508939A685B40AE7")
+                .unwrap(),
         );
+
+        test_step_template::<MAX_VALIDATOR_SET_SIZE>(start_block, start_header_hash);
     }
 
     #[test]
@@ -151,14 +505,12 @@ mod tests {
 
         // This block is on Mocha-4 testnet.
         let start_block = 500u64;
-        let start_header_hash =
-            hex::decode_upper("46604E5FF15811D674CBAF2067DE6479A381EEC1BA046B90508939A685B40AE7")
-                .unwrap();
-
-        test_next_header_template::<MAX_VALIDATOR_SET_SIZE>(
-            start_block as usize,
-            start_header_hash.as_slice().try_into().unwrap(),
+        let start_header_hash = H256::from_slice(
+            &hex::decode_upper("46604E5FF15811D674CBAF2067DE6479A381EEC1BA046B90508939A685B40AE7")
+                .unwrap(),
         );
+
+        test_step_template::<MAX_VALIDATOR_SET_SIZE>(start_block, start_header_hash);
     }
 
     #[test]
@@ -168,13 +520,11 @@ mod tests {
 
         // This block is on Mocha-4 testnet.
         let start_block = 500u64;
-        let start_header_hash =
-            hex::decode_upper("46604E5FF15811D674CBAF2067DE6479A381EEC1BA046B90508939A685B40AE7")
-                .unwrap();
-
-        test_next_header_template::<MAX_VALIDATOR_SET_SIZE>(
-            start_block as usize,
-            start_header_hash.as_slice().try_into().unwrap(),
+        let start_header_hash = H256::from_slice(
+            &hex::decode_upper("46604E5FF15811D674CBAF2067DE6479A381EEC1BA046B90508939A685B40AE7")
+                .unwrap(),
         );
+
+        test_step_template::<MAX_VALIDATOR_SET_SIZE>(start_block, start_header_hash);
     }
 }
@@ -0,0 +1,515 @@
+//! Native verification of namespaced merkle tree (NMT) absence proofs against a block's data_hash.
+//!
+//! Celestia's data_hash is the root of a Tendermint merkle tree over the NMT roots of the rows and
+//! columns of the block's extended data square. An absence proof shows a namespace has no shares
+//! in a row: the leaf at the proven index has a greater namespace, and every leaf before it has a
+//! smaller namespace.
+//!
+//! The header has no square size field: Celestia's square size is part of the block data, and is
+//! only committed to by the header through data_hash. The extended data square of a square of
+//! size k is 2k wide, so data_hash is over 4k NMT roots (2k rows, then 2k columns) and each row has
+//! 2k leaves. The square size is derived from the shape of the proofs against data_hash, so a
+//! circuit proving it would check the same shape, with the row root proof depth fixed by the
+//! maximum square size. If a future header adds a square size field, it should be proven like the
+//! other header leaves (see `ChainConfig::proven_header_leaves`) and checked against this.
+
+use std::fmt;
+
+use ethers::types::H256;
+use sha2::{Digest, Sha256};
+
+use crate::config::get_path_indices;
+use crate::consts::{MERKLE_INNER_PREFIX as INNER_PREFIX, MERKLE_LEAF_PREFIX as LEAF_PREFIX};
+
+pub const NAMESPACE_SIZE_BYTES: usize = 29;
+
+/// Namespace of the parity shares of the extended data square.
+pub const PARITY_NAMESPACE: Namespace = [0xFF; NAMESPACE_SIZE_BYTES];
+
+pub type Namespace = [u8; NAMESPACE_SIZE_BYTES];
+
+/// A node of a namespaced merkle tree: the range of namespaces under the node, and its digest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NamespacedHash {
+    pub min: Namespace,
+    pub max: Namespace,
+    pub digest: [u8; 32],
+}
+
+impl NamespacedHash {
+    /// Hash of a leaf of the tree, which is a share prefixed by its namespace.
+    pub fn leaf(namespace: Namespace, data: &[u8]) -> Self {
+        let digest = Sha256::new()
+            .chain_update([LEAF_PREFIX])
+            .chain_update(namespace)
+            .chain_update(data)
+            .finalize()
+            .into();
+        Self {
+            min: namespace,
+            max: namespace,
+            digest,
+        }
+    }
+
+    /// Hash of an inner node. Returns None if the namespaces of the children are out of order.
+    /// As in Celestia's NMT, the parity namespace is ignored in the max namespace of a node, unless
+    /// the node only covers parity shares.
+    pub fn node(left: &Self, right: &Self) -> Option<Self> {
+        if left.max > right.min {
+            return None;
+        }
+        let max = if left.min == PARITY_NAMESPACE {
+            PARITY_NAMESPACE
+        } else if right.min == PARITY_NAMESPACE {
+            left.max
+        } else {
+            right.max
+        };
+        let digest = Sha256::new()
+            .chain_update([INNER_PREFIX])
+            .chain_update(left.to_bytes())
+            .chain_update(right.to_bytes())
+            .finalize()
+            .into();
+        Some(Self {
+            min: left.min,
+            max,
+            digest,
+        })
+    }
+
+    /// Serialized as min || max || digest, which is how NMT roots are committed to in data_hash.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.min[..], &self.max[..], &self.digest[..]].concat()
+    }
+}
+
+/// Proof that an NMT root is a leaf of the data_hash merkle tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowRootProof {
+    pub row_root: NamespacedHash,
+    pub index: usize,
+    pub total: usize,
+    /// Siblings on the path from the row root to data_hash, ordered from the leaf to the root.
+    pub siblings: Vec<H256>,
+}
+
+/// Proof that a namespace has no shares in the row of an NMT root. The row has a power of two
+/// number of leaves, as all rows of the extended data square do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NmtAbsenceProof {
+    /// Index of the first leaf of the row whose namespace is greater than the namespace.
+    pub index: usize,
+    pub leaf_hash: NamespacedHash,
+    /// Siblings on the path from the leaf to the row root, ordered from the leaf to the root.
+    pub siblings: Vec<NamespacedHash>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NmtError {
+    /// The row root is not a leaf of the data_hash merkle tree.
+    RowRootNotInDataHash,
+    /// The namespace is outside the range of namespaces of the row.
+    NamespaceOutOfRange,
+    /// The leaf at the proven index does not have a namespace greater than the namespace.
+    LeafNamespaceNotGreater,
+    /// A leaf before the proven index has a namespace at least the namespace.
+    PrecedingNamespaceNotLess,
+    /// The namespaces of two sibling nodes are out of order.
+    UnorderedNodes,
+    /// The proof does not hash to the row root.
+    RowRootMismatch,
+    /// The number of NMT roots and the width of the row don't match a square whose size is a power
+    /// of two.
+    InvalidSquareSize,
+}
+
+impl fmt::Display for NmtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NmtError::RowRootNotInDataHash => {
+                write!(f, "row root is not committed to by data_hash")
+            }
+            NmtError::NamespaceOutOfRange => {
+                write!(f, "namespace is outside the namespace range of the row")
+            }
+            NmtError::LeafNamespaceNotGreater => {
+                write!(
+                    f,
+                    "proven leaf's namespace is not greater than the namespace"
+                )
+            }
+            NmtError::PrecedingNamespaceNotLess => {
+                write!(
+                    f,
+                    "a preceding leaf's namespace is not less than the namespace"
+                )
+            }
+            NmtError::UnorderedNodes => write!(f, "sibling namespaces are out of order"),
+            NmtError::RowRootMismatch => write!(f, "absence proof does not match the row root"),
+            NmtError::InvalidSquareSize => {
+                write!(f, "proofs don't match the shape of an extended data square")
+            }
+        }
+    }
+}
+
+impl std::error::Error for NmtError {}
+
+/// Verify a Tendermint merkle proof of leaf against root.
+fn verify_merkle_proof(
+    root: H256,
+    leaf: &[u8],
+    index: usize,
+    total: usize,
+    siblings: &[H256],
+) -> bool {
+    if index >= total {
+        return false;
+    }
+    let path = get_path_indices(index, total);
+    if path.len() != siblings.len() {
+        return false;
+    }
+    let mut node: [u8; 32] = Sha256::new()
+        .chain_update([LEAF_PREFIX])
+        .chain_update(leaf)
+        .finalize()
+        .into();
+    for (is_right, sibling) in path.iter().zip(siblings.iter()) {
+        let (left, right) = if *is_right {
+            (sibling.as_bytes(), &node[..])
+        } else {
+            (&node[..], sibling.as_bytes())
+        };
+        node = Sha256::new()
+            .chain_update([INNER_PREFIX])
+            .chain_update(left)
+            .chain_update(right)
+            .finalize()
+            .into();
+    }
+    H256(node) == root
+}
+
+/// Verify namespace has no shares in the row whose NMT root is committed to by data_hash.
+pub fn verify_nmt_absence(
+    data_hash: H256,
+    namespace: &Namespace,
+    row_root_proof: &RowRootProof,
+    absence_proof: &NmtAbsenceProof,
+) -> Result<(), NmtError> {
+    let row_root = &row_root_proof.row_root;
+    if !verify_merkle_proof(
+        data_hash,
+        &row_root.to_bytes(),
+        row_root_proof.index,
+        row_root_proof.total,
+        &row_root_proof.siblings,
+    ) {
+        return Err(NmtError::RowRootNotInDataHash);
+    }
+
+    // Absence of a namespace outside the row's range doesn't need a proof, but says nothing about
+    // the other rows, so only namespaces within the row's range are accepted.
+    if *namespace < row_root.min || *namespace > row_root.max {
+        return Err(NmtError::NamespaceOutOfRange);
+    }
+    if absence_proof.leaf_hash.min <= *namespace {
+        return Err(NmtError::LeafNamespaceNotGreater);
+    }
+
+    // Leaves after the proven leaf have greater namespaces as the nodes are ordered, so it is
+    // enough to check the left siblings only cover smaller namespaces.
+    let mut node = absence_proof.leaf_hash;
+    for (level, sibling) in absence_proof.siblings.iter().enumerate() {
+        let is_right = (absence_proof.index >> level) & 1 == 1;
+        node = if is_right {
+            if sibling.max >= *namespace {
+                return Err(NmtError::PrecedingNamespaceNotLess);
+            }
+            NamespacedHash::node(sibling, &node)
+        } else {
+            NamespacedHash::node(&node, sibling)
+        }
+        .ok_or(NmtError::UnorderedNodes)?;
+    }
+    // The index must fit in the depth of the proof. Shifting by the bit width or more is zero.
+    let index_above_depth = u32::try_from(absence_proof.siblings.len())
+        .ok()
+        .and_then(|depth| absence_proof.index.checked_shr(depth))
+        .unwrap_or(0);
+    if index_above_depth != 0 || node != *row_root {
+        return Err(NmtError::RowRootMismatch);
+    }
+
+    Ok(())
+}
+
+/// Derive the size of the original data square from the proof of a row root against data_hash and
+/// a proof against that row root. data_hash is over the 4k row and column roots of a square of size
+/// k, which must be a power of two, and each row has 2k leaves.
+/// Note: The proofs themselves must be verified, e.g. with verify_nmt_absence.
+pub fn square_size(
+    row_root_proof: &RowRootProof,
+    absence_proof: &NmtAbsenceProof,
+) -> Result<usize, NmtError> {
+    let total = row_root_proof.total;
+    if total % 4 != 0 || !(total / 4).is_power_of_two() {
+        return Err(NmtError::InvalidSquareSize);
+    }
+    let square_size = total / 4;
+    // Row roots are the first half of the roots.
+    if row_root_proof.index >= 2 * square_size {
+        return Err(NmtError::InvalidSquareSize);
+    }
+    // A row of 2k leaves has a proof of depth log2(2k). The depth comes from the proof, so it may
+    // be too large to shift by.
+    let row_width = u32::try_from(absence_proof.siblings.len())
+        .ok()
+        .and_then(|depth| 1usize.checked_shl(depth))
+        .ok_or(NmtError::InvalidSquareSize)?;
+    if row_width != 2 * square_size {
+        return Err(NmtError::InvalidSquareSize);
+    }
+    Ok(square_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn namespace(id: u8) -> Namespace {
+        let mut namespace = [0u8; NAMESPACE_SIZE_BYTES];
+        namespace[NAMESPACE_SIZE_BYTES - 1] = id;
+        namespace
+    }
+
+    /// The levels of the NMT over leaves, from the leaves up to the root.
+    fn nmt_levels(leaves: &[NamespacedHash]) -> Vec<Vec<NamespacedHash>> {
+        let mut levels = vec![leaves.to_vec()];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| NamespacedHash::node(&pair[0], &pair[1]).unwrap())
+                .collect();
+            levels.push(level);
+        }
+        levels
+    }
+
+    /// Tendermint merkle root of leaves and the proof of the leaf at index.
+    fn merkle_root_and_proof(leaves: &[Vec<u8>], index: usize) -> (H256, Vec<H256>) {
+        let hash = |prefix: u8, data: &[&[u8]]| {
+            let mut hasher = Sha256::new().chain_update([prefix]);
+            for d in data {
+                hasher.update(d);
+            }
+            H256(hasher.finalize().into())
+        };
+        if leaves.len() == 1 {
+            return (hash(LEAF_PREFIX, &[&leaves[0]]), Vec::new());
+        }
+        let split_point = leaves.len().next_power_of_two() / 2;
+        let (left, left_proof) = merkle_root_and_proof(&leaves[..split_point], index);
+        let (right, right_proof) =
+            merkle_root_and_proof(&leaves[split_point..], index.saturating_sub(split_point));
+        let mut proof = if index < split_point {
+            left_proof
+        } else {
+            right_proof
+        };
+        proof.push(if index < split_point { right } else { left });
+        (
+            hash(INNER_PREFIX, &[left.as_bytes(), right.as_bytes()]),
+            proof,
+        )
+    }
+
+    /// The data_hash over the roots of 4 rows of 8 shares, where the second half of each row is
+    /// parity shares. Row 1 has no shares of namespace 4, and its share of namespace 5 is at
+    /// index 2.
+    fn setup() -> (H256, RowRootProof, NmtAbsenceProof) {
+        let rows: Vec<Vec<u8>> = vec![
+            vec![1, 1, 1, 1],
+            vec![2, 3, 5, 6],
+            vec![7, 7, 8, 8],
+            vec![9, 9, 9, 9],
+        ];
+        let row_levels = rows
+            .iter()
+            .map(|namespaces| {
+                let leaves = namespaces
+                    .iter()
+                    .map(|id| namespace(*id))
+                    .chain(std::iter::repeat(PARITY_NAMESPACE))
+                    .take(8)
+                    .enumerate()
+                    .map(|(i, ns)| NamespacedHash::leaf(ns, &[i as u8; 16]))
+                    .collect::<Vec<_>>();
+                nmt_levels(&leaves)
+            })
+            .collect::<Vec<_>>();
+        let row_roots = row_levels
+            .iter()
+            .map(|levels| levels.last().unwrap()[0])
+            .collect::<Vec<_>>();
+
+        let row_index = 1;
+        let (data_hash, siblings) = merkle_root_and_proof(
+            &row_roots.iter().map(|r| r.to_bytes()).collect::<Vec<_>>(),
+            row_index,
+        );
+        let row_root_proof = RowRootProof {
+            row_root: row_roots[row_index],
+            index: row_index,
+            total: row_roots.len(),
+            siblings,
+        };
+
+        let leaf_index = 2;
+        let levels = &row_levels[row_index];
+        let absence_proof = NmtAbsenceProof {
+            index: leaf_index,
+            leaf_hash: levels[0][leaf_index],
+            siblings: (0..levels.len() - 1)
+                .map(|level| levels[level][(leaf_index >> level) ^ 1])
+                .collect(),
+        };
+        (data_hash, row_root_proof, absence_proof)
+    }
+
+    #[test]
+    fn test_verify_nmt_absence() {
+        let (data_hash, row_root_proof, absence_proof) = setup();
+        assert_eq!(
+            verify_nmt_absence(data_hash, &namespace(4), &row_root_proof, &absence_proof),
+            Ok(())
+        );
+
+        // Namespace 5 is present, and namespace 3 is present before the proven leaf.
+        assert_eq!(
+            verify_nmt_absence(data_hash, &namespace(5), &row_root_proof, &absence_proof),
+            Err(NmtError::LeafNamespaceNotGreater)
+        );
+        assert_eq!(
+            verify_nmt_absence(data_hash, &namespace(3), &row_root_proof, &absence_proof),
+            Err(NmtError::PrecedingNamespaceNotLess)
+        );
+        // Namespace 8 is outside the range of the row.
+        assert_eq!(
+            verify_nmt_absence(data_hash, &namespace(8), &row_root_proof, &absence_proof),
+            Err(NmtError::NamespaceOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_verify_nmt_absence_invalid_proof() {
+        let (data_hash, row_root_proof, absence_proof) = setup();
+
+        let mut wrong_data_hash = data_hash;
+        wrong_data_hash.0[0] ^= 1;
+        assert_eq!(
+            verify_nmt_absence(
+                wrong_data_hash,
+                &namespace(4),
+                &row_root_proof,
+                &absence_proof
+            ),
+            Err(NmtError::RowRootNotInDataHash)
+        );
+
+        // A leaf hash claiming a namespace absent from the row doesn't hash to the row root.
+        let mut forged_proof = absence_proof.clone();
+        forged_proof.leaf_hash.digest[0] ^= 1;
+        assert_eq!(
+            verify_nmt_absence(data_hash, &namespace(4), &row_root_proof, &forged_proof),
+            Err(NmtError::RowRootMismatch)
+        );
+
+        let mut wrong_index_proof = absence_proof;
+        wrong_index_proof.index = 3;
+        assert!(verify_nmt_absence(
+            data_hash,
+            &namespace(4),
+            &row_root_proof,
+            &wrong_index_proof
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_square_size() {
+        // A square of size 2 is extended to 4 rows of 4 shares, with 4 row and 4 column roots.
+        let roots = (0..8u8)
+            .map(|i| {
+                let leaves = [namespace(3 * i + 1), namespace(3 * i + 3)]
+                    .into_iter()
+                    .chain([PARITY_NAMESPACE; 2])
+                    .enumerate()
+                    .map(|(j, ns)| NamespacedHash::leaf(ns, &[i, j as u8]))
+                    .collect::<Vec<_>>();
+                nmt_levels(&leaves)
+            })
+            .collect::<Vec<_>>();
+        let row_index = 1;
+        let (data_hash, siblings) = merkle_root_and_proof(
+            &roots
+                .iter()
+                .map(|levels| levels.last().unwrap()[0].to_bytes())
+                .collect::<Vec<_>>(),
+            row_index,
+        );
+        let row_root_proof = RowRootProof {
+            row_root: roots[row_index].last().unwrap()[0],
+            index: row_index,
+            total: roots.len(),
+            siblings,
+        };
+        // Row 1 has shares of namespaces 4 and 6, so namespace 5 is absent.
+        let leaf_index = 1;
+        let levels = &roots[row_index];
+        let absence_proof = NmtAbsenceProof {
+            index: leaf_index,
+            leaf_hash: levels[0][leaf_index],
+            siblings: (0..levels.len() - 1)
+                .map(|level| levels[level][(leaf_index >> level) ^ 1])
+                .collect(),
+        };
+        assert_eq!(
+            verify_nmt_absence(data_hash, &namespace(5), &row_root_proof, &absence_proof),
+            Ok(())
+        );
+        assert_eq!(square_size(&row_root_proof, &absence_proof), Ok(2));
+
+        // A column root is not a row root.
+        let mut column_root_proof = row_root_proof;
+        column_root_proof.index = 4;
+        assert_eq!(
+            square_size(&column_root_proof, &absence_proof),
+            Err(NmtError::InvalidSquareSize)
+        );
+
+        // A proof deeper than the bit width of a row's width is an error, not an overflow.
+        let mut row_root_proof = column_root_proof;
+        row_root_proof.index = row_index;
+        let deep_absence_proof = NmtAbsenceProof {
+            siblings: vec![absence_proof.leaf_hash; usize::BITS as usize],
+            ..absence_proof
+        };
+        assert_eq!(
+            square_size(&row_root_proof, &deep_absence_proof),
+            Err(NmtError::InvalidSquareSize)
+        );
+
+        // The rows of the other fixture are wider than its number of roots allows.
+        let (_, row_root_proof, absence_proof) = setup();
+        assert_eq!(
+            square_size(&row_root_proof, &absence_proof),
+            Err(NmtError::InvalidSquareSize)
+        );
+    }
+}
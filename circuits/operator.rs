@@ -0,0 +1,571 @@
+//! Liveness reporting, graceful shutdown, gas estimation and event validation for the BlobstreamX
+//! operator.
+//!
+//! The operator runs one request per iteration of its loop. On SIGINT or SIGTERM, the current
+//! iteration is allowed to finish and the loop exits instead of sleeping until the next one.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use ethers::abi::{encode, Token};
+use ethers::providers::Middleware;
+use ethers::types::{Bytes, Filter, Log, TransactionRequest, H160, H256, U256, U64};
+use ethers::utils::keccak256;
+use log::{info, warn};
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+
+/// Tracks the last time the operator successfully completed a request. Cloned handles share the
+/// same state.
+#[derive(Debug, Clone, Default)]
+pub struct HealthState {
+    // Unix timestamp in seconds, 0 if no request has succeeded yet.
+    last_success_secs: Arc<AtomicU64>,
+}
+
+impl HealthState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that a request succeeded now.
+    pub fn record_success(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time is before the unix epoch")
+            .as_secs();
+        self.last_success_secs.store(now, Ordering::Relaxed);
+    }
+
+    /// Unix timestamp in seconds of the last successful request, if any.
+    pub fn last_success(&self) -> Option<u64> {
+        match self.last_success_secs.load(Ordering::Relaxed) {
+            0 => None,
+            secs => Some(secs),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        json!({
+            "status": "ok",
+            "last_successful_request": self.last_success(),
+        })
+        .to_string()
+    }
+}
+
+/// Serve the health state as JSON over HTTP on listener until shutdown is signalled. Every path
+/// returns the health state.
+pub async fn serve_health(
+    listener: TcpListener,
+    state: HealthState,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        tokio::select! {
+            res = listener.accept() => {
+                let mut stream = match res {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("Failed to accept health check connection: {}", e);
+                        continue;
+                    }
+                };
+                let body = state.to_json();
+                tokio::spawn(async move {
+                    // The request is not parsed, only drained before responding.
+                    let mut buf = [0u8; 1024];
+                    let _ = stream.read(&mut buf).await;
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    if let Err(e) = stream.write_all(response.as_bytes()).await {
+                        warn!("Failed to respond to health check: {}", e);
+                    }
+                });
+            }
+            _ = shutdown.changed() => return,
+        }
+    }
+}
+
+/// Resolves once SIGINT or SIGTERM is received.
+pub async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm =
+            signal(SignalKind::terminate()).expect("failed to install SIGTERM handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = sigterm.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// Spawn a task which signals shutdown on the returned receiver once SIGINT or SIGTERM is
+/// received.
+pub fn spawn_shutdown_listener() -> watch::Receiver<bool> {
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    tokio::spawn(async move {
+        wait_for_shutdown_signal().await;
+        info!("Shutdown signal received, finishing the current request");
+        let _ = shutdown_tx.send(true);
+    });
+    shutdown_rx
+}
+
+/// A single iteration of the operator loop.
+#[async_trait]
+pub trait OperatorTask: Send {
+    async fn run_once(&mut self);
+}
+
+/// Run task until shutdown is signalled, sleeping loop_delay between iterations. Shutdown never
+/// interrupts an iteration: the current iteration completes, then the loop exits.
+pub async fn run_until_shutdown<T: OperatorTask>(
+    task: &mut T,
+    loop_delay: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    while !*shutdown.borrow() {
+        task.run_once().await;
+
+        tokio::select! {
+            _ = tokio::time::sleep(loop_delay) => {}
+            res = shutdown.changed() => {
+                if res.is_err() {
+                    // The sender was dropped, so shutdown can no longer be signalled.
+                    tokio::time::sleep(loop_delay).await;
+                }
+            }
+        }
+    }
+    info!("Operator loop exited");
+}
+
+/// Signature of the gateway's fulfillCall, through which a relayer submits the proof of a request
+/// and the gateway calls back the contract.
+const FULFILL_CALL_SIGNATURE: &str = "fulfillCall(bytes32,bytes,bytes,bytes,address,bytes)";
+
+/// The arguments of the gateway's fulfillCall for a proven request: the function id and input of
+/// the request, the output and proof of the function, and the callback into the contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FulfillCall {
+    pub function_id: H256,
+    pub input: Bytes,
+    pub output: Bytes,
+    pub proof: Bytes,
+    pub callback_address: H160,
+    pub callback_data: Bytes,
+}
+
+impl FulfillCall {
+    /// The calldata of the gateway's fulfillCall with these arguments.
+    pub fn calldata(&self) -> Bytes {
+        let selector = &keccak256(FULFILL_CALL_SIGNATURE)[..4];
+        let args = encode(&[
+            Token::FixedBytes(self.function_id.as_bytes().to_vec()),
+            Token::Bytes(self.input.to_vec()),
+            Token::Bytes(self.output.to_vec()),
+            Token::Bytes(self.proof.to_vec()),
+            Token::Address(self.callback_address),
+            Token::Bytes(self.callback_data.to_vec()),
+        ]);
+        Bytes::from([selector, args.as_slice()].concat())
+    }
+}
+
+/// Estimate the gas of relaying the proof of a request, by calling the gateway's fulfillCall with
+/// call from relayer, without sending the transaction. The contract's commit functions can only
+/// be called by the gateway while it verifies a proof, so they can't be estimated directly.
+///
+/// The operator loop doesn't call this yet: it needs the request's output and proof, and the
+/// SuccinctClient's relay_proof fetches and submits them internally without returning them. Until
+/// the client exposes the proof of a request, the per-request gas log of the operator is not
+/// implemented.
+pub async fn estimate_fulfill_gas<M: Middleware>(
+    provider: &M,
+    gateway: H160,
+    call: &FulfillCall,
+    relayer: Option<H160>,
+) -> anyhow::Result<U256> {
+    let mut tx = TransactionRequest::new().to(gateway).data(call.calldata());
+    if let Some(relayer) = relayer {
+        tx = tx.from(relayer);
+    }
+    provider
+        .estimate_gas(&tx.into(), None)
+        .await
+        .map_err(|e| anyhow!("failed to estimate gas: {}", e))
+}
+
+/// Signature of the contract's DataCommitmentStored event.
+const DATA_COMMITMENT_STORED_SIGNATURE: &str =
+    "DataCommitmentStored(uint256,uint64,uint64,bytes32)";
+
+/// Interval between polls for the contract's logs.
+const LOG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The topics and data of the DataCommitmentStored event the contract emits when it stores
+/// data_commitment for the block range [start_block, end_block) with proof_nonce. The block range
+/// and data commitment are indexed, so only the proof nonce is in the data.
+pub fn expected_commitment_event(
+    proof_nonce: U256,
+    start_block: u64,
+    end_block: u64,
+    data_commitment: H256,
+) -> (Vec<H256>, Bytes) {
+    let topics = vec![
+        H256(keccak256(DATA_COMMITMENT_STORED_SIGNATURE)),
+        H256::from_low_u64_be(start_block),
+        H256::from_low_u64_be(end_block),
+        data_commitment,
+    ];
+    let data = Bytes::from(encode(&[Token::Uint(proof_nonce)]));
+    (topics, data)
+}
+
+/// Check log is a DataCommitmentStored event of contract with topics and data, as computed by
+/// expected_commitment_event.
+pub fn validate_commitment_event(
+    log: &Log,
+    contract: H160,
+    topics: &[H256],
+    data: &Bytes,
+) -> anyhow::Result<()> {
+    if log.address != contract || log.topics.first() != topics.first() {
+        return Err(anyhow!(
+            "log of transaction {:?} is not a DataCommitmentStored event of {:?}",
+            log.transaction_hash,
+            contract
+        ));
+    }
+    if log.topics != topics || log.data != *data {
+        return Err(anyhow!(
+            "DataCommitmentStored event of transaction {:?} does not match the proven commitment: \
+             expected topics {:?} and data {}, got topics {:?} and data {}",
+            log.transaction_hash,
+            topics,
+            data,
+            log.topics,
+            log.data
+        ));
+    }
+    Ok(())
+}
+
+/// Wait up to timeout for contract to emit the DataCommitmentStored event of the block range in
+/// topics, from from_block on, and validate it with validate_commitment_event. The event is found
+/// by its block range, so a stored commitment which differs from the proven one is reported rather
+/// than waited on.
+pub async fn await_commitment_event<M: Middleware>(
+    provider: &M,
+    contract: H160,
+    from_block: U64,
+    topics: &[H256],
+    data: &Bytes,
+    timeout: Duration,
+) -> anyhow::Result<Log> {
+    let filter = Filter::new()
+        .address(contract)
+        .from_block(from_block)
+        .topic0(topics[0])
+        .topic1(topics[1])
+        .topic2(topics[2]);
+    let log = tokio::time::timeout(timeout, async {
+        loop {
+            let logs = provider
+                .get_logs(&filter)
+                .await
+                .map_err(|e| anyhow!("failed to get logs of {:?}: {}", contract, e))?;
+            match logs.into_iter().next() {
+                Some(log) => return Ok::<_, anyhow::Error>(log),
+                None => tokio::time::sleep(LOG_POLL_INTERVAL).await,
+            }
+        }
+    })
+    .await
+    .map_err(|_| {
+        anyhow!(
+            "timed out waiting for the DataCommitmentStored event of blocks [{}, {})",
+            topics[1].to_low_u64_be(),
+            topics[2].to_low_u64_be()
+        )
+    })??;
+    validate_commitment_event(&log, contract, topics, data)?;
+    Ok(log)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use ethers::abi::{decode, ParamType};
+    use ethers::providers::Provider;
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::fixture::read_fixture;
+    use crate::fixture::tests::FIXTURE_PATH;
+
+    // The DataCommitmentStored event of a commitment to blocks [10000, 10004) with proof nonce 1.
+    const PROOF_NONCE: u64 = 1;
+    const START_BLOCK: u64 = 10000;
+    const END_BLOCK: u64 = 10004;
+
+    const CONTRACT: H160 = H160([0xa8; 20]);
+
+    // A log of the event with topics and data, as the contract emits it.
+    fn make_log(topics: Vec<H256>, data: Bytes) -> Log {
+        Log {
+            address: CONTRACT,
+            topics,
+            data,
+            transaction_hash: Some(H256::repeat_byte(1)),
+            ..Default::default()
+        }
+    }
+
+    fn get_data_commitment_fixture() -> H256 {
+        let data_commitment = read_fixture(&format!(
+            "{}/{}-{}/data_commitment.json",
+            FIXTURE_PATH, START_BLOCK, END_BLOCK
+        ));
+        let v: serde_json::Value = serde_json::from_str(&data_commitment).unwrap();
+        v["result"]["data_commitment"]
+            .as_str()
+            .unwrap()
+            .parse::<H256>()
+            .unwrap()
+    }
+
+    struct MockTask {
+        started: Arc<AtomicUsize>,
+        completed: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl OperatorTask for MockTask {
+        async fn run_once(&mut self) {
+            self.started.fetch_add(1, Ordering::SeqCst);
+            // Simulate a slow request.
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            self.completed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_finishes_current_request() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let completed = Arc::new(AtomicUsize::new(0));
+        let mut task = MockTask {
+            started: started.clone(),
+            completed: completed.clone(),
+        };
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let handle = tokio::spawn(async move {
+            run_until_shutdown(&mut task, Duration::from_secs(3600), shutdown_rx).await;
+        });
+
+        // Signal shutdown in the middle of the first request.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert_eq!(completed.load(Ordering::SeqCst), 0);
+        shutdown_tx.send(true).unwrap();
+
+        // The loop exits without panicking and without waiting for the loop delay.
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .expect("operator loop did not exit after shutdown")
+            .expect("operator loop panicked");
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert_eq!(completed.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_serve_health() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = HealthState::new();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let server = tokio::spawn(serve_health(listener, state.clone(), shutdown_rx));
+
+        async fn get(addr: std::net::SocketAddr) -> String {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        }
+
+        let response = get(addr).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\"last_successful_request\":null"));
+
+        state.record_success();
+        let response = get(addr).await;
+        assert!(response.contains(&format!(
+            "\"last_successful_request\":{}",
+            state.last_success().unwrap()
+        )));
+
+        shutdown_tx.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("health server did not exit after shutdown")
+            .unwrap();
+    }
+
+    fn make_fulfill_call() -> FulfillCall {
+        FulfillCall {
+            function_id: H256::repeat_byte(1),
+            input: Bytes::from(vec![2; 40]),
+            output: Bytes::from(vec![3; 64]),
+            proof: Bytes::from(vec![4; 3]),
+            callback_address: H160::from_low_u64_be(5),
+            callback_data: Bytes::from(vec![0xde, 0xad, 0xbe, 0xef]),
+        }
+    }
+
+    #[test]
+    fn test_fulfill_call_calldata() {
+        let call = make_fulfill_call();
+        let calldata = call.calldata();
+        assert_eq!(
+            &calldata[..4],
+            &keccak256("fulfillCall(bytes32,bytes,bytes,bytes,address,bytes)")[..4]
+        );
+
+        let tokens = decode(
+            &[
+                ParamType::FixedBytes(32),
+                ParamType::Bytes,
+                ParamType::Bytes,
+                ParamType::Bytes,
+                ParamType::Address,
+                ParamType::Bytes,
+            ],
+            &calldata[4..],
+        )
+        .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::FixedBytes(call.function_id.as_bytes().to_vec()),
+                Token::Bytes(call.input.to_vec()),
+                Token::Bytes(call.output.to_vec()),
+                Token::Bytes(call.proof.to_vec()),
+                Token::Address(call.callback_address),
+                Token::Bytes(call.callback_data.to_vec()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_estimate_fulfill_gas() {
+        let (provider, mock) = Provider::mocked();
+        mock.push::<U256, _>(U256::from(250000u64)).unwrap();
+
+        let call = make_fulfill_call();
+        let gas = estimate_fulfill_gas(
+            &provider,
+            H160::from_low_u64_be(1),
+            &call,
+            Some(H160::from_low_u64_be(2)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(gas, U256::from(250000u64));
+    }
+
+    #[test]
+    fn test_validate_commitment_event() {
+        let (topics, data) = expected_commitment_event(
+            U256::from(PROOF_NONCE),
+            START_BLOCK,
+            END_BLOCK,
+            get_data_commitment_fixture(),
+        );
+        let log = make_log(topics.clone(), data.clone());
+        validate_commitment_event(&log, CONTRACT, &topics, &data).unwrap();
+
+        // A different commitment, nonce or contract is rejected.
+        let (wrong_topics, _) = expected_commitment_event(
+            U256::from(PROOF_NONCE),
+            START_BLOCK,
+            END_BLOCK,
+            H256::zero(),
+        );
+        assert!(validate_commitment_event(&log, CONTRACT, &wrong_topics, &data).is_err());
+        let (_, wrong_data) = expected_commitment_event(
+            U256::from(PROOF_NONCE + 1),
+            START_BLOCK,
+            END_BLOCK,
+            get_data_commitment_fixture(),
+        );
+        assert!(validate_commitment_event(&log, CONTRACT, &topics, &wrong_data).is_err());
+        assert!(validate_commitment_event(&log, H160::from_low_u64_be(1), &topics, &data).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_await_commitment_event() {
+        let (topics, data) = expected_commitment_event(
+            U256::from(PROOF_NONCE),
+            START_BLOCK,
+            END_BLOCK,
+            get_data_commitment_fixture(),
+        );
+        let log = make_log(topics.clone(), data.clone());
+
+        let (provider, mock) = Provider::mocked();
+        mock.push::<Vec<Log>, _>(vec![log.clone()]).unwrap();
+        let awaited = await_commitment_event(
+            &provider,
+            CONTRACT,
+            U64::zero(),
+            &topics,
+            &data,
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(awaited, log);
+
+        // The stored commitment of the block range differs from the proven one.
+        let (stored_topics, _) = expected_commitment_event(
+            U256::from(PROOF_NONCE),
+            START_BLOCK,
+            END_BLOCK,
+            H256::zero(),
+        );
+        mock.push::<Vec<Log>, _>(vec![make_log(stored_topics, data.clone())])
+            .unwrap();
+        assert!(await_commitment_event(
+            &provider,
+            CONTRACT,
+            U64::zero(),
+            &topics,
+            &data,
+            Duration::from_secs(5),
+        )
+        .await
+        .is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! Writing circuit inputs to disk in formats other tools can read. JSON is always available,
+//! bincode and MessagePack are behind the `bincode` and `msgpack` features.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::anyhow;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The serialization format of inputs written with write_inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    #[cfg(feature = "bincode")]
+    Bincode,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+/// Write inputs to path in format.
+pub fn write_inputs<T: Serialize>(
+    inputs: &T,
+    format: OutputFormat,
+    path: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    let bytes = match format {
+        OutputFormat::Json => serde_json::to_vec_pretty(inputs)?,
+        #[cfg(feature = "bincode")]
+        OutputFormat::Bincode => {
+            bincode::serialize(inputs).map_err(|e| anyhow!("failed to encode bincode: {}", e))?
+        }
+        #[cfg(feature = "msgpack")]
+        OutputFormat::MessagePack => rmp_serde::to_vec_named(inputs)
+            .map_err(|e| anyhow!("failed to encode MessagePack: {}", e))?,
+    };
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+/// Read inputs written with write_inputs in format from path.
+pub fn read_inputs<T: DeserializeOwned>(
+    format: OutputFormat,
+    path: impl AsRef<Path>,
+) -> anyhow::Result<T> {
+    let bytes = fs::read(path)?;
+    let inputs =
+        match format {
+            OutputFormat::Json => serde_json::from_slice(&bytes)
+                .map_err(|e| anyhow!("failed to decode JSON: {}", e))?,
+            #[cfg(feature = "bincode")]
+            OutputFormat::Bincode => bincode::deserialize(&bytes)
+                .map_err(|e| anyhow!("failed to decode bincode: {}", e))?,
+            #[cfg(feature = "msgpack")]
+            OutputFormat::MessagePack => rmp_serde::from_slice(&bytes)
+                .map_err(|e| anyhow!("failed to decode MessagePack: {}", e))?,
+        };
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::fixture::tests::FIXTURE_PATH;
+    use crate::header_range::{skip_inputs_from_fixtures, SkipInputs, CELESTIA_TRUSTING_PERIOD};
+
+    fn test_round_trip_template(format: OutputFormat, file_name: &str) {
+        let inputs =
+            skip_inputs_from_fixtures(FIXTURE_PATH, 10000, 10004, CELESTIA_TRUSTING_PERIOD)
+                .unwrap();
+
+        let path = env::temp_dir().join(file_name);
+        write_inputs(&inputs, format, &path).unwrap();
+        let read = read_inputs::<SkipInputs>(format, &path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(read, inputs);
+    }
+
+    #[test]
+    fn test_round_trip_json() {
+        test_round_trip_template(OutputFormat::Json, "blobstreamx_test_inputs.json");
+    }
+
+    #[test]
+    #[cfg(feature = "bincode")]
+    fn test_round_trip_bincode() {
+        test_round_trip_template(OutputFormat::Bincode, "blobstreamx_test_inputs.bin");
+    }
+
+    #[test]
+    #[cfg(feature = "msgpack")]
+    fn test_round_trip_msgpack() {
+        test_round_trip_template(OutputFormat::MessagePack, "blobstreamx_test_inputs.msgpack");
+    }
+}
@@ -0,0 +1,138 @@
+//! Tracing spans around building a circuit, assigning its inputs and proving, so proving can be
+//! profiled with `tracing-flame` or `tokio-console`. The spans are only emitted with the `tracing`
+//! feature, and the helpers are plain wrappers otherwise.
+
+use plonky2x::backend::circuit::{Circuit, CircuitBuild, PublicInput, PublicOutput};
+use plonky2x::prelude::plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
+use plonky2x::prelude::plonky2::plonk::proof::ProofWithPublicInputs;
+use plonky2x::prelude::{CircuitBuilder, PlonkParameters};
+
+/// Enter an info-level span named $name until the end of the enclosing block.
+macro_rules! profile_span {
+    ($name:expr) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!($name).entered();
+    };
+}
+
+/// Define and build circuit C.
+pub fn build_circuit<C: Circuit, L: PlonkParameters<D>, const D: usize>() -> CircuitBuild<L, D>
+where
+    <<L as PlonkParameters<D>>::Config as GenericConfig<D>>::Hasher: AlgebraicHasher<L::Field>,
+{
+    profile_span!("build_circuit");
+    let mut builder = CircuitBuilder::<L, D>::new();
+    C::define(&mut builder);
+    builder.build()
+}
+
+/// Create the input of circuit and assign it with write_input.
+pub fn assign_input<L: PlonkParameters<D>, const D: usize>(
+    circuit: &CircuitBuild<L, D>,
+    write_input: impl FnOnce(&mut PublicInput<L, D>),
+) -> PublicInput<L, D> {
+    profile_span!("assign_input");
+    let mut input = circuit.input();
+    write_input(&mut input);
+    input
+}
+
+/// Generate the witness of circuit for input and prove it.
+pub fn prove_circuit<L: PlonkParameters<D>, const D: usize>(
+    circuit: &CircuitBuild<L, D>,
+    input: &PublicInput<L, D>,
+) -> (
+    ProofWithPublicInputs<L::Field, L::Config, D>,
+    PublicOutput<L, D>,
+)
+where
+    <<L as PlonkParameters<D>>::Config as GenericConfig<D>>::Hasher: AlgebraicHasher<L::Field>,
+{
+    profile_span!("prove_circuit");
+    circuit.prove(input)
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use plonky2x::backend::circuit::DefaultParameters;
+    use plonky2x::frontend::uint::uint64::U64Variable;
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use super::*;
+
+    type L = DefaultParameters;
+    const D: usize = 2;
+
+    /// Outputs the sum of two inputs, so the spans can be profiled without fixtures.
+    #[derive(Debug, Clone)]
+    struct AddCircuit;
+
+    impl Circuit for AddCircuit {
+        fn define<L: PlonkParameters<D>, const D: usize>(builder: &mut CircuitBuilder<L, D>) {
+            let a = builder.read::<U64Variable>();
+            let b = builder.read::<U64Variable>();
+            let sum = builder.add(a, b);
+            builder.write(sum);
+        }
+    }
+
+    /// Records the names of the spans created while it is the default subscriber.
+    #[derive(Default)]
+    struct SpanRecorder {
+        next_id: AtomicU64,
+        span_names: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Subscriber for SpanRecorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            self.span_names
+                .lock()
+                .unwrap()
+                .push(span.metadata().name().to_string());
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn test_profile_spans() {
+        let recorder = SpanRecorder::default();
+        let span_names = recorder.span_names.clone();
+        tracing::subscriber::with_default(recorder, || {
+            let circuit = build_circuit::<AddCircuit, L, D>();
+            let input = assign_input(&circuit, |input| {
+                input.write::<U64Variable>(1);
+                input.write::<U64Variable>(2);
+            });
+            let (proof, mut output) = prove_circuit(&circuit, &input);
+            circuit.verify(&proof, &input, &output);
+            assert_eq!(output.read::<U64Variable>(), 3);
+        });
+
+        let span_names = span_names.lock().unwrap();
+        for name in ["build_circuit", "assign_input", "prove_circuit"] {
+            assert!(
+                span_names.iter().any(|span_name| span_name == name),
+                "no {} span was emitted",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Transports for fetching blocks from a Tendermint node. InputDataFetcher sends its RPC requests
+//! through RestRpc, which queries the node's JSON RPC over HTTP. The gRPC transport is a stub until
+//! a gRPC client is added.
+
+use async_trait::async_trait;
+use tendermint::block::signed_header::SignedHeader;
+use tendermint::block::Header;
+use tendermint::validator::Info;
+
+use crate::fixture::SignedBlockResponse;
+use crate::input::{
+    parse_commit_header, parse_signed_block_validators, rpc_client, FetchError, MAX_NUM_RETRIES,
+};
+
+/// A transport to a Tendermint node. Only request is transport specific, the other methods parse
+/// its responses.
+#[async_trait]
+pub trait TendermintRpc: Sync {
+    /// Request route, e.g. `commit?height=1`, and return the body of the response.
+    async fn request(&self, route: &str) -> Result<String, FetchError>;
+
+    /// Get the signed header of a block.
+    async fn signed_block(&self, block_number: u64) -> Result<SignedHeader, FetchError> {
+        let route = format!("signed_block?height={}", block_number);
+        let res = self.request(&route).await?;
+        let v: SignedBlockResponse =
+            serde_json::from_str(&res).map_err(|e| FetchError::InvalidResponse {
+                route: route.clone(),
+                reason: e.to_string(),
+            })?;
+        Ok(v.result)
+    }
+
+    /// Get the validator set which signed a block, verified by parse_signed_block_validators.
+    async fn validator_set(&self, block_number: u64) -> Result<Vec<Info>, FetchError> {
+        let route = format!("signed_block?height={}", block_number);
+        let res = self.request(&route).await?;
+        parse_signed_block_validators(&res).map_err(|e| FetchError::InvalidResponse {
+            route: route.clone(),
+            reason: e.to_string(),
+        })
+    }
+
+    /// Get the header of a block.
+    async fn header(&self, block_number: u64) -> Result<Header, FetchError> {
+        let route = format!("commit?height={}", block_number);
+        let res = self.request(&route).await?;
+        parse_commit_header(&route, &res)
+    }
+
+    /// Get the header of the latest block.
+    async fn latest_header(&self) -> Result<Header, FetchError> {
+        let route = "commit";
+        let res = self.request(route).await?;
+        parse_commit_header(route, &res)
+    }
+}
+
+/// Queries the JSON RPC of a Celestia node over HTTP, trying urls in turn.
+#[derive(Debug, Clone)]
+pub struct RestRpc {
+    urls: Vec<String>,
+}
+
+impl RestRpc {
+    pub fn new(urls: Vec<String>) -> Self {
+        Self { urls }
+    }
+}
+
+#[async_trait]
+impl TendermintRpc for RestRpc {
+    async fn request(&self, route: &str) -> Result<String, FetchError> {
+        rpc_client()
+            .request_with_retries(&self.urls, route, MAX_NUM_RETRIES)
+            .await
+    }
+}
+
+/// Queries a node over gRPC.
+/// Note: Not implemented yet, every request fails with FetchError::RequestFailed.
+#[derive(Debug, Clone)]
+pub struct GrpcRpc {
+    endpoint: String,
+}
+
+impl GrpcRpc {
+    pub fn new(endpoint: String) -> Self {
+        Self { endpoint }
+    }
+}
+
+#[async_trait]
+impl TendermintRpc for GrpcRpc {
+    async fn request(&self, route: &str) -> Result<String, FetchError> {
+        Err(FetchError::RequestFailed {
+            route: format!("{}/{}", self.endpoint, route),
+            reason: String::from("the gRPC transport is not implemented"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tendermintx::input::{InputDataFetcher, InputDataMode};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::fixture::tests::FIXTURE_PATH;
+    use crate::fixture::{get_signed_block, get_signed_block_validators, read_fixture};
+    use crate::input::DataCommitmentInputFetcher;
+
+    /// Serve the signed_block and commit responses of block_number from the fixtures over HTTP,
+    /// and return the url of the server.
+    async fn serve_fixture_block(block_number: u64) -> String {
+        let signed_block = read_fixture(&format!(
+            "{}/{}/signed_block.json",
+            FIXTURE_PATH, block_number
+        ));
+        let signed_block_json: serde_json::Value = serde_json::from_str(&signed_block).unwrap();
+        let commit = json!({
+            "result": { "signed_header": signed_block_json["result"] }
+        })
+        .to_string();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 1024];
+                let n = stream.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let body = if request.starts_with("GET /commit") {
+                    &commit
+                } else {
+                    &signed_block
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    async fn fetch_block<R: TendermintRpc>(
+        rpc: &R,
+        block_number: u64,
+    ) -> Result<(SignedHeader, Vec<Info>, Header), FetchError> {
+        Ok((
+            rpc.signed_block(block_number).await?,
+            rpc.validator_set(block_number).await?,
+            rpc.header(block_number).await?,
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_rest_rpc() {
+        let block_number = 10000;
+        let url = serve_fixture_block(block_number).await;
+
+        let rpc = RestRpc::new(vec![url]);
+        let (signed_header, validators, header) = fetch_block(&rpc, block_number).await.unwrap();
+        assert_eq!(signed_header, get_signed_block(FIXTURE_PATH, block_number));
+        assert_eq!(
+            validators,
+            get_signed_block_validators(FIXTURE_PATH, block_number)
+        );
+        assert_eq!(header, signed_header.header);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_rpc_unimplemented() {
+        let rpc = GrpcRpc::new(String::from("http://localhost:9090"));
+        assert!(matches!(
+            fetch_block(&rpc, 10000).await,
+            Err(FetchError::RequestFailed { .. })
+        ));
+    }
+
+    // Ensure InputDataFetcher fetches through RestRpc in RPC mode.
+    #[tokio::test]
+    async fn test_input_data_fetcher_rest_rpc() {
+        let block_number = 10000;
+        let url = serve_fixture_block(block_number).await;
+
+        let mut fetcher = InputDataFetcher::default();
+        fetcher.mode = InputDataMode::Rpc;
+        fetcher.urls = vec![url];
+        fetcher.save = false;
+        assert_eq!(
+            fetcher
+                .get_verified_validator_set_from_number(block_number)
+                .await,
+            Ok(get_signed_block_validators(FIXTURE_PATH, block_number))
+        );
+        assert_eq!(
+            fetcher.get_latest_header().await,
+            Ok(get_signed_block(FIXTURE_PATH, block_number).header)
+        );
+    }
+}
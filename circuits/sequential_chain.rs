@@ -0,0 +1,134 @@
+use plonky2x::backend::circuit::Circuit;
+use plonky2x::frontend::uint::uint64::U64Variable;
+use plonky2x::prelude::{Bytes32Variable, CircuitBuilder, PlonkParameters};
+use tendermintx::config::TendermintConfig;
+use tendermintx::step::{StepOffchainInputs, TendermintStepCircuit};
+
+use crate::header::HeaderBuilder;
+
+/// Proves the header NB_STEPS blocks after a trusted header by chaining NB_STEPS steps. Each step
+/// verifies the next header's validators_hash equals the previous header's next_validators_hash,
+/// and that more than 2/3 of the validator set signed the next header, so unlike a skip there is
+/// no check that 1/3 of the trusted validator set signed the target.
+/// Note: Proving cost grows linearly with NB_STEPS, so this is only cheaper than a skip over short
+/// ranges.
+#[derive(Debug, Clone)]
+pub struct SequentialChainCircuit<
+    const MAX_VALIDATOR_SET_SIZE: usize,
+    const CHAIN_ID_SIZE_BYTES: usize,
+    const NB_STEPS: usize,
+    C: TendermintConfig<CHAIN_ID_SIZE_BYTES>,
+> {
+    _phantom: std::marker::PhantomData<C>,
+}
+
+impl<
+        const MAX_VALIDATOR_SET_SIZE: usize,
+        const CHAIN_ID_SIZE_BYTES: usize,
+        const NB_STEPS: usize,
+        C: TendermintConfig<CHAIN_ID_SIZE_BYTES>,
+    > Circuit for SequentialChainCircuit<MAX_VALIDATOR_SET_SIZE, CHAIN_ID_SIZE_BYTES, NB_STEPS, C>
+{
+    fn define<L: PlonkParameters<D>, const D: usize>(builder: &mut CircuitBuilder<L, D>) {
+        assert!(NB_STEPS > 0);
+
+        let trusted_block_number = builder.evm_read::<U64Variable>();
+        let trusted_header_hash = builder.evm_read::<Bytes32Variable>();
+        builder.assert_nonzero_hash(&trusted_header_hash);
+
+        let one = builder.constant::<U64Variable>(1u64);
+        let mut prev_block_number = trusted_block_number;
+        let mut prev_header_hash = trusted_header_hash;
+        for _ in 0..NB_STEPS {
+            let next_header_hash = builder.step::<MAX_VALIDATOR_SET_SIZE, CHAIN_ID_SIZE_BYTES>(
+                C::CHAIN_ID_BYTES,
+                prev_block_number,
+                prev_header_hash,
+            );
+            builder.assert_nonzero_hash(&next_header_hash);
+
+            prev_block_number = builder.add(prev_block_number, one);
+            prev_header_hash = next_header_hash;
+        }
+
+        builder.evm_write(prev_header_hash);
+    }
+
+    fn register_generators<L: PlonkParameters<D>, const D: usize>(
+        generator_registry: &mut plonky2x::prelude::HintRegistry<L, D>,
+    ) where
+        <<L as PlonkParameters<D>>::Config as plonky2x::prelude::plonky2::plonk::config::GenericConfig<D>>::Hasher:
+            plonky2x::prelude::plonky2::plonk::config::AlgebraicHasher<L::Field>,
+    {
+        generator_registry.register_async_hint::<StepOffchainInputs<MAX_VALIDATOR_SET_SIZE>>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use ethers::types::H256;
+    use plonky2x::prelude::DefaultBuilder;
+    use tendermintx::config::{Mocha4Config, MOCHA_4_CHAIN_ID_SIZE_BYTES};
+
+    use super::*;
+    use crate::fixture::get_signed_block;
+    use crate::fixture::tests::FIXTURE_PATH;
+
+    fn test_sequential_chain_template<
+        const MAX_VALIDATOR_SET_SIZE: usize,
+        const NB_STEPS: usize,
+    >(
+        trusted_block: u64,
+        trusted_header_hash: H256,
+        target_header_hash: H256,
+    ) {
+        env::set_var("RUST_LOG", "debug");
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = DefaultBuilder::new();
+        SequentialChainCircuit::<
+            MAX_VALIDATOR_SET_SIZE,
+            MOCHA_4_CHAIN_ID_SIZE_BYTES,
+            NB_STEPS,
+            Mocha4Config,
+        >::define(&mut builder);
+        let circuit = builder.build();
+
+        let mut input = circuit.input();
+        input.evm_write::<U64Variable>(trusted_block);
+        input.evm_write::<Bytes32Variable>(trusted_header_hash);
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (proof, mut output) = rt.block_on(async { circuit.prove_async(&input).await });
+        circuit.verify(&proof, &input, &output);
+
+        let header_hash = output.evm_read::<Bytes32Variable>();
+        assert_eq!(header_hash, target_header_hash);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "ci", ignore)]
+    fn test_sequential_chain() {
+        const MAX_VALIDATOR_SET_SIZE: usize = 4;
+        const NB_STEPS: usize = 3;
+
+        // These blocks are on Mocha-4 testnet, and the validator set doesn't change over them.
+        let trusted_block = 10000u64;
+        let header_hash = |block_number| {
+            H256::from_slice(
+                get_signed_block(FIXTURE_PATH, block_number)
+                    .header
+                    .hash()
+                    .as_bytes(),
+            )
+        };
+
+        test_sequential_chain_template::<MAX_VALIDATOR_SET_SIZE, NB_STEPS>(
+            trusted_block,
+            header_hash(trusted_block),
+            header_hash(trusted_block + NB_STEPS as u64),
+        );
+    }
+}
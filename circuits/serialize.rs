@@ -0,0 +1,113 @@
+//! Saving and reloading built circuits, so a prover can skip rebuilding a circuit on every start.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::anyhow;
+use plonky2x::backend::circuit::{Circuit, CircuitBuild};
+use plonky2x::prelude::plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
+use plonky2x::prelude::{GateRegistry, HintRegistry, PlonkParameters};
+
+/// The gate and hint registries of circuit C, used to (de)serialize its built circuit.
+fn registries<C: Circuit, L: PlonkParameters<D>, const D: usize>(
+) -> (GateRegistry<L, D>, HintRegistry<L, D>)
+where
+    <<L as PlonkParameters<D>>::Config as GenericConfig<D>>::Hasher: AlgebraicHasher<L::Field>,
+{
+    let mut gate_registry = GateRegistry::new();
+    let mut hint_registry = HintRegistry::new();
+    C::register_gates(&mut gate_registry);
+    C::register_generators(&mut hint_registry);
+    (gate_registry, hint_registry)
+}
+
+/// Save the built circuit of C to path.
+pub fn save_circuit<C: Circuit, L: PlonkParameters<D>, const D: usize>(
+    circuit: &CircuitBuild<L, D>,
+    path: impl AsRef<Path>,
+) -> anyhow::Result<()>
+where
+    <<L as PlonkParameters<D>>::Config as GenericConfig<D>>::Hasher: AlgebraicHasher<L::Field>,
+{
+    let (gate_registry, hint_registry) = registries::<C, L, D>();
+    let bytes = circuit
+        .serialize(&gate_registry, &hint_registry)
+        .map_err(|e| anyhow!("failed to serialize circuit: {:?}", e))?;
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+/// Load a built circuit of C saved with save_circuit.
+pub fn load_circuit<C: Circuit, L: PlonkParameters<D>, const D: usize>(
+    path: impl AsRef<Path>,
+) -> anyhow::Result<CircuitBuild<L, D>>
+where
+    <<L as PlonkParameters<D>>::Config as GenericConfig<D>>::Hasher: AlgebraicHasher<L::Field>,
+{
+    let (gate_registry, hint_registry) = registries::<C, L, D>();
+    let bytes = fs::read(path)?;
+    CircuitBuild::<L, D>::deserialize(&bytes, &gate_registry, &hint_registry)
+        .map_err(|e| anyhow!("failed to deserialize circuit: {:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use plonky2x::backend::circuit::DefaultParameters;
+    use plonky2x::frontend::uint::uint64::U64Variable;
+    use plonky2x::prelude::{CircuitBuilder, DefaultBuilder};
+
+    use super::*;
+
+    type L = DefaultParameters;
+    const D: usize = 2;
+
+    /// Outputs the sum of two inputs.
+    #[derive(Debug, Clone)]
+    struct AddCircuit;
+
+    impl Circuit for AddCircuit {
+        fn define<L: PlonkParameters<D>, const D: usize>(builder: &mut CircuitBuilder<L, D>) {
+            let a = builder.read::<U64Variable>();
+            let b = builder.read::<U64Variable>();
+            let sum = builder.add(a, b);
+            builder.write(sum);
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_circuit() {
+        env_logger::try_init().unwrap_or_default();
+
+        type C = AddCircuit;
+
+        let mut builder = DefaultBuilder::new();
+        C::define(&mut builder);
+        let circuit = builder.build();
+
+        let path = env::temp_dir().join("blobstreamx_test_save_and_load_circuit.bin");
+        save_circuit::<C, L, D>(&circuit, &path).unwrap();
+        let loaded_circuit = load_circuit::<C, L, D>(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded_circuit.data.common, circuit.data.common);
+        assert_eq!(
+            loaded_circuit.data.verifier_only.circuit_digest,
+            circuit.data.verifier_only.circuit_digest
+        );
+
+        let mut input = loaded_circuit.input();
+        input.write::<U64Variable>(1);
+        input.write::<U64Variable>(2);
+
+        // A proof from the loaded circuit verifies against the freshly built circuit.
+        let (proof, mut output) = loaded_circuit.prove(&input);
+        loaded_circuit.verify(&proof, &input, &output);
+        circuit.verify(&proof, &input, &output);
+        assert_eq!(output.read::<U64Variable>(), 3);
+
+        let (fresh_proof, _) = circuit.prove(&input);
+        assert_eq!(proof.public_inputs, fresh_proof.public_inputs);
+    }
+}
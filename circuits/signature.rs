@@ -0,0 +1,449 @@
+use std::fmt;
+
+use ethers::types::{H256, U256};
+use num::{BigUint, One, Zero};
+use plonky2x::frontend::curta::ec::point::{CompressedEdwardsY, CompressedEdwardsYVariable};
+use plonky2x::frontend::ecc::curve25519::curve_types::AffinePoint;
+use plonky2x::frontend::ecc::curve25519::ed25519::eddsa::EDDSASignatureVariableValue;
+use plonky2x::frontend::ecc::curve25519::ed25519::Ed25519;
+use plonky2x::prelude::*;
+use subtle_encoding::hex;
+use tendermint::Signature;
+
+use crate::consts::VOTE_MESSAGE_MAX_SIZE_BYTES;
+use crate::vars::{CommitSignature, CommitSignatureVariable};
+
+/// Encodings of the Ed25519 points of small order (the 8-torsion subgroup), including their
+/// non-canonical encodings. A signature whose R is one of these points is malleable, so such
+/// signatures are rejected.
+pub const SMALL_ORDER_POINT_ENCODINGS: [&str; 14] = [
+    "0000000000000000000000000000000000000000000000000000000000000000",
+    "0000000000000000000000000000000000000000000000000000000000000080",
+    "0100000000000000000000000000000000000000000000000000000000000000",
+    "0100000000000000000000000000000000000000000000000000000000000080",
+    "26e8958fc2b227b045c3f489f2ef98f0d5dfac05d3c63339b13802886d53fc05",
+    "26e8958fc2b227b045c3f489f2ef98f0d5dfac05d3c63339b13802886d53fc85",
+    "c7176a703d4dd84fba3c0b760d10670f2a2053fa2c39ccc64ec7fd7792ac037a",
+    "c7176a703d4dd84fba3c0b760d10670f2a2053fa2c39ccc64ec7fd7792ac03fa",
+    "ecffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+    "ecffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+    "edffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+    "edffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+    "eeffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+    "eeffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+];
+
+/// The small order point encodings as bytes.
+pub fn small_order_point_encodings() -> Vec<[u8; 32]> {
+    SMALL_ORDER_POINT_ENCODINGS
+        .iter()
+        .map(|encoding| hex::decode(encoding).unwrap().try_into().unwrap())
+        .collect()
+}
+
+/// Order of the Ed25519 base point, L = 2^252 + 27742317777372353535851937790883648493, encoded
+/// little-endian as the s scalar of a signature is.
+pub const GROUP_ORDER_ENCODING: &str =
+    "edd3f55c1a631258d69cf7a2def9de1400000000000000000000000000000010";
+
+/// The group order encoding as bytes.
+pub fn group_order_encoding() -> [u8; 32] {
+    hex::decode(GROUP_ORDER_ENCODING)
+        .unwrap()
+        .try_into()
+        .unwrap()
+}
+
+/// Whether the little-endian encoded s scalar of a signature is reduced, i.e. s < L.
+pub fn is_canonical_s(sig_s: &[u8; 32]) -> bool {
+    let group_order = group_order_encoding();
+    // Compare from the most significant byte.
+    sig_s.iter().rev().lt(group_order.iter().rev())
+}
+
+/// Whether the encoded point is of small order.
+pub fn is_small_order_encoding(point: &[u8; 32]) -> bool {
+    small_order_point_encodings().contains(point)
+}
+
+/// Whether the encoded point is on the curve, i.e. there is an x with -x^2 + y^2 = 1 + d x^2 y^2
+/// for the encoded y. The sign bit of x is not checked, as with ZIP 215 either sign is accepted.
+pub fn is_on_curve_encoding(point: &[u8; 32]) -> bool {
+    let p = (BigUint::one() << 255u32) - BigUint::from(19u32);
+    let inv = |a: &BigUint| a.modpow(&(&p - BigUint::from(2u32)), &p);
+    // d = -121665 / 121666.
+    let d = (&p - BigUint::from(121665u32)) * inv(&BigUint::from(121666u32)) % &p;
+
+    let mut y_bytes = *point;
+    y_bytes[31] &= 0x7f;
+    let y = BigUint::from_bytes_le(&y_bytes) % &p;
+    let y2 = &y * &y % &p;
+
+    // x^2 = (y^2 - 1) / (d y^2 + 1), which must be zero or a quadratic residue.
+    let u = (&y2 + &p - BigUint::one()) % &p;
+    let v = (&d * &y2 + BigUint::one()) % &p;
+    let x2 = u * inv(&v) % &p;
+    x2.is_zero() || x2.modpow(&((&p - BigUint::one()) >> 1u32), &p).is_one()
+}
+
+/// Errors of converting a signature into the witness of the circuits' Ed25519 verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureWitnessError {
+    /// The signature is not 64 bytes long.
+    InvalidLength(usize),
+    /// The R point of the signature is not on the curve.
+    InvalidR,
+    /// The s scalar of the signature is not less than the group order.
+    NonCanonicalS,
+}
+
+impl fmt::Display for SignatureWitnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureWitnessError::InvalidLength(length) => {
+                write!(f, "signature is {} bytes long, expected 64", length)
+            }
+            SignatureWitnessError::InvalidR => write!(f, "signature R is not on the curve"),
+            SignatureWitnessError::NonCanonicalS => {
+                write!(f, "signature s is not less than the group order")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SignatureWitnessError {}
+
+/// Split a signature into its R point and s scalar, as witnessed by the circuits' Ed25519
+/// verification. R must be on the curve and s must be canonical.
+pub fn signature_to_witness(
+    sig: &Signature,
+) -> Result<(AffinePoint<Ed25519>, BigUint), SignatureWitnessError> {
+    let sig_bytes = sig.as_bytes();
+    if sig_bytes.len() != 64 {
+        return Err(SignatureWitnessError::InvalidLength(sig_bytes.len()));
+    }
+    let sig_r: [u8; 32] = sig_bytes[..32].try_into().unwrap();
+    let sig_s: [u8; 32] = sig_bytes[32..].try_into().unwrap();
+
+    if !is_on_curve_encoding(&sig_r) {
+        return Err(SignatureWitnessError::InvalidR);
+    }
+    if !is_canonical_s(&sig_s) {
+        return Err(SignatureWitnessError::NonCanonicalS);
+    }
+
+    let r = AffinePoint::<Ed25519>::new_from_compressed_point(&sig_r);
+    if !r.is_valid() {
+        return Err(SignatureWitnessError::InvalidR);
+    }
+    Ok((r, BigUint::from_bytes_le(&sig_s)))
+}
+
+/// The value of a CommitSignatureVariable: the signature split into its R point and s scalar, and
+/// the signed message padded with zeros to VOTE_MESSAGE_MAX_SIZE_BYTES.
+pub fn to_commit_signature<F: RichField>(
+    signature: &Signature,
+    message: &[u8],
+) -> CommitSignature<F> {
+    let sig_bytes = signature.as_bytes();
+    assert_eq!(sig_bytes.len(), 64, "signature is not 64 bytes long");
+    assert!(
+        message.len() <= VOTE_MESSAGE_MAX_SIZE_BYTES,
+        "signed message is longer than VOTE_MESSAGE_MAX_SIZE_BYTES"
+    );
+
+    let mut padded_message = [0u8; VOTE_MESSAGE_MAX_SIZE_BYTES];
+    padded_message[..message.len()].copy_from_slice(message);
+    CommitSignature {
+        signature: EDDSASignatureVariableValue {
+            r: CompressedEdwardsY(sig_bytes[..32].try_into().unwrap()),
+            s: U256::from_little_endian(&sig_bytes[32..]),
+        },
+        message: padded_message,
+        message_byte_length: message.len() as u32,
+    }
+}
+
+/// Verifies the signatures on a commit natively.
+pub trait SignatureVerifier {
+    /// Whether signature is a valid signature of message by the Ed25519 pubkey.
+    fn verify(&self, pubkey: &[u8], signature: &[u8], message: &[u8]) -> bool;
+}
+
+/// Verifies Ed25519 signatures with ZIP 215 rules, as the circuits do.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ed25519Verifier;
+
+impl SignatureVerifier for Ed25519Verifier {
+    fn verify(&self, pubkey: &[u8], signature: &[u8], message: &[u8]) -> bool {
+        ed25519_consensus::VerificationKey::try_from(pubkey)
+            .and_then(|key| {
+                let signature = ed25519_consensus::Signature::try_from(signature)?;
+                key.verify(&signature, message)
+            })
+            .is_ok()
+    }
+}
+
+/// Accepts every signature, so tests of the voting power and validator matching logic don't pay
+/// for Ed25519 verification. Only available in tests.
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct AcceptAllVerifier;
+
+#[cfg(test)]
+impl SignatureVerifier for AcceptAllVerifier {
+    fn verify(&self, _pubkey: &[u8], _signature: &[u8], _message: &[u8]) -> bool {
+        true
+    }
+}
+
+/// Gadgets over Ed25519 signatures.
+pub trait SignatureBuilder<L: PlonkParameters<D>, const D: usize> {
+    /// Assert the encoded R point of a signature is not of small order.
+    fn assert_not_small_order(&mut self, sig_r: &Bytes32Variable);
+
+    /// Assert the little-endian encoded s scalar of a signature is less than the group order L.
+    /// s and s + L both satisfy the verification equation, so accepting unreduced scalars makes
+    /// signatures malleable.
+    fn assert_canonical_s(&mut self, sig_s: &Bytes32Variable);
+
+    /// Verify the signature of each active validator is a valid Ed25519 signature by its pubkey of
+    /// the first message_byte_length bytes of its message. The signatures of inactive validators,
+    /// e.g. validators which did not sign the commit, are not verified.
+    fn verify_signatures<const NB_SIGNATURES: usize>(
+        &mut self,
+        active: &[BoolVariable],
+        pubkeys: &[Bytes32Variable],
+        signatures: &[CommitSignatureVariable],
+    );
+}
+
+impl<L: PlonkParameters<D>, const D: usize> SignatureBuilder<L, D> for CircuitBuilder<L, D> {
+    fn assert_not_small_order(&mut self, sig_r: &Bytes32Variable) {
+        let false_var = self._false();
+        for encoding in small_order_point_encodings() {
+            let small_order_point = self.constant::<Bytes32Variable>(H256(encoding));
+            let is_small_order = self.is_equal(*sig_r, small_order_point);
+            self.assert_is_equal(is_small_order, false_var);
+        }
+    }
+
+    fn assert_canonical_s(&mut self, sig_s: &Bytes32Variable) {
+        let true_var = self._true();
+        let group_order = group_order_encoding();
+
+        // Compare s to L bit by bit from the most significant bit. is_lt is set once a bit of s is
+        // below the bit of L, with all more significant bits equal.
+        let mut is_lt = self._false();
+        let mut is_eq = true_var;
+        let sig_s_bytes = sig_s.as_bytes();
+        for i in (0..32).rev() {
+            let bits = sig_s_bytes[i].as_be_bits();
+            for j in 0..8 {
+                let order_bit = (group_order[i] >> (7 - j)) & 1 == 1;
+                let not_bit = self.not(bits[j]);
+                if order_bit {
+                    let is_lt_here = self.and(is_eq, not_bit);
+                    is_lt = self.or(is_lt, is_lt_here);
+                    is_eq = self.and(is_eq, bits[j]);
+                } else {
+                    is_eq = self.and(is_eq, not_bit);
+                }
+            }
+        }
+        self.assert_is_equal(is_lt, true_var);
+    }
+
+    fn verify_signatures<const NB_SIGNATURES: usize>(
+        &mut self,
+        active: &[BoolVariable],
+        pubkeys: &[Bytes32Variable],
+        signatures: &[CommitSignatureVariable],
+    ) {
+        assert_eq!(active.len(), NB_SIGNATURES);
+        assert_eq!(pubkeys.len(), NB_SIGNATURES);
+        assert_eq!(signatures.len(), NB_SIGNATURES);
+
+        let message_byte_lengths = signatures
+            .iter()
+            .map(|s| s.message_byte_length)
+            .collect::<Vec<_>>();
+        let messages = signatures.iter().map(|s| s.message).collect::<Vec<_>>();
+        let eddsa_signatures = signatures
+            .iter()
+            .map(|s| s.signature.clone())
+            .collect::<Vec<_>>();
+        let pubkeys = pubkeys
+            .iter()
+            .map(|pubkey| CompressedEdwardsYVariable(*pubkey))
+            .collect::<Vec<_>>();
+        self.curta_eddsa_verify_sigs_conditional::<VOTE_MESSAGE_MAX_SIZE_BYTES, NB_SIGNATURES>(
+            ArrayVariable::<BoolVariable, NB_SIGNATURES>::from(active.to_vec()),
+            Some(ArrayVariable::<U32Variable, NB_SIGNATURES>::from(
+                message_byte_lengths,
+            )),
+            ArrayVariable::<BytesVariable<VOTE_MESSAGE_MAX_SIZE_BYTES>, NB_SIGNATURES>::from(
+                messages,
+            ),
+            ArrayVariable::from(eddsa_signatures),
+            ArrayVariable::<CompressedEdwardsYVariable, NB_SIGNATURES>::from(pubkeys),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2x::prelude::plonky2::field::types::PrimeField;
+    use tendermint::block::CommitSig;
+
+    use super::*;
+    use crate::fixture::get_signed_block;
+    use crate::fixture::tests::FIXTURE_PATH;
+
+    type L = DefaultParameters;
+    const D: usize = 2;
+
+    fn test_assert_not_small_order_template(sig_r: [u8; 32]) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let sig_r_var = builder.read::<Bytes32Variable>();
+        builder.assert_not_small_order(&sig_r_var);
+        let circuit = builder.build();
+
+        let mut input = circuit.input();
+        input.write::<Bytes32Variable>(H256(sig_r));
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_assert_not_small_order() {
+        let signed_header = get_signed_block(FIXTURE_PATH, 10000);
+        let signature = match &signed_header.commit.signatures[0] {
+            CommitSig::BlockIdFlagCommit { signature, .. } => signature.clone().unwrap(),
+            _ => panic!("expected a commit signature"),
+        };
+        let sig_r: [u8; 32] = signature.as_bytes()[..32].try_into().unwrap();
+        assert!(!is_small_order_encoding(&sig_r));
+        test_assert_not_small_order_template(sig_r);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_not_small_order_identity() {
+        // The identity point (0, 1).
+        let mut sig_r = [0u8; 32];
+        sig_r[0] = 1;
+        assert!(is_small_order_encoding(&sig_r));
+        test_assert_not_small_order_template(sig_r);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_not_small_order_order_8() {
+        test_assert_not_small_order_template(small_order_point_encodings()[4]);
+    }
+
+    fn test_assert_canonical_s_template(sig_s: [u8; 32]) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let sig_s_var = builder.read::<Bytes32Variable>();
+        builder.assert_canonical_s(&sig_s_var);
+        let circuit = builder.build();
+
+        let mut input = circuit.input();
+        input.write::<Bytes32Variable>(H256(sig_s));
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    fn get_fixture_signature() -> Signature {
+        let signed_header = get_signed_block(FIXTURE_PATH, 10000);
+        match &signed_header.commit.signatures[0] {
+            CommitSig::BlockIdFlagCommit { signature, .. } => signature.clone().unwrap(),
+            _ => panic!("expected a commit signature"),
+        }
+    }
+
+    fn get_fixture_sig_s() -> [u8; 32] {
+        get_fixture_signature().as_bytes()[32..].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_signature_to_witness() {
+        let signature = get_fixture_signature();
+        let (r, s) = signature_to_witness(&signature).unwrap();
+
+        // Re-encode R as its y coordinate with the sign of x in the top bit, and s little-endian.
+        let mut encoded = r.y.to_canonical_biguint().to_bytes_le();
+        encoded.resize(32, 0);
+        if r.x.to_canonical_biguint().bit(0) {
+            encoded[31] |= 0x80;
+        }
+        let mut encoded_s = s.to_bytes_le();
+        encoded_s.resize(32, 0);
+        encoded.extend(encoded_s);
+        assert_eq!(encoded, signature.as_bytes());
+    }
+
+    #[test]
+    fn test_signature_to_witness_invalid() {
+        let signature = get_fixture_signature();
+
+        // No x satisfies the curve equation for y = 2.
+        let mut invalid_r = signature.as_bytes().to_vec();
+        invalid_r[..32].fill(0);
+        invalid_r[0] = 2;
+        assert!(matches!(
+            signature_to_witness(&Signature::try_from(invalid_r.as_slice()).unwrap()),
+            Err(SignatureWitnessError::InvalidR)
+        ));
+
+        let mut unreduced_s = signature.as_bytes().to_vec();
+        unreduced_s[32..].copy_from_slice(&group_order_encoding());
+        assert!(matches!(
+            signature_to_witness(&Signature::try_from(unreduced_s.as_slice()).unwrap()),
+            Err(SignatureWitnessError::NonCanonicalS)
+        ));
+    }
+
+    #[test]
+    fn test_assert_canonical_s() {
+        let sig_s = get_fixture_sig_s();
+        assert!(is_canonical_s(&sig_s));
+        test_assert_canonical_s_template(sig_s);
+
+        // L - 1 is the largest canonical scalar.
+        let mut max_s = group_order_encoding();
+        max_s[0] -= 1;
+        assert!(is_canonical_s(&max_s));
+        test_assert_canonical_s_template(max_s);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_canonical_s_unreduced() {
+        // s + L satisfies the verification equation for the same message as s.
+        let sig_s = get_fixture_sig_s();
+        let group_order = group_order_encoding();
+        let mut unreduced_s = [0u8; 32];
+        let mut carry = 0u16;
+        for i in 0..32 {
+            let sum = sig_s[i] as u16 + group_order[i] as u16 + carry;
+            unreduced_s[i] = sum as u8;
+            carry = sum >> 8;
+        }
+        assert_eq!(carry, 0);
+        assert!(!is_canonical_s(&unreduced_s));
+        test_assert_canonical_s_template(unreduced_s);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_canonical_s_group_order() {
+        test_assert_canonical_s_template(group_order_encoding());
+    }
+}
@@ -0,0 +1,110 @@
+//! Gate statistics for built circuits, used to audit circuit complexity.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use plonky2x::prelude::plonky2::field::extension::Extendable;
+use plonky2x::prelude::plonky2::field::types::Field;
+use plonky2x::prelude::plonky2::hash::hash_types::RichField;
+use plonky2x::prelude::plonky2::plonk::circuit_data::CircuitData;
+use plonky2x::prelude::plonky2::plonk::config::GenericConfig;
+
+/// Count the number of rows used by each gate type in `common.gates`, in order.
+///
+/// The selector polynomials hold, at each row, the index of the gate used in that row (or an
+/// unused marker for rows belonging to another selector group), so the counts are recovered by
+/// interpolating them back to their values over the trace domain.
+pub fn gate_counts<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    data: &CircuitData<F, C, D>,
+) -> Vec<(String, usize)> {
+    let common = &data.common;
+    let num_selectors = common.selectors_info.num_selectors();
+
+    let mut counts = vec![0usize; common.gates.len()];
+    if num_selectors == 0 {
+        // A single gate type fills every row.
+        counts[0] = common.degree();
+    } else {
+        let gate_indices = (0..common.gates.len())
+            .map(F::from_canonical_usize)
+            .collect::<Vec<_>>();
+        for selector in
+            data.prover_only.constants_sigmas_commitment.polynomials[..num_selectors].iter()
+        {
+            for value in selector.clone().fft().values {
+                if let Some(i) = gate_indices.iter().position(|g| *g == value) {
+                    counts[i] += 1;
+                }
+            }
+        }
+    }
+
+    common
+        .gates
+        .iter()
+        .zip(counts)
+        .map(|(gate, count)| (gate.0.id(), count))
+        .collect()
+}
+
+/// Write the per-gate-type row counts of a built circuit to `path` as a CSV with the header
+/// `gate,count`.
+pub fn dump_circuit_stats<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    data: &CircuitData<F, C, D>,
+    path: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "gate,count")?;
+    for (gate, count) in gate_counts(data) {
+        // Gate ids are Debug strings which can contain commas, so always quote them.
+        writeln!(writer, "\"{}\",{}", gate.replace('"', "\"\""), count)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use plonky2x::prelude::{BytesVariable, DefaultBuilder, Variable};
+
+    use super::*;
+
+    #[test]
+    fn test_dump_circuit_stats() {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = DefaultBuilder::new();
+        let a = builder.read::<Variable>();
+        let b = builder.read::<Variable>();
+        let c = builder.mul_add(a, b, a);
+        builder.write::<Variable>(c);
+        let msg = builder.read::<BytesVariable<64>>();
+        let digest = builder.sha256(&msg.0);
+        builder.write(digest);
+        let circuit = builder.build();
+
+        let counts = gate_counts(&circuit.data);
+        // Every row of the trace is assigned to exactly one gate.
+        let total: usize = counts.iter().map(|(_, count)| count).sum();
+        assert_eq!(total, circuit.data.common.degree());
+
+        let path = env::temp_dir().join("blobstreamx_test_dump_circuit_stats.csv");
+        dump_circuit_stats(&circuit.data, &path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("gate,count"));
+        assert_eq!(lines.count(), counts.len());
+        // The SHA256 gadget is built from U32 arithmetic gates on top of the base arithmetic gate.
+        assert!(csv.contains("ArithmeticGate"));
+        assert!(csv.contains("U32"));
+    }
+}
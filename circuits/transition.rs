@@ -0,0 +1,513 @@
+//! Proving a transition from a trusted block to a target block with the cheapest circuit: a step
+//! if the target is the next block, and a skip otherwise.
+
+use std::fmt;
+use std::time::Duration;
+
+use ethers::types::H256;
+use plonky2x::backend::circuit::{Circuit, CircuitBuild, DefaultParameters};
+use plonky2x::frontend::uint::uint64::U64Variable;
+use plonky2x::prelude::plonky2::plonk::proof::ProofWithPublicInputs;
+use plonky2x::prelude::{Bytes32Variable, DefaultBuilder, PlonkParameters};
+use tendermintx::config::TendermintConfig;
+use tendermintx::input::InputDataFetcher;
+
+use crate::config::{BlockVersionConfig, CheckpointConfig, HeaderLayoutConfig};
+use crate::header_range::{
+    skip_inputs, CombinedSkipCircuit, SkipInputs, SkipInputsError, CELESTIA_TRUSTING_PERIOD,
+};
+use crate::input::{DataCommitmentInputFetcher, HeaderHashSource, InputDataError};
+use crate::next_header::{StepProof, StepProveError, StepProver};
+
+type L = DefaultParameters;
+const D: usize = 2;
+
+/// The circuit which proves a transition from trusted_block to target_block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionKind {
+    /// The target block is the block after the trusted block.
+    Step,
+    /// The target block is more than one block after the trusted block.
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransitionError {
+    /// The target block is not after the trusted block.
+    InvalidRange {
+        trusted_block: u64,
+        target_block: u64,
+    },
+    /// The target block is more than skip_max blocks after the trusted block.
+    ExceedsSkipMax {
+        trusted_block: u64,
+        target_block: u64,
+        skip_max: u64,
+    },
+    /// The signed header of a block of the transition could not be fetched.
+    Fetch { block: u64, reason: String },
+    /// The block's header hash changed between fetching the inputs and proving, as the chain
+    /// reorged.
+    Reorg { block: u64, reason: String },
+    /// The fetched blocks of a skip are not a valid skip, e.g. the target block is outside the
+    /// trusting period.
+    InvalidSkip(SkipInputsError),
+    /// The proof of the transition did not verify.
+    ProofVerification {
+        trusted_block: u64,
+        target_block: u64,
+        reason: String,
+    },
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransitionError::InvalidRange {
+                trusted_block,
+                target_block,
+            } => write!(
+                f,
+                "target block {} is not after trusted block {}",
+                target_block, trusted_block
+            ),
+            TransitionError::ExceedsSkipMax {
+                trusted_block,
+                target_block,
+                skip_max,
+            } => write!(
+                f,
+                "target block {} is more than {} blocks after trusted block {}",
+                target_block, skip_max, trusted_block
+            ),
+            TransitionError::Fetch { block, reason } => {
+                write!(f, "failed to fetch block {}: {}", block, reason)
+            }
+            TransitionError::Reorg { block, reason } => {
+                write!(f, "block {} is no longer canonical: {}", block, reason)
+            }
+            TransitionError::InvalidSkip(e) => write!(f, "invalid skip: {}", e),
+            TransitionError::ProofVerification {
+                trusted_block,
+                target_block,
+                reason,
+            } => write!(
+                f,
+                "proof of the transition from block {} to block {} failed to verify: {}",
+                trusted_block, target_block, reason
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TransitionError {}
+
+impl From<StepProveError> for TransitionError {
+    fn from(e: StepProveError) -> Self {
+        match e {
+            StepProveError::Fetch { block, reason } => TransitionError::Fetch { block, reason },
+            StepProveError::Reorg { block, reason } => TransitionError::Reorg { block, reason },
+            StepProveError::ProofVerification { prev_block, reason } => {
+                TransitionError::ProofVerification {
+                    trusted_block: prev_block,
+                    target_block: prev_block + 1,
+                    reason,
+                }
+            }
+        }
+    }
+}
+
+impl TransitionKind {
+    /// Choose the circuit proving the transition from trusted_block to target_block: a step if
+    /// target_block is trusted_block + 1, and a skip of at most skip_max blocks otherwise.
+    pub fn new(
+        trusted_block: u64,
+        target_block: u64,
+        skip_max: u64,
+    ) -> Result<Self, TransitionError> {
+        if target_block <= trusted_block {
+            return Err(TransitionError::InvalidRange {
+                trusted_block,
+                target_block,
+            });
+        }
+        if target_block - trusted_block > skip_max {
+            return Err(TransitionError::ExceedsSkipMax {
+                trusted_block,
+                target_block,
+                skip_max,
+            });
+        }
+        if target_block == trusted_block + 1 {
+            Ok(TransitionKind::Step)
+        } else {
+            Ok(TransitionKind::Skip)
+        }
+    }
+}
+
+/// A proof of a skip from trusted_block to target_block, and its public outputs.
+#[derive(Debug, Clone)]
+pub struct SkipProof {
+    pub proof: ProofWithPublicInputs<
+        <L as PlonkParameters<D>>::Field,
+        <L as PlonkParameters<D>>::Config,
+        D,
+    >,
+    pub target_header_hash: H256,
+    pub data_commitment: H256,
+    pub next_validators_hash: H256,
+}
+
+/// A proof of a transition, by the circuit chosen for it.
+#[derive(Debug, Clone)]
+pub enum TransitionProof {
+    Step(StepProof),
+    Skip(SkipProof),
+}
+
+impl TransitionProof {
+    pub fn kind(&self) -> TransitionKind {
+        match self {
+            TransitionProof::Step(_) => TransitionKind::Step,
+            TransitionProof::Skip(_) => TransitionKind::Skip,
+        }
+    }
+}
+
+/// Proves transitions for config C, with the step circuit when the target is the next block and
+/// the skip circuit otherwise. Each circuit is built the first time it's needed, and reused.
+pub struct TransitionProver<
+    const MAX_VALIDATOR_SET_SIZE: usize,
+    const CHAIN_ID_SIZE_BYTES: usize,
+    C: TendermintConfig<CHAIN_ID_SIZE_BYTES>
+        + HeaderLayoutConfig
+        + CheckpointConfig
+        + BlockVersionConfig,
+    const NB_MAP_JOBS: usize,
+    const BATCH_SIZE: usize,
+> {
+    step_prover: StepProver<MAX_VALIDATOR_SET_SIZE, CHAIN_ID_SIZE_BYTES, C>,
+    skip_circuit: Option<CircuitBuild<L, D>>,
+    trusting_period: Duration,
+}
+
+impl<
+        const MAX_VALIDATOR_SET_SIZE: usize,
+        const CHAIN_ID_SIZE_BYTES: usize,
+        C: TendermintConfig<CHAIN_ID_SIZE_BYTES>
+            + HeaderLayoutConfig
+            + CheckpointConfig
+            + BlockVersionConfig,
+        const NB_MAP_JOBS: usize,
+        const BATCH_SIZE: usize,
+    > Default
+    for TransitionProver<MAX_VALIDATOR_SET_SIZE, CHAIN_ID_SIZE_BYTES, C, NB_MAP_JOBS, BATCH_SIZE>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<
+        const MAX_VALIDATOR_SET_SIZE: usize,
+        const CHAIN_ID_SIZE_BYTES: usize,
+        C: TendermintConfig<CHAIN_ID_SIZE_BYTES>
+            + HeaderLayoutConfig
+            + CheckpointConfig
+            + BlockVersionConfig,
+        const NB_MAP_JOBS: usize,
+        const BATCH_SIZE: usize,
+    > TransitionProver<MAX_VALIDATOR_SET_SIZE, CHAIN_ID_SIZE_BYTES, C, NB_MAP_JOBS, BATCH_SIZE>
+{
+    pub fn new() -> Self {
+        Self::with_trusting_period(CELESTIA_TRUSTING_PERIOD)
+    }
+
+    /// A prover which rejects skips whose target header's time is more than trusting_period after
+    /// the trusted header's time.
+    pub fn with_trusting_period(trusting_period: Duration) -> Self {
+        Self {
+            step_prover: StepProver::new(),
+            skip_circuit: None,
+            trusting_period,
+        }
+    }
+
+    /// The most blocks the skip circuit can prove: SKIP_MAX of the config, and at most the
+    /// NB_MAP_JOBS * BATCH_SIZE blocks the circuit's map reduce covers.
+    pub fn skip_max() -> u64 {
+        C::SKIP_MAX.min(NB_MAP_JOBS * BATCH_SIZE) as u64
+    }
+
+    /// The circuit proving the transition from trusted_block to target_block.
+    pub fn transition_kind(
+        trusted_block: u64,
+        target_block: u64,
+    ) -> Result<TransitionKind, TransitionError> {
+        TransitionKind::new(trusted_block, target_block, Self::skip_max())
+    }
+
+    /// Prove the transition from trusted_block to target_block with the circuit chosen by
+    /// transition_kind, building it if it hasn't been built yet.
+    pub async fn prove_transition(
+        &mut self,
+        trusted_block: u64,
+        target_block: u64,
+    ) -> Result<TransitionProof, TransitionError> {
+        match Self::transition_kind(trusted_block, target_block)? {
+            TransitionKind::Step => Ok(TransitionProof::Step(
+                self.step_prover.prove(trusted_block).await?,
+            )),
+            TransitionKind::Skip => Ok(TransitionProof::Skip(
+                self.prove_skip(trusted_block, target_block).await?,
+            )),
+        }
+    }
+
+    async fn prove_skip(
+        &mut self,
+        trusted_block: u64,
+        target_block: u64,
+    ) -> Result<SkipProof, TransitionError> {
+        // Check the skip before building the circuit, so an invalid skip fails fast.
+        let mut data_fetcher = InputDataFetcher::default();
+        let inputs = fetch_skip_inputs(
+            &mut data_fetcher,
+            trusted_block,
+            target_block,
+            self.trusting_period,
+        )
+        .await?;
+
+        if self.skip_circuit.is_none() {
+            let mut builder = DefaultBuilder::new();
+            CombinedSkipCircuit::<
+                MAX_VALIDATOR_SET_SIZE,
+                CHAIN_ID_SIZE_BYTES,
+                C,
+                NB_MAP_JOBS,
+                BATCH_SIZE,
+            >::define(&mut builder);
+            self.skip_circuit = Some(builder.build());
+        }
+        let circuit = self.skip_circuit.as_ref().unwrap();
+
+        // Building the circuit is slow, so the chain may have reorged since the inputs were
+        // fetched.
+        let target_header_hash =
+            H256::from_slice(inputs.target_signed_header.header.hash().as_bytes());
+        data_fetcher
+            .verify_block_still_canonical(target_block, target_header_hash)
+            .await
+            .map_err(|e| TransitionError::Reorg {
+                block: target_block,
+                reason: e.to_string(),
+            })?;
+
+        let mut input = circuit.input();
+        input.evm_write::<U64Variable>(trusted_block);
+        input.evm_write::<Bytes32Variable>(inputs.trusted_header_hash);
+        input.evm_write::<U64Variable>(target_block);
+
+        let (proof, mut output) = circuit.prove_async(&input).await;
+        circuit
+            .data
+            .verify(proof.clone())
+            .map_err(|e| TransitionError::ProofVerification {
+                trusted_block,
+                target_block,
+                reason: e.to_string(),
+            })?;
+
+        let target_header_hash = output.evm_read::<Bytes32Variable>();
+        let data_commitment = output.evm_read::<Bytes32Variable>();
+        let next_validators_hash = output.evm_read::<Bytes32Variable>();
+
+        Ok(SkipProof {
+            proof,
+            target_header_hash,
+            data_commitment,
+            next_validators_hash,
+        })
+    }
+}
+
+/// Fetch the blocks of a skip from trusted_block to target_block and check them with skip_inputs,
+/// which rejects a target block outside trusting_period. The trusted block's commit must be
+/// finalized.
+pub async fn fetch_skip_inputs(
+    data_fetcher: &mut InputDataFetcher,
+    trusted_block: u64,
+    target_block: u64,
+    trusting_period: Duration,
+) -> Result<SkipInputs, TransitionError> {
+    let fetch_error = |block: u64| {
+        move |e: InputDataError| TransitionError::Fetch {
+            block,
+            reason: e.to_string(),
+        }
+    };
+    let trusted_signed_header = data_fetcher
+        .get_finalized_signed_header_from_number(trusted_block)
+        .await
+        .map_err(fetch_error(trusted_block))?;
+    let trusted_validators = data_fetcher
+        .get_verified_validator_set_from_number(trusted_block)
+        .await
+        .map_err(fetch_error(trusted_block))?;
+    let target_signed_header = data_fetcher
+        .get_signed_header_from_number(target_block)
+        .await;
+    let target_validators = data_fetcher
+        .get_verified_validator_set_from_number(target_block)
+        .await
+        .map_err(fetch_error(target_block))?;
+
+    skip_inputs(
+        trusted_signed_header,
+        trusted_validators,
+        target_signed_header,
+        target_validators,
+        trusting_period,
+    )
+    .map_err(TransitionError::InvalidSkip)
+}
+
+#[cfg(test)]
+mod tests {
+    use tendermintx::config::MOCHA_4_CHAIN_ID_SIZE_BYTES;
+    use tendermintx::input::InputDataMode;
+
+    use super::*;
+    use crate::config::Mocha4BlobstreamXConfig1024;
+    use crate::fixture::tests::FIXTURE_PATH;
+
+    type Prover =
+        TransitionProver<4, MOCHA_4_CHAIN_ID_SIZE_BYTES, Mocha4BlobstreamXConfig1024, 2, 2>;
+    type WideProver =
+        TransitionProver<4, MOCHA_4_CHAIN_ID_SIZE_BYTES, Mocha4BlobstreamXConfig1024, 32, 64>;
+
+    #[test]
+    fn test_transition_kind() {
+        // Adjacent blocks are proven with a step.
+        assert_eq!(
+            Prover::transition_kind(10000, 10001),
+            Ok(TransitionKind::Step)
+        );
+
+        // A gap is proven with a skip, up to the NB_MAP_JOBS * BATCH_SIZE = 4 blocks the skip
+        // circuit covers, as that's less than SKIP_MAX.
+        assert_eq!(Prover::skip_max(), 4);
+        assert_eq!(
+            Prover::transition_kind(10000, 10004),
+            Ok(TransitionKind::Skip)
+        );
+        assert_eq!(
+            Prover::transition_kind(10000, 10005),
+            Err(TransitionError::ExceedsSkipMax {
+                trusted_block: 10000,
+                target_block: 10005,
+                skip_max: 4,
+            })
+        );
+
+        // SKIP_MAX bounds the skip when the circuit covers more blocks.
+        assert_eq!(WideProver::skip_max(), 1024);
+        assert_eq!(
+            WideProver::transition_kind(10000, 11024),
+            Ok(TransitionKind::Skip)
+        );
+        assert_eq!(
+            WideProver::transition_kind(10000, 11025),
+            Err(TransitionError::ExceedsSkipMax {
+                trusted_block: 10000,
+                target_block: 11025,
+                skip_max: 1024,
+            })
+        );
+
+        assert_eq!(
+            Prover::transition_kind(10000, 10000),
+            Err(TransitionError::InvalidRange {
+                trusted_block: 10000,
+                target_block: 10000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_step_prove_error() {
+        // A step fails with the same errors as a skip.
+        assert_eq!(
+            TransitionError::from(StepProveError::ProofVerification {
+                prev_block: 10000,
+                reason: String::from("invalid proof"),
+            }),
+            TransitionError::ProofVerification {
+                trusted_block: 10000,
+                target_block: 10001,
+                reason: String::from("invalid proof"),
+            }
+        );
+        assert_eq!(
+            TransitionError::from(StepProveError::Reorg {
+                block: 10001,
+                reason: String::from("reorged"),
+            }),
+            TransitionError::Reorg {
+                block: 10001,
+                reason: String::from("reorged"),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_skip_inputs() {
+        let mut data_fetcher = InputDataFetcher::default();
+        data_fetcher.mode = InputDataMode::Fixture;
+        data_fetcher.fixture_path = String::from(FIXTURE_PATH);
+
+        let inputs = fetch_skip_inputs(&mut data_fetcher, 10000, 10004, CELESTIA_TRUSTING_PERIOD)
+            .await
+            .unwrap();
+        assert_eq!(inputs.trusted_block, 10000);
+        assert_eq!(inputs.target_block, 10004);
+
+        // Block 10004 is 46.65s after block 10000.
+        assert!(matches!(
+            fetch_skip_inputs(&mut data_fetcher, 10000, 10004, Duration::from_secs(46)).await,
+            Err(TransitionError::InvalidSkip(
+                SkipInputsError::OutsideTrustingPeriod {
+                    target_block: 10004,
+                    ..
+                }
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(feature = "ci", ignore)]
+    async fn test_prove_transition_step() {
+        env_logger::try_init().unwrap_or_default();
+        dotenv::dotenv().ok();
+
+        let mut prover = Prover::new();
+        let proof = prover.prove_transition(500, 501).await.unwrap();
+        assert_eq!(proof.kind(), TransitionKind::Step);
+        assert!(prover.skip_circuit.is_none());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(feature = "ci", ignore)]
+    async fn test_prove_transition_skip() {
+        env_logger::try_init().unwrap_or_default();
+        dotenv::dotenv().ok();
+
+        let mut prover = Prover::new();
+        let proof = prover.prove_transition(500, 504).await.unwrap();
+        assert_eq!(proof.kind(), TransitionKind::Skip);
+        assert!(prover.skip_circuit.is_some());
+    }
+}
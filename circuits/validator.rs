@@ -0,0 +1,1610 @@
+use ethers::types::H256;
+use plonky2x::frontend::merkle::tendermint::TendermintMerkleTree;
+use plonky2x::frontend::uint::uint128::U128Variable;
+use plonky2x::frontend::uint::uint64::U64Variable;
+use plonky2x::prelude::*;
+use tendermint::validator::Info;
+
+use crate::consts::{
+    ADDRESS_SIZE_BYTES, MERKLE_LEAF_PREFIX, VALIDATOR_PUBKEY_PREFIX,
+    VALIDATOR_VOTING_POWER_MAX_SIZE_BYTES, VALIDATOR_VOTING_POWER_TAG,
+};
+use crate::vars::{Validator, ValidatorSignerVariable, ValidatorVariable};
+
+/// Gadgets over the validator set of a block.
+pub trait ValidatorBuilder<L: PlonkParameters<D>, const D: usize> {
+    /// Assert the enabled validators form a prefix of the validator set. Specifically, once a
+    /// validator is disabled, all subsequent validators must also be disabled. The Tendermint
+    /// merkle tree computation over the validator set assumes the disabled leaves are trailing.
+    fn assert_enabled_prefix(&mut self, enabled: &[BoolVariable]);
+
+    /// Assert exactly num_enabled validators are enabled, where num_enabled is the size of the
+    /// block's validator set. Together with assert_enabled_prefix, the enabled validators are the
+    /// first num_enabled, so a validator of the set can't be disabled to change the tree.
+    fn assert_enabled_count(&mut self, enabled: &[BoolVariable], num_enabled: &Variable);
+
+    /// Assert at least one validator is enabled. A block always has a validator, and the merkle
+    /// tree over an empty validator set is the hash of no leaves rather than an inner node.
+    fn assert_nonempty_validator_set(&mut self, enabled: &[BoolVariable]);
+
+    /// Compute the leaf hash of a validator in the validator set's merkle tree. The leaf is
+    /// variable length, as the voting power is encoded as a varint, so the padding of the single
+    /// SHA256 chunk is conditioned on the encoded length rather than hashing every possible length
+    /// and selecting one. A zero voting power is omitted from the leaf, as in protobuf, and the
+    /// voting power is asserted to be at most i64::MAX, so it fits in the 9-byte varint.
+    fn hash_validator_leaf(
+        &mut self,
+        pubkey: &Bytes32Variable,
+        voting_power: &U64Variable,
+    ) -> Bytes32Variable;
+
+    /// Assert the leaf hashes of the disabled validators are the zero hash. A disabled right child
+    /// is skipped when hashing a layer of the Tendermint merkle tree, so the root doesn't depend on
+    /// the disabled leaf hashes, and without this check they could hold arbitrary values.
+    fn assert_disabled_leaf_hashes_zero(
+        &mut self,
+        leaf_hashes: &[Bytes32Variable],
+        enabled: &[BoolVariable],
+    );
+
+    /// Compute the root of the validator set's merkle tree from the leaf hashes of its validators,
+    /// where nb_validators is the size of the validator set. The enabled validators must be the
+    /// first nb_validators, of which there is at least one, and the leaf hashes of the disabled
+    /// validators must be zero.
+    fn get_validators_root<const MAX_VALIDATOR_SET_SIZE: usize>(
+        &mut self,
+        leaf_hashes: &ArrayVariable<Bytes32Variable, MAX_VALIDATOR_SET_SIZE>,
+        enabled: &[BoolVariable],
+        nb_validators: &Variable,
+    ) -> Bytes32Variable;
+
+    /// Verify the enabled validators hash to validators_hash, where nb_validators is the size of
+    /// the validator set, so a validator of the set can't be dropped by disabling it.
+    fn verify_validators_hash<const MAX_VALIDATOR_SET_SIZE: usize>(
+        &mut self,
+        validators_hash: &Bytes32Variable,
+        validators: &ArrayVariable<ValidatorVariable, MAX_VALIDATOR_SET_SIZE>,
+        nb_validators: &Variable,
+    );
+
+    /// Verify the enabled validators of next_validators hash to next_validators_hash, and assert
+    /// their total voting power is positive, as a next validator set without voting power halts the
+    /// chain after the handoff. Only the hash of the next validator set is proven against the
+    /// header, so the set itself is an input.
+    fn assert_next_validators_power_positive<const MAX_VALIDATOR_SET_SIZE: usize>(
+        &mut self,
+        next_validators_hash: &Bytes32Variable,
+        next_validators: &ArrayVariable<ValidatorVariable, MAX_VALIDATOR_SET_SIZE>,
+    );
+
+    /// Assert the pubkeys of the enabled validators are pairwise distinct, so a validator's voting
+    /// power can't be counted more than once.
+    fn assert_distinct_pubkeys(&mut self, pubkeys: &[Bytes32Variable], enabled: &[BoolVariable]);
+
+    /// Return whether a validator with target_pubkey is in validators and signed the commit.
+    fn prove_validator_signed(
+        &mut self,
+        target_pubkey: &Bytes32Variable,
+        validators: &[ValidatorSignerVariable],
+    ) -> BoolVariable;
+
+    /// Derive a validator's 20-byte address from its pubkey: the first 20 bytes of the SHA256 of
+    /// the pubkey.
+    fn pubkey_to_address(&mut self, pubkey: &Bytes32Variable) -> BytesVariable<ADDRESS_SIZE_BYTES>;
+
+    /// Assert the proposer (by its 20-byte address) is in validators and signed the commit. A
+    /// validator's address is the first 20 bytes of the SHA256 of its pubkey. Optional: a commit
+    /// is valid without the proposer's signature, so only use this where the proposer must sign.
+    fn assert_proposer_signed(
+        &mut self,
+        proposer_address: &[ByteVariable],
+        validators: &[ValidatorSignerVariable],
+    );
+
+    /// Assert the verified validators are signers, that there are at most max_sigs_to_verify of
+    /// them, and that their voting power alone exceeds 2/3 of the total voting power. Signature
+    /// verification can then be gated on verified rather than signed, capping the number of
+    /// signatures verified for large validator sets.
+    fn assert_verified_signers_exceed_threshold(
+        &mut self,
+        voting_powers: &[U64Variable],
+        signed: &[BoolVariable],
+        verified: &[BoolVariable],
+        max_sigs_to_verify: usize,
+    );
+
+    /// Assert value is not zero.
+    fn assert_nonzero_u32(&mut self, value: &U32Variable);
+
+    /// Return whether voting_power is greater than threshold_numerator / threshold_denominator of
+    /// total_voting_power. The threshold denominator is asserted to be nonzero, as the check is
+    /// meaningless otherwise.
+    fn voting_power_greater_than_threshold(
+        &mut self,
+        voting_power: &U64Variable,
+        total_voting_power: &U64Variable,
+        threshold_numerator: &U32Variable,
+        threshold_denominator: &U32Variable,
+    ) -> BoolVariable;
+
+    /// Assert voting_power is at most i64::MAX. Tendermint voting powers are non-negative i64s, so
+    /// a voting power with the high bit set would be negative.
+    fn assert_valid_voting_power(&mut self, voting_power: &U64Variable);
+
+    /// Add two voting powers, asserting the sum doesn't overflow i64::MAX. Tendermint voting
+    /// powers are non-negative i64s, so both inputs are asserted to be at most i64::MAX.
+    fn add_voting_power(&mut self, a: &U64Variable, b: &U64Variable) -> U64Variable;
+
+    /// Subtract voting power b from a, saturating at zero. Both inputs are asserted to be at most
+    /// i64::MAX.
+    fn saturating_sub_voting_power(&mut self, a: &U64Variable, b: &U64Variable) -> U64Variable;
+
+    /// Verify header_time is the BFT time of the commit: the median of the timestamps of its
+    /// signatures, weighted by the voting powers of their validators. Signatures which are absent
+    /// (not signed) are excluded, as in Tendermint. Times are in nanoseconds since the Unix epoch.
+    /// Optional: header_time is the time of the block after the commit's block, whose last commit
+    /// is the commit.
+    /// Spec: https://github.com/cometbft/cometbft/blob/v0.34.x/spec/consensus/bft-time.md
+    fn verify_bft_time(
+        &mut self,
+        signed_timestamps: &[U64Variable],
+        powers: &[U64Variable],
+        signed: &[BoolVariable],
+        header_time: &U64Variable,
+    );
+}
+
+// Voting powers are non-negative i64s, so they fit in 63 bits.
+const VOTING_POWER_NB_BITS: usize = 63;
+
+impl<L: PlonkParameters<D>, const D: usize> ValidatorBuilder<L, D> for CircuitBuilder<L, D> {
+    fn assert_enabled_prefix(&mut self, enabled: &[BoolVariable]) {
+        let true_var = self._true();
+        for i in 1..enabled.len() {
+            // If validator i is enabled, validator i-1 must be enabled.
+            let is_disabled = self.not(enabled[i]);
+            let prefix_check = self.or(is_disabled, enabled[i - 1]);
+            self.assert_is_equal(prefix_check, true_var);
+        }
+    }
+
+    fn assert_enabled_count(&mut self, enabled: &[BoolVariable], num_enabled: &Variable) {
+        let mut count = self.zero();
+        for is_enabled in enabled.iter() {
+            count = self.add(count, is_enabled.variable);
+        }
+        self.assert_is_equal(count, *num_enabled);
+    }
+
+    fn assert_nonempty_validator_set(&mut self, enabled: &[BoolVariable]) {
+        assert!(!enabled.is_empty());
+        let mut any_enabled = self._false();
+        for is_enabled in enabled.iter() {
+            any_enabled = self.or(any_enabled, *is_enabled);
+        }
+        let true_var = self._true();
+        self.assert_is_equal(any_enabled, true_var);
+    }
+
+    fn hash_validator_leaf(
+        &mut self,
+        pubkey: &Bytes32Variable,
+        voting_power: &U64Variable,
+    ) -> Bytes32Variable {
+        self.assert_valid_voting_power(voting_power);
+        let (encoded_validator, byte_length) = marshal_validator(self, pubkey, voting_power);
+
+        // The leaf prefix, the validator, and the SHA256 padding fit in a single chunk.
+        let mut leaf = vec![self.constant::<ByteVariable>(MERKLE_LEAF_PREFIX)];
+        leaf.extend(encoded_validator);
+        let one = self.one();
+        let leaf_length = self.add(byte_length, one);
+        let leaf_length = U32Variable::from_variables_unsafe(&[leaf_length]);
+        let last_chunk = self.constant::<U32Variable>(0);
+        self.curta_sha256_variable::<1>(&leaf, last_chunk, leaf_length)
+    }
+
+    fn assert_disabled_leaf_hashes_zero(
+        &mut self,
+        leaf_hashes: &[Bytes32Variable],
+        enabled: &[BoolVariable],
+    ) {
+        assert_eq!(leaf_hashes.len(), enabled.len());
+        let true_var = self._true();
+        let zero_hash = self.constant::<Bytes32Variable>(H256::zero());
+        for (leaf_hash, is_enabled) in leaf_hashes.iter().zip(enabled.iter()) {
+            let is_zero_hash = self.is_equal(*leaf_hash, zero_hash);
+            let disabled_check = self.or(*is_enabled, is_zero_hash);
+            self.assert_is_equal(disabled_check, true_var);
+        }
+    }
+
+    fn get_validators_root<const MAX_VALIDATOR_SET_SIZE: usize>(
+        &mut self,
+        leaf_hashes: &ArrayVariable<Bytes32Variable, MAX_VALIDATOR_SET_SIZE>,
+        enabled: &[BoolVariable],
+        nb_validators: &Variable,
+    ) -> Bytes32Variable {
+        assert_eq!(enabled.len(), MAX_VALIDATOR_SET_SIZE);
+        self.assert_nonempty_validator_set(enabled);
+        self.assert_enabled_prefix(enabled);
+        self.assert_enabled_count(enabled, nb_validators);
+        self.assert_disabled_leaf_hashes_zero(&leaf_hashes.as_vec(), enabled);
+        self.get_root_from_hashed_leaves::<MAX_VALIDATOR_SET_SIZE>(
+            leaf_hashes.clone(),
+            *nb_validators,
+        )
+    }
+
+    fn verify_validators_hash<const MAX_VALIDATOR_SET_SIZE: usize>(
+        &mut self,
+        validators_hash: &Bytes32Variable,
+        validators: &ArrayVariable<ValidatorVariable, MAX_VALIDATOR_SET_SIZE>,
+        nb_validators: &Variable,
+    ) {
+        let zero_hash = self.constant::<Bytes32Variable>(H256::zero());
+        let mut leaf_hashes = Vec::new();
+        let mut enabled = Vec::new();
+        for validator in validators.as_vec().iter() {
+            let leaf_hash = self.hash_validator_leaf(&validator.pubkey, &validator.voting_power);
+            leaf_hashes.push(self.select(validator.enabled, leaf_hash, zero_hash));
+            enabled.push(validator.enabled);
+        }
+        let root = self.get_validators_root::<MAX_VALIDATOR_SET_SIZE>(
+            &ArrayVariable::<Bytes32Variable, MAX_VALIDATOR_SET_SIZE>::from(leaf_hashes),
+            &enabled,
+            nb_validators,
+        );
+        self.assert_is_equal(root, *validators_hash);
+    }
+
+    fn assert_next_validators_power_positive<const MAX_VALIDATOR_SET_SIZE: usize>(
+        &mut self,
+        next_validators_hash: &Bytes32Variable,
+        next_validators: &ArrayVariable<ValidatorVariable, MAX_VALIDATOR_SET_SIZE>,
+    ) {
+        let zero_hash = self.constant::<Bytes32Variable>(H256::zero());
+        let zero = self.constant::<U64Variable>(0);
+        let mut leaf_hashes = Vec::new();
+        let mut enabled = Vec::new();
+        let mut nb_validators = self.zero();
+        let mut total_voting_power = zero;
+        for validator in next_validators.as_vec().iter() {
+            let leaf_hash = self.hash_validator_leaf(&validator.pubkey, &validator.voting_power);
+            leaf_hashes.push(self.select(validator.enabled, leaf_hash, zero_hash));
+            enabled.push(validator.enabled);
+            nb_validators = self.add(nb_validators, validator.enabled.variable);
+
+            let voting_power = self.select(validator.enabled, validator.voting_power, zero);
+            total_voting_power = self.add_voting_power(&total_voting_power, &voting_power);
+        }
+        let root = self.get_validators_root::<MAX_VALIDATOR_SET_SIZE>(
+            &ArrayVariable::<Bytes32Variable, MAX_VALIDATOR_SET_SIZE>::from(leaf_hashes),
+            &enabled,
+            &nb_validators,
+        );
+        self.assert_is_equal(root, *next_validators_hash);
+
+        let false_var = self._false();
+        let is_zero_power = self.is_equal(total_voting_power, zero);
+        self.assert_is_equal(is_zero_power, false_var);
+    }
+
+    fn assert_distinct_pubkeys(&mut self, pubkeys: &[Bytes32Variable], enabled: &[BoolVariable]) {
+        assert_eq!(pubkeys.len(), enabled.len());
+        let false_var = self._false();
+        for i in 0..pubkeys.len() {
+            for j in i + 1..pubkeys.len() {
+                // If validators i and j are both enabled, their pubkeys must differ.
+                let both_enabled = self.and(enabled[i], enabled[j]);
+                let is_duplicate = self.is_equal(pubkeys[i], pubkeys[j]);
+                let is_enabled_duplicate = self.and(both_enabled, is_duplicate);
+                self.assert_is_equal(is_enabled_duplicate, false_var);
+            }
+        }
+    }
+
+    fn prove_validator_signed(
+        &mut self,
+        target_pubkey: &Bytes32Variable,
+        validators: &[ValidatorSignerVariable],
+    ) -> BoolVariable {
+        let mut is_signer = self._false();
+        for validator in validators.iter() {
+            let is_target = self.is_equal(validator.pubkey, *target_pubkey);
+            let is_target_signer = self.and(is_target, validator.signed);
+            is_signer = self.or(is_signer, is_target_signer);
+        }
+        is_signer
+    }
+
+    fn pubkey_to_address(&mut self, pubkey: &Bytes32Variable) -> BytesVariable<ADDRESS_SIZE_BYTES> {
+        let pubkey_hash = self.sha256(&pubkey.as_bytes());
+        BytesVariable::<ADDRESS_SIZE_BYTES>(
+            pubkey_hash.as_bytes()[..ADDRESS_SIZE_BYTES]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    fn assert_proposer_signed(
+        &mut self,
+        proposer_address: &[ByteVariable],
+        validators: &[ValidatorSignerVariable],
+    ) {
+        assert_eq!(proposer_address.len(), ADDRESS_SIZE_BYTES);
+        let true_var = self._true();
+        let mut proposer_signed = self._false();
+        for validator in validators.iter() {
+            let address = self.pubkey_to_address(&validator.pubkey);
+            let mut is_proposer = self._true();
+            for (proposer_byte, address_byte) in proposer_address.iter().zip(address.0.iter()) {
+                let is_equal_byte = self.is_equal(*proposer_byte, *address_byte);
+                is_proposer = self.and(is_proposer, is_equal_byte);
+            }
+            let is_proposer_signer = self.and(is_proposer, validator.signed);
+            proposer_signed = self.or(proposer_signed, is_proposer_signer);
+        }
+        self.assert_is_equal(proposer_signed, true_var);
+    }
+
+    fn assert_verified_signers_exceed_threshold(
+        &mut self,
+        voting_powers: &[U64Variable],
+        signed: &[BoolVariable],
+        verified: &[BoolVariable],
+        max_sigs_to_verify: usize,
+    ) {
+        assert_eq!(voting_powers.len(), signed.len());
+        assert_eq!(voting_powers.len(), verified.len());
+
+        let true_var = self._true();
+        let zero = self.constant::<U64Variable>(0);
+        let one = self.constant::<U64Variable>(1);
+        let mut total_voting_power = zero;
+        let mut verified_voting_power = zero;
+        let mut nb_verified = zero;
+        for i in 0..voting_powers.len() {
+            // A validator can only be verified if it signed.
+            let not_verified = self.not(verified[i]);
+            let verified_implies_signed = self.or(not_verified, signed[i]);
+            self.assert_is_equal(verified_implies_signed, true_var);
+
+            // Each voting power and the total are at most i64::MAX, so the sums don't wrap.
+            total_voting_power = self.add_voting_power(&total_voting_power, &voting_powers[i]);
+            let power = self.select(verified[i], voting_powers[i], zero);
+            verified_voting_power = self.add(verified_voting_power, power);
+            let count = self.select(verified[i], one, zero);
+            nb_verified = self.add(nb_verified, count);
+        }
+
+        let max_sigs_to_verify = self.constant::<U64Variable>(max_sigs_to_verify as u64);
+        let is_within_cap = self.lte(nb_verified, max_sigs_to_verify);
+        self.assert_is_equal(is_within_cap, true_var);
+
+        // verified_voting_power * 3 > total_voting_power * 2, over 128 bits so a total voting power
+        // above 2^64 / 3 doesn't overflow the products.
+        let two = self.constant::<U32Variable>(2);
+        let three = self.constant::<U32Variable>(3);
+        let exceeds_threshold = self.voting_power_greater_than_threshold(
+            &verified_voting_power,
+            &total_voting_power,
+            &two,
+            &three,
+        );
+        self.assert_is_equal(exceeds_threshold, true_var);
+    }
+
+    fn assert_nonzero_u32(&mut self, value: &U32Variable) {
+        let false_var = self._false();
+        let zero = self.constant::<U32Variable>(0);
+        let is_zero = self.is_equal(*value, zero);
+        self.assert_is_equal(is_zero, false_var);
+    }
+
+    fn voting_power_greater_than_threshold(
+        &mut self,
+        voting_power: &U64Variable,
+        total_voting_power: &U64Variable,
+        threshold_numerator: &U32Variable,
+        threshold_denominator: &U32Variable,
+    ) -> BoolVariable {
+        self.assert_nonzero_u32(threshold_denominator);
+
+        // voting_power * threshold_denominator > total_voting_power * threshold_numerator. The
+        // products are computed over 128 bits, so they can't overflow.
+        let zero = self.constant::<U32Variable>(0);
+        let voting_power = U128Variable {
+            limbs: [voting_power.limbs[0], voting_power.limbs[1], zero, zero],
+        };
+        let total_voting_power = U128Variable {
+            limbs: [
+                total_voting_power.limbs[0],
+                total_voting_power.limbs[1],
+                zero,
+                zero,
+            ],
+        };
+        let threshold_numerator = U128Variable {
+            limbs: [*threshold_numerator, zero, zero, zero],
+        };
+        let threshold_denominator = U128Variable {
+            limbs: [*threshold_denominator, zero, zero, zero],
+        };
+        let scaled_voting_power = self.mul(voting_power, threshold_denominator);
+        let scaled_total_voting_power = self.mul(total_voting_power, threshold_numerator);
+        self.gt(scaled_voting_power, scaled_total_voting_power)
+    }
+
+    fn assert_valid_voting_power(&mut self, voting_power: &U64Variable) {
+        self.api
+            .range_check(voting_power.limbs[1].variable.0, VOTING_POWER_NB_BITS - 32);
+    }
+
+    fn add_voting_power(&mut self, a: &U64Variable, b: &U64Variable) -> U64Variable {
+        // Both inputs are below 2^63, so their sum is below 2^64 and the carry out of the upper
+        // limb is always zero.
+        self.assert_valid_voting_power(a);
+        self.assert_valid_voting_power(b);
+        let sum = self.add(*a, *b);
+        // sum <= i64::MAX
+        self.api
+            .range_check(sum.limbs[1].variable.0, VOTING_POWER_NB_BITS - 32);
+        sum
+    }
+
+    fn saturating_sub_voting_power(&mut self, a: &U64Variable, b: &U64Variable) -> U64Variable {
+        self.assert_valid_voting_power(a);
+        self.assert_valid_voting_power(b);
+        // a - b borrows out of the upper limb and wraps around if a < b.
+        let zero = self.constant::<U64Variable>(0);
+        let is_underflow = self.lt(*a, *b);
+        let difference = self.sub(*a, *b);
+        self.select(is_underflow, zero, difference)
+    }
+
+    fn verify_bft_time(
+        &mut self,
+        signed_timestamps: &[U64Variable],
+        powers: &[U64Variable],
+        signed: &[BoolVariable],
+        header_time: &U64Variable,
+    ) {
+        assert_eq!(signed_timestamps.len(), powers.len());
+        assert_eq!(signed_timestamps.len(), signed.len());
+
+        // Tendermint sorts the timestamps and picks the first at which the running sum of the
+        // voting powers reaches half = total_voting_power / 2. Without sorting, that's the signed
+        // timestamp t with power_before(t) < half <= power_up_to(t), or the earliest signed
+        // timestamp if half is zero.
+        let true_var = self._true();
+        let zero = self.constant::<U64Variable>(0);
+        let mut total_voting_power = zero;
+        let mut power_before = zero;
+        let mut power_up_to = zero;
+        let mut is_signed_timestamp = self._false();
+        let mut is_earliest = self._true();
+        for i in 0..signed_timestamps.len() {
+            let power = self.select(signed[i], powers[i], zero);
+            total_voting_power = self.add_voting_power(&total_voting_power, &power);
+
+            let is_before = self.lt(signed_timestamps[i], *header_time);
+            let power_if_before = self.select(is_before, power, zero);
+            power_before = self.add_voting_power(&power_before, &power_if_before);
+            let is_up_to = self.lte(signed_timestamps[i], *header_time);
+            let power_if_up_to = self.select(is_up_to, power, zero);
+            power_up_to = self.add_voting_power(&power_up_to, &power_if_up_to);
+
+            let is_equal = self.is_equal(signed_timestamps[i], *header_time);
+            let is_signed_equal = self.and(signed[i], is_equal);
+            is_signed_timestamp = self.or(is_signed_timestamp, is_signed_equal);
+            let is_signed_before = self.and(signed[i], is_before);
+            let not_signed_before = self.not(is_signed_before);
+            is_earliest = self.and(is_earliest, not_signed_before);
+        }
+        self.assert_is_equal(is_signed_timestamp, true_var);
+
+        // With half = total_voting_power / 2, power_before < half iff power_before + 1 <=
+        // total_voting_power - power_before - 1, and half <= power_up_to iff total_voting_power <=
+        // 2 * power_up_to + 1. power_before and power_up_to are at most total_voting_power, which
+        // is at most i64::MAX, so neither side overflows.
+        let one = self.constant::<U64Variable>(1);
+        let power_before_plus_one = self.add(power_before, one);
+        let power_not_before = self.sub(total_voting_power, power_before);
+        let power_not_before = self.saturating_sub_voting_power(&power_not_before, &one);
+        let is_below_half = self.lte(power_before_plus_one, power_not_before);
+        let below_half_or_earliest = self.or(is_below_half, is_earliest);
+        self.assert_is_equal(below_half_or_earliest, true_var);
+
+        let double_power_up_to = self.add(power_up_to, power_up_to);
+        let double_power_up_to = self.add(double_power_up_to, one);
+        let reaches_half = self.lte(total_voting_power, double_power_up_to);
+        self.assert_is_equal(reaches_half, true_var);
+    }
+}
+
+/// Encode a validator as a protobuf SimpleValidator, padded to VALIDATOR_MAX_SIZE_BYTES, and return
+/// the encoding and its length in bytes. The bytes past the length are ignored.
+fn marshal_validator<L: PlonkParameters<D>, const D: usize>(
+    builder: &mut CircuitBuilder<L, D>,
+    pubkey: &Bytes32Variable,
+    voting_power: &U64Variable,
+) -> (Vec<ByteVariable>, Variable) {
+    let mut encoded = VALIDATOR_PUBKEY_PREFIX
+        .iter()
+        .map(|b| builder.constant::<ByteVariable>(*b))
+        .collect::<Vec<_>>();
+    encoded.extend(pubkey.as_bytes());
+    let length_without_power =
+        builder.constant::<Variable>(L::Field::from_canonical_usize(encoded.len()));
+    encoded.push(builder.constant::<ByteVariable>(VALIDATOR_VOTING_POWER_TAG));
+
+    // Each byte of the varint holds 7 bits of the voting power, least significant first, and its
+    // top bit is set if any higher bit of the voting power is set.
+    let false_var = builder._false();
+    let power_bits = builder.to_le_bits(*voting_power);
+    let mut has_more = vec![false_var; VALIDATOR_VOTING_POWER_MAX_SIZE_BYTES];
+    let mut any_higher_bit = false_var;
+    for i in (0..VALIDATOR_VOTING_POWER_MAX_SIZE_BYTES).rev() {
+        has_more[i] = any_higher_bit;
+        for bit in power_bits.iter().skip(7 * i).take(7) {
+            any_higher_bit = builder.or(any_higher_bit, *bit);
+        }
+    }
+
+    // The varint has at least one byte, and one more for each byte with its top bit set.
+    let mut byte_length =
+        builder.constant::<Variable>(L::Field::from_canonical_usize(encoded.len() + 1));
+    for (i, more) in has_more.iter().enumerate() {
+        // The bits of a ByteVariable are big-endian.
+        let mut bits = [false_var; 8];
+        bits[0] = *more;
+        for j in 0..7 {
+            if let Some(bit) = power_bits.get(7 * i + j) {
+                bits[7 - j] = *bit;
+            }
+        }
+        encoded.push(ByteVariable(bits));
+        byte_length = builder.add(byte_length, more.variable);
+    }
+
+    // A zero voting power is the protobuf default, so the field is omitted.
+    let zero = builder.constant::<U64Variable>(0);
+    let is_zero_power = builder.is_equal(*voting_power, zero);
+    let byte_length = builder.select(is_zero_power, length_without_power, byte_length);
+    (encoded, byte_length)
+}
+
+/// The values of a validator set as ValidatorVariables, padded with disabled validators to
+/// MAX_VALIDATOR_SET_SIZE.
+pub fn to_validator_values<F: RichField, const MAX_VALIDATOR_SET_SIZE: usize>(
+    validators: &[Info],
+) -> Vec<Validator<F>> {
+    assert!(validators.len() <= MAX_VALIDATOR_SET_SIZE);
+    let mut values = validators
+        .iter()
+        .map(|v| Validator::<F> {
+            pubkey: H256::from_slice(&v.pub_key.to_bytes()),
+            voting_power: v.power.value(),
+            enabled: true,
+        })
+        .collect::<Vec<_>>();
+    values.resize(
+        MAX_VALIDATOR_SET_SIZE,
+        Validator::<F> {
+            pubkey: H256::zero(),
+            voting_power: 0,
+            enabled: false,
+        },
+    );
+    values
+}
+
+/// Select the signers whose signatures are verified when at most max_sigs_to_verify signatures
+/// can be verified: the max_sigs_to_verify signers with the highest voting power. Panics if their
+/// voting power does not exceed 2/3 of the total voting power of the validator set.
+/// Returns whether each validator is selected.
+pub fn select_signers_to_verify(
+    validators: &[Info],
+    signed: &[bool],
+    max_sigs_to_verify: usize,
+) -> Vec<bool> {
+    assert_eq!(validators.len(), signed.len());
+
+    let total_voting_power = validators
+        .iter()
+        .map(|v| v.power.value() as u128)
+        .sum::<u128>();
+
+    let mut signers = (0..validators.len())
+        .filter(|&i| signed[i])
+        .collect::<Vec<_>>();
+    signers.sort_by_key(|&i| std::cmp::Reverse(validators[i].power.value()));
+    signers.truncate(max_sigs_to_verify);
+
+    let verified_voting_power = signers
+        .iter()
+        .map(|&i| validators[i].power.value() as u128)
+        .sum::<u128>();
+    assert!(
+        verified_voting_power * 3 > total_voting_power * 2,
+        "the top {} signers do not exceed 2/3 of the voting power",
+        max_sigs_to_verify
+    );
+
+    let mut verified = vec![false; validators.len()];
+    for i in signers {
+        verified[i] = true;
+    }
+    verified
+}
+
+/// Select the minimal set of signers whose voting power exceeds threshold_num / threshold_denom
+/// of the total voting power of the validator set. Greedily picks the highest-power signers.
+/// Returns the indices of the selected validators in ascending order.
+/// Note: validators is the full validator set and signed marks which validators signed.
+pub fn select_minimal_signing_set(
+    validators: &[Info],
+    signed: &[bool],
+    threshold_num: u64,
+    threshold_denom: u64,
+) -> Vec<usize> {
+    assert_eq!(validators.len(), signed.len());
+
+    let total_voting_power = validators
+        .iter()
+        .map(|v| v.power.value() as u128)
+        .sum::<u128>();
+
+    let mut signers = (0..validators.len())
+        .filter(|&i| signed[i])
+        .collect::<Vec<_>>();
+    signers.sort_by_key(|&i| std::cmp::Reverse(validators[i].power.value()));
+
+    let mut selected = Vec::new();
+    let mut selected_voting_power = 0u128;
+    for i in signers {
+        if selected_voting_power * threshold_denom as u128
+            > total_voting_power * threshold_num as u128
+        {
+            break;
+        }
+        selected_voting_power += validators[i].power.value() as u128;
+        selected.push(i);
+    }
+
+    assert!(
+        selected_voting_power * threshold_denom as u128
+            > total_voting_power * threshold_num as u128,
+        "signers do not exceed the voting power threshold"
+    );
+
+    selected.sort();
+    selected
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use ed25519_consensus::SigningKey;
+    use plonky2x::frontend::merkle::tree::MerkleInclusionProofVariable;
+    use sha2::{Digest, Sha256};
+    use tendermint::block::CommitSig;
+    use tendermint::validator::Set as ValidatorSet;
+    use tendermint::vote::Power;
+    use tendermint::PublicKey;
+    use tendermintx::input::InputDataFetcher;
+
+    use super::*;
+    use crate::config::ChainConfig;
+    use crate::consts::*;
+    use crate::fixture::adversarial::{
+        with_insufficient_power, with_wrong_validator_hash, BlockFixture,
+    };
+    use crate::fixture::tests::FIXTURE_PATH;
+    use crate::fixture::{get_signed_block, get_signed_block_validators};
+    use crate::header::HeaderBuilder;
+    use crate::input::compute_validators_hash;
+    use crate::input::DataCommitmentInputFetcher;
+    use crate::vars::ValidatorSigner;
+
+    /// Generate a validator with a random Ed25519 key and the given voting power.
+    pub(crate) fn make_test_validator(power: u64) -> Info {
+        let signing_key = SigningKey::new(rand::thread_rng());
+        let pubkey =
+            PublicKey::from_raw_ed25519(signing_key.verification_key().as_bytes()).unwrap();
+        Info::new(pubkey, Power::try_from(power).unwrap())
+    }
+
+    /// A validator's voting power and flags as circuit constants.
+    pub(crate) struct TestValidatorVariable {
+        pub(crate) voting_power: U64Variable,
+        pub(crate) signed: BoolVariable,
+        pub(crate) enabled: BoolVariable,
+        pub(crate) verified: BoolVariable,
+    }
+
+    /// Build the circuit constants of a validator. The voting power is split into its 32-bit limbs
+    /// by U64Variable, so tests don't split it by hand.
+    pub(crate) fn make_validator_variable(
+        builder: &mut CircuitBuilder<L, D>,
+        power: u64,
+        signed: bool,
+        enabled: bool,
+        verified: bool,
+    ) -> TestValidatorVariable {
+        TestValidatorVariable {
+            voting_power: builder.constant::<U64Variable>(power),
+            signed: builder.constant::<BoolVariable>(signed),
+            enabled: builder.constant::<BoolVariable>(enabled),
+            verified: builder.constant::<BoolVariable>(verified),
+        }
+    }
+
+    type L = DefaultParameters;
+    type F = <L as PlonkParameters<D>>::Field;
+    const D: usize = 2;
+
+    fn test_assert_enabled_prefix_template(enabled: &[bool]) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+
+        let enabled = enabled
+            .iter()
+            .map(|e| builder.constant::<BoolVariable>(*e))
+            .collect::<Vec<_>>();
+        builder.assert_enabled_prefix(&enabled);
+
+        let circuit = builder.build();
+        let input = circuit.input();
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_assert_enabled_prefix() {
+        test_assert_enabled_prefix_template(&[true, true, false, false]);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_enabled_prefix_interleaved() {
+        test_assert_enabled_prefix_template(&[true, false, true, false]);
+    }
+
+    fn test_assert_enabled_count_template(enabled: &[bool], num_enabled: usize) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let enabled = enabled
+            .iter()
+            .map(|e| builder.constant::<BoolVariable>(*e))
+            .collect::<Vec<_>>();
+        let num_enabled = builder.read::<Variable>();
+        builder.assert_enabled_prefix(&enabled);
+        builder.assert_enabled_count(&enabled, &num_enabled);
+
+        let circuit = builder.build();
+        let mut input = circuit.input();
+        input.write::<Variable>(F::from_canonical_usize(num_enabled));
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_assert_enabled_count() {
+        // The validator set of the block is padded to 4 validators.
+        let validators = get_signed_block_validators(FIXTURE_PATH, 10000);
+        let enabled = (0..4).map(|i| i < validators.len()).collect::<Vec<_>>();
+        test_assert_enabled_count_template(&enabled, validators.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_enabled_count_disabled_validator() {
+        // The last validator of the set is disabled.
+        let validators = get_signed_block_validators(FIXTURE_PATH, 10000);
+        let enabled = (0..4).map(|i| i + 1 < validators.len()).collect::<Vec<_>>();
+        test_assert_enabled_count_template(&enabled, validators.len());
+    }
+
+    fn test_assert_nonempty_validator_set_template(enabled: &[bool]) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let enabled = enabled
+            .iter()
+            .map(|e| builder.constant::<BoolVariable>(*e))
+            .collect::<Vec<_>>();
+        builder.assert_nonempty_validator_set(&enabled);
+
+        let circuit = builder.build();
+        let input = circuit.input();
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_assert_nonempty_validator_set() {
+        test_assert_nonempty_validator_set_template(&[true, false, false, false]);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_nonempty_validator_set_empty() {
+        test_assert_nonempty_validator_set_template(&[false, false, false, false]);
+    }
+
+    fn test_hash_validator_leaf_template(powers: &[u64]) -> Vec<H256> {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let pubkey = builder.read::<Bytes32Variable>();
+        let voting_power = builder.read::<U64Variable>();
+        let leaf_hash = builder.hash_validator_leaf(&pubkey, &voting_power);
+        builder.write(leaf_hash);
+        let circuit = builder.build();
+
+        let mut leaf_hashes = Vec::new();
+        for power in powers.iter() {
+            let mut input = circuit.input();
+            input.write::<Bytes32Variable>(H256::repeat_byte(0x01));
+            input.write::<U64Variable>(*power);
+            let (proof, mut output) = circuit.prove(&input);
+            circuit.verify(&proof, &input, &output);
+            leaf_hashes.push(output.read::<Bytes32Variable>());
+        }
+        leaf_hashes
+    }
+
+    #[test]
+    fn test_hash_validator_leaf() {
+        // Voting powers whose varints are 0 (omitted), 1, 1, 2, 6 and 9 bytes long.
+        let powers = [0, 1, 127, 128, 1 << 35, i64::MAX as u64];
+        let leaf_hashes = test_hash_validator_leaf_template(&powers);
+
+        // The leaf hashes match tendermint's protobuf encoding of the validators.
+        for (power, leaf_hash) in powers.iter().zip(leaf_hashes.iter()) {
+            let validator = Info::new(
+                PublicKey::from_raw_ed25519(&[0x01; 32]).unwrap(),
+                Power::try_from(*power).unwrap(),
+            );
+            let expected_leaf_hash =
+                Sha256::digest([&[MERKLE_LEAF_PREFIX], validator.hash_bytes().as_slice()].concat());
+            assert_eq!(*leaf_hash, H256::from_slice(&expected_leaf_hash));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Integer too large to fit in")]
+    fn test_hash_validator_leaf_not_i64() {
+        // Bit 63 doesn't fit in the 9-byte varint.
+        test_hash_validator_leaf_template(&[1 << 63]);
+    }
+
+    fn test_assert_disabled_leaf_hashes_zero_template(
+        disabled_leaf_hash: H256,
+        check_disabled_leaf_hashes: bool,
+    ) {
+        env_logger::try_init().unwrap_or_default();
+
+        const MAX_VALIDATOR_SET_SIZE: usize = 4;
+        let block_number = 10000;
+        let validators = get_signed_block_validators(FIXTURE_PATH, block_number);
+        let validators_hash = get_signed_block(FIXTURE_PATH, block_number)
+            .header
+            .validators_hash;
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let mut leaf_hashes = Vec::new();
+        let mut enabled = Vec::new();
+        for i in 0..MAX_VALIDATOR_SET_SIZE {
+            if i < validators.len() {
+                let pubkey = builder.constant::<Bytes32Variable>(H256::from_slice(
+                    &validators[i].pub_key.to_bytes(),
+                ));
+                let voting_power = builder.constant::<U64Variable>(validators[i].power.value());
+                leaf_hashes.push(builder.hash_validator_leaf(&pubkey, &voting_power));
+            } else {
+                leaf_hashes.push(builder.constant::<Bytes32Variable>(disabled_leaf_hash));
+            }
+            enabled.push(builder.constant::<BoolVariable>(i < validators.len()));
+        }
+        if check_disabled_leaf_hashes {
+            builder.assert_disabled_leaf_hashes_zero(&leaf_hashes, &enabled);
+        }
+
+        // The root only depends on the enabled leaves.
+        let nb_enabled_leaves =
+            builder.constant::<Variable>(F::from_canonical_usize(validators.len()));
+        let root = builder.get_root_from_hashed_leaves::<MAX_VALIDATOR_SET_SIZE>(
+            ArrayVariable::<Bytes32Variable, MAX_VALIDATOR_SET_SIZE>::from(leaf_hashes),
+            nb_enabled_leaves,
+        );
+        let expected_root =
+            builder.constant::<Bytes32Variable>(H256::from_slice(validators_hash.as_bytes()));
+        builder.assert_is_equal(root, expected_root);
+
+        let circuit = builder.build();
+        let input = circuit.input();
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_assert_disabled_leaf_hashes_zero() {
+        test_assert_disabled_leaf_hashes_zero_template(H256::zero(), true);
+        // Without the check, a disabled leaf can carry arbitrary bits without changing the root.
+        test_assert_disabled_leaf_hashes_zero_template(H256::repeat_byte(0xab), false);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_disabled_leaf_hashes_zero_nonzero() {
+        test_assert_disabled_leaf_hashes_zero_template(H256::repeat_byte(0xab), true);
+    }
+
+    /// Prove next_validators hash to next_validators_hash and have positive voting power.
+    fn test_assert_next_validators_power_positive_template(
+        next_validators_hash: H256,
+        next_validators: &[Info],
+    ) {
+        env_logger::try_init().unwrap_or_default();
+
+        const MAX_VALIDATOR_SET_SIZE: usize = 4;
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let next_validators_hash_var = builder.read::<Bytes32Variable>();
+        let next_validators_var =
+            builder.read::<ArrayVariable<ValidatorVariable, MAX_VALIDATOR_SET_SIZE>>();
+        builder
+            .assert_next_validators_power_positive(&next_validators_hash_var, &next_validators_var);
+        let circuit = builder.build();
+
+        let mut input = circuit.input();
+        input.write::<Bytes32Variable>(next_validators_hash);
+        input.write::<ArrayVariable<ValidatorVariable, MAX_VALIDATOR_SET_SIZE>>(
+            to_validator_values::<F, MAX_VALIDATOR_SET_SIZE>(next_validators),
+        );
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_assert_next_validators_power_positive() {
+        let block_number = 10000;
+        let next_validators_hash = get_signed_block(FIXTURE_PATH, block_number)
+            .header
+            .next_validators_hash;
+        // The validator set doesn't change after the fixture block.
+        let next_validators = get_signed_block_validators(FIXTURE_PATH, block_number);
+        test_assert_next_validators_power_positive_template(
+            H256::from_slice(next_validators_hash.as_bytes()),
+            &next_validators,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_next_validators_power_positive_zero_power() {
+        // A next validator set whose validators all have zero voting power, in the order and with
+        // the leaf encoding tendermint hashes it in.
+        let next_validators =
+            ValidatorSet::without_proposer(vec![make_test_validator(0), make_test_validator(0)])
+                .validators()
+                .clone();
+        test_assert_next_validators_power_positive_template(
+            compute_validators_hash(&next_validators),
+            &next_validators,
+        );
+    }
+
+    fn test_assert_distinct_pubkeys_template(pubkeys: &[u64], enabled: &[bool]) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+
+        let pubkeys = pubkeys
+            .iter()
+            .map(|p| builder.constant::<Bytes32Variable>(H256::from_low_u64_be(*p)))
+            .collect::<Vec<_>>();
+        let enabled = enabled
+            .iter()
+            .map(|e| builder.constant::<BoolVariable>(*e))
+            .collect::<Vec<_>>();
+        builder.assert_distinct_pubkeys(&pubkeys, &enabled);
+
+        let circuit = builder.build();
+        let input = circuit.input();
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_assert_distinct_pubkeys() {
+        test_assert_distinct_pubkeys_template(&[1, 2, 3, 0], &[true, true, true, false]);
+        // Disabled validators may share a pubkey (e.g. zero padding).
+        test_assert_distinct_pubkeys_template(&[1, 2, 0, 0], &[true, true, false, false]);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_distinct_pubkeys_duplicate() {
+        test_assert_distinct_pubkeys_template(&[1, 2, 1, 0], &[true, true, true, false]);
+    }
+
+    fn test_prove_validator_signed_template(
+        target_pubkey: H256,
+        validators: Vec<ValidatorSigner<F>>,
+        expected_is_signer: bool,
+    ) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+
+        let target_pubkey = builder.constant::<Bytes32Variable>(target_pubkey);
+        let validators = validators
+            .into_iter()
+            .map(|v| builder.constant::<ValidatorSignerVariable>(v))
+            .collect::<Vec<_>>();
+        let is_signer = builder.prove_validator_signed(&target_pubkey, &validators);
+        builder.write(is_signer);
+
+        let circuit = builder.build();
+        let input = circuit.input();
+        let (proof, mut output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+
+        assert_eq!(output.read::<BoolVariable>(), expected_is_signer);
+    }
+
+    #[test]
+    fn test_prove_validator_signed() {
+        let pubkeys = (0..3)
+            .map(|i| H256::from_low_u64_be(i + 1))
+            .collect::<Vec<_>>();
+        let signed = [true, false, true];
+        let validators = pubkeys
+            .iter()
+            .zip(signed)
+            .map(|(pubkey, signed)| ValidatorSigner {
+                pubkey: *pubkey,
+                signed,
+            })
+            .collect::<Vec<_>>();
+
+        // A validator that signed.
+        test_prove_validator_signed_template(pubkeys[2], validators.clone(), true);
+        // A validator in the set that did not sign.
+        test_prove_validator_signed_template(pubkeys[1], validators.clone(), false);
+        // A pubkey that is not in the set.
+        test_prove_validator_signed_template(H256::from_low_u64_be(42), validators, false);
+    }
+
+    #[test]
+    fn test_pubkey_to_address() {
+        env_logger::try_init().unwrap_or_default();
+
+        let validators = get_signed_block_validators(FIXTURE_PATH, 10000);
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let pubkey = builder.read::<Bytes32Variable>();
+        let address = builder.pubkey_to_address(&pubkey);
+        builder.write(address);
+        let circuit = builder.build();
+
+        for validator in validators.iter() {
+            let mut input = circuit.input();
+            input.write::<Bytes32Variable>(H256::from_slice(&validator.pub_key.to_bytes()));
+            let (proof, mut output) = circuit.prove(&input);
+            circuit.verify(&proof, &input, &output);
+
+            let address = output.read::<BytesVariable<ADDRESS_SIZE_BYTES>>();
+            assert_eq!(address, validator.address.as_bytes());
+        }
+    }
+
+    /// The timestamps of the signatures of a block's commit, or 0 for absent signatures.
+    fn get_signature_timestamps(block_number: u64) -> Vec<u64> {
+        get_signed_block(FIXTURE_PATH, block_number)
+            .commit
+            .signatures
+            .iter()
+            .map(|signature| match signature {
+                CommitSig::BlockIdFlagCommit { timestamp, .. }
+                | CommitSig::BlockIdFlagNil { timestamp, .. } => {
+                    timestamp.unix_timestamp_nanos() as u64
+                }
+                CommitSig::BlockIdFlagAbsent => 0,
+            })
+            .collect()
+    }
+
+    fn test_verify_bft_time_template(block_number: u64, powers: &[u64], header_time: u64) {
+        env_logger::try_init().unwrap_or_default();
+
+        let signed_header = get_signed_block(FIXTURE_PATH, block_number);
+        let nb_validators = signed_header.commit.signatures.len();
+        assert_eq!(powers.len(), nb_validators);
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let timestamps = (0..nb_validators)
+            .map(|_| builder.read::<U64Variable>())
+            .collect::<Vec<_>>();
+        let power_vars = (0..nb_validators)
+            .map(|_| builder.read::<U64Variable>())
+            .collect::<Vec<_>>();
+        let signed = (0..nb_validators)
+            .map(|_| builder.read::<BoolVariable>())
+            .collect::<Vec<_>>();
+        let header_time_var = builder.read::<U64Variable>();
+        builder.verify_bft_time(&timestamps, &power_vars, &signed, &header_time_var);
+        let circuit = builder.build();
+
+        let mut input = circuit.input();
+        for timestamp in get_signature_timestamps(block_number) {
+            input.write::<U64Variable>(timestamp);
+        }
+        for power in powers.iter() {
+            input.write::<U64Variable>(*power);
+        }
+        for signature in signed_header.commit.signatures.iter() {
+            input.write::<BoolVariable>(!matches!(signature, CommitSig::BlockIdFlagAbsent));
+        }
+        input.write::<U64Variable>(header_time);
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    /// The voting powers of the fixture validators of a block, and the time of the next block,
+    /// which is the BFT time of the block's commit.
+    fn get_bft_time_inputs(block_number: u64) -> (Vec<u64>, u64) {
+        let powers = get_signed_block_validators(FIXTURE_PATH, block_number)
+            .iter()
+            .map(|validator| validator.power.value())
+            .collect();
+        let header_time = get_signed_block(FIXTURE_PATH, block_number + 1)
+            .header
+            .time
+            .unix_timestamp_nanos() as u64;
+        (powers, header_time)
+    }
+
+    #[test]
+    fn test_verify_bft_time() {
+        // The 2 fixture validators have equal voting power, so the BFT time is the earlier
+        // timestamp, which is the first signature's in block 10000 and the second's in 10001.
+        for block_number in [10000, 10001] {
+            let (powers, header_time) = get_bft_time_inputs(block_number);
+            test_verify_bft_time_template(block_number, &powers, header_time);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_verify_bft_time_not_median() {
+        let (powers, header_time) = get_bft_time_inputs(10000);
+        test_verify_bft_time_template(10000, &powers, header_time + 1);
+    }
+
+    #[test]
+    fn test_verify_bft_time_unequal_powers() {
+        // In block 10000, the first signature is earlier than the second. With more than half of
+        // the voting power, the second validator's timestamp is the BFT time.
+        let timestamps = get_signature_timestamps(10000);
+        assert!(timestamps[0] < timestamps[1]);
+        test_verify_bft_time_template(10000, &[30, 70], timestamps[1]);
+        test_verify_bft_time_template(10000, &[70, 30], timestamps[0]);
+        test_verify_bft_time_template(10000, &[i64::MAX as u64, 0], timestamps[0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_verify_bft_time_unequal_powers_lighter_timestamp() {
+        let timestamps = get_signature_timestamps(10000);
+        test_verify_bft_time_template(10000, &[30, 70], timestamps[0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_verify_bft_time_max_power_before() {
+        // All of the voting power, i64::MAX, is before the second timestamp, so it is not the BFT
+        // time. Doubling that power would wrap around to zero.
+        let timestamps = get_signature_timestamps(10000);
+        test_verify_bft_time_template(10000, &[i64::MAX as u64, 0], timestamps[1]);
+    }
+
+    fn test_assert_proposer_signed_template(proposer_signed: bool) {
+        env_logger::try_init().unwrap_or_default();
+
+        let block_number = 10000;
+        let signed_header = get_signed_block(FIXTURE_PATH, block_number);
+        let validators = get_signed_block_validators(FIXTURE_PATH, block_number);
+        let proposer_address = signed_header.header.proposer_address;
+        assert!(validators.iter().any(|v| v.address == proposer_address));
+
+        let proposer_address_proof = InputDataFetcher::default()
+            .get_proposer_address_proof::<F>(&signed_header.header, ChainConfig::default());
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let header = builder.read::<Bytes32Variable>();
+        let proof =
+            builder.read::<MerkleInclusionProofVariable<
+                PROPOSER_ADDRESS_PROOF_DEPTH,
+                PROTOBUF_ADDRESS_SIZE_BYTES,
+            >>();
+        let validator_vars = validators
+            .iter()
+            .map(|_| builder.read::<ValidatorSignerVariable>())
+            .collect::<Vec<_>>();
+        let proposer_address_var =
+            builder.verify_proposer_address(&header, &proof, ChainConfig::default());
+        builder.assert_proposer_signed(&proposer_address_var, &validator_vars);
+        let circuit = builder.build();
+
+        let mut input = circuit.input();
+        input.write::<Bytes32Variable>(H256::from_slice(signed_header.header.hash().as_bytes()));
+        input.write::<MerkleInclusionProofVariable<
+            PROPOSER_ADDRESS_PROOF_DEPTH,
+            PROTOBUF_ADDRESS_SIZE_BYTES,
+        >>(proposer_address_proof);
+        for validator in validators.iter() {
+            // Every validator of the fixture block signed. Drop the proposer's signature to craft a
+            // commit without it.
+            input.write::<ValidatorSignerVariable>(ValidatorSigner {
+                pubkey: H256::from_slice(&validator.pub_key.to_bytes()),
+                signed: proposer_signed || validator.address != proposer_address,
+            });
+        }
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_assert_proposer_signed() {
+        test_assert_proposer_signed_template(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_proposer_signed_proposer_absent() {
+        test_assert_proposer_signed_template(false);
+    }
+
+    fn test_assert_verified_signers_exceed_threshold_template(
+        powers: &[u64],
+        signed: &[bool],
+        verified: &[bool],
+        max_sigs_to_verify: usize,
+    ) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+
+        let validators = (0..powers.len())
+            .map(|i| make_validator_variable(&mut builder, powers[i], signed[i], true, verified[i]))
+            .collect::<Vec<_>>();
+        let voting_powers = validators
+            .iter()
+            .map(|v| v.voting_power)
+            .collect::<Vec<_>>();
+        let signed = validators.iter().map(|v| v.signed).collect::<Vec<_>>();
+        let verified = validators.iter().map(|v| v.verified).collect::<Vec<_>>();
+        builder.assert_verified_signers_exceed_threshold(
+            &voting_powers,
+            &signed,
+            &verified,
+            max_sigs_to_verify,
+        );
+
+        let circuit = builder.build();
+        let input = circuit.input();
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_assert_verified_signers_exceed_threshold() {
+        const NB_VALIDATORS: usize = 128;
+        const MAX_SIGS_TO_VERIFY: usize = 90;
+
+        let validators = (1..=NB_VALIDATORS as u64)
+            .map(make_test_validator)
+            .collect::<Vec<_>>();
+        let powers = validators
+            .iter()
+            .map(|v| v.power.value())
+            .collect::<Vec<_>>();
+        let signed = [true; NB_VALIDATORS];
+
+        let verified = select_signers_to_verify(&validators, &signed, MAX_SIGS_TO_VERIFY);
+        assert_eq!(verified.iter().filter(|v| **v).count(), MAX_SIGS_TO_VERIFY);
+        // The top 90 signers by voting power are the validators with power 39 to 128.
+        for (power, verified) in powers.iter().zip(verified.iter()) {
+            assert_eq!(*verified, *power > 38);
+        }
+
+        test_assert_verified_signers_exceed_threshold_template(
+            &powers,
+            &signed,
+            &verified,
+            MAX_SIGS_TO_VERIFY,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_verified_signers_exceed_threshold_below_threshold() {
+        // The top 40 signers only hold 4340 of the 8256 total voting power.
+        let powers = (1..=128u64).collect::<Vec<_>>();
+        let signed = [true; 128];
+        let verified = powers.iter().map(|p| *p > 88).collect::<Vec<_>>();
+        test_assert_verified_signers_exceed_threshold_template(&powers, &signed, &verified, 90);
+    }
+
+    #[test]
+    fn test_assert_verified_signers_exceed_threshold_large_total() {
+        // The total voting power is i64::MAX = 2^63 - 1, and the verified voting power is
+        // 7 * 2^60, so scaling it by 3 gives 21 * 2^60, which exceeds 2^64. Over 64 bits the
+        // product would wrap to 5 * 2^60, below twice the total, and the check would fail.
+        let powers = [1u64 << 62, (1 << 61) + (1 << 60), (1 << 60) - 1];
+        assert_eq!(powers.iter().sum::<u64>(), i64::MAX as u64);
+        let signed = [true; 3];
+        let verified = [true, true, false];
+        test_assert_verified_signers_exceed_threshold_template(&powers, &signed, &verified, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_verified_signers_exceed_threshold_large_total_below_threshold() {
+        // The verified voting power is 2^62, and 3 * 2^62 is below twice the total of 2^63 - 1.
+        let powers = [1u64 << 62, (1 << 61) + (1 << 60), (1 << 60) - 1];
+        let signed = [true; 3];
+        let verified = [true, false, false];
+        test_assert_verified_signers_exceed_threshold_template(&powers, &signed, &verified, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Integer too large to fit in")]
+    fn test_assert_verified_signers_exceed_threshold_negative_voting_power() {
+        // A voting power with the high bit set is negative as an i64, even though every signer is
+        // verified.
+        let powers = [1u64 << 63, 1, 1];
+        let signed = [true; 3];
+        let verified = [true; 3];
+        test_assert_verified_signers_exceed_threshold_template(&powers, &signed, &verified, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_assert_verified_signers_exceed_threshold_above_cap() {
+        let powers = [10u64, 20, 30, 40];
+        let signed = [true; 4];
+        let verified = [false, true, true, true];
+        test_assert_verified_signers_exceed_threshold_template(&powers, &signed, &verified, 2);
+    }
+
+    #[test]
+    fn test_make_validator_variable_limbs() {
+        env_logger::try_init().unwrap_or_default();
+
+        // Powers at the boundaries of the 32-bit limbs, up to Tendermint's max total voting power.
+        let powers = [
+            0u64,
+            1,
+            u32::MAX as u64,
+            1 << 32,
+            (1 << 32) + 1,
+            i64::MAX as u64 / 8,
+        ];
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        for power in powers {
+            let validator = make_validator_variable(&mut builder, power, true, false, true);
+            builder.write::<U32Variable>(validator.voting_power.limbs[0]);
+            builder.write::<U32Variable>(validator.voting_power.limbs[1]);
+            builder.write::<BoolVariable>(validator.signed);
+            builder.write::<BoolVariable>(validator.enabled);
+            builder.write::<BoolVariable>(validator.verified);
+        }
+        let circuit = builder.build();
+
+        let input = circuit.input();
+        let (proof, mut output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+
+        for power in powers {
+            // limbs[0] is the lower 32 bits of the power.
+            assert_eq!(output.read::<U32Variable>(), power as u32);
+            assert_eq!(output.read::<U32Variable>(), (power >> 32) as u32);
+            assert!(output.read::<BoolVariable>());
+            assert!(!output.read::<BoolVariable>());
+            assert!(output.read::<BoolVariable>());
+        }
+        assert_eq!(make_test_validator(1 << 32).power.value(), 1 << 32);
+    }
+
+    fn test_voting_power_greater_than_threshold_template(
+        voting_power: u64,
+        total_voting_power: u64,
+        threshold_numerator: u32,
+        threshold_denominator: u32,
+        expected: bool,
+    ) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let voting_power_var = builder.read::<U64Variable>();
+        let total_voting_power_var = builder.read::<U64Variable>();
+        let threshold_numerator_var = builder.read::<U32Variable>();
+        let threshold_denominator_var = builder.read::<U32Variable>();
+        let exceeds_threshold = builder.voting_power_greater_than_threshold(
+            &voting_power_var,
+            &total_voting_power_var,
+            &threshold_numerator_var,
+            &threshold_denominator_var,
+        );
+        builder.write(exceeds_threshold);
+        let circuit = builder.build();
+
+        let mut input = circuit.input();
+        input.write::<U64Variable>(voting_power);
+        input.write::<U64Variable>(total_voting_power);
+        input.write::<U32Variable>(threshold_numerator);
+        input.write::<U32Variable>(threshold_denominator);
+        let (proof, mut output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+        assert_eq!(output.read::<BoolVariable>(), expected);
+    }
+
+    #[test]
+    fn test_voting_power_greater_than_threshold() {
+        test_voting_power_greater_than_threshold_template(67, 100, 2, 3, true);
+        test_voting_power_greater_than_threshold_template(66, 99, 2, 3, false);
+        test_voting_power_greater_than_threshold_template(34, 100, 1, 3, true);
+        // The products don't fit in 64 bits.
+        test_voting_power_greater_than_threshold_template(
+            i64::MAX as u64,
+            i64::MAX as u64,
+            u32::MAX - 1,
+            u32::MAX,
+            true,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_voting_power_greater_than_threshold_zero_denominator() {
+        test_voting_power_greater_than_threshold_template(67, 100, 2, 0, true);
+    }
+
+    fn test_voting_power_arithmetic_template(pairs: &[(u64, u64)]) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let a = builder.read::<U64Variable>();
+        let b = builder.read::<U64Variable>();
+        let sum = builder.add_voting_power(&a, &b);
+        let difference = builder.saturating_sub_voting_power(&a, &b);
+        builder.write::<U64Variable>(sum);
+        builder.write::<U64Variable>(difference);
+        let circuit = builder.build();
+
+        for (a, b) in pairs.iter() {
+            let mut input = circuit.input();
+            input.write::<U64Variable>(*a);
+            input.write::<U64Variable>(*b);
+            let (proof, mut output) = circuit.prove(&input);
+            circuit.verify(&proof, &input, &output);
+
+            assert_eq!(output.read::<U64Variable>(), a + b);
+            assert_eq!(output.read::<U64Variable>(), a.saturating_sub(*b));
+        }
+    }
+
+    #[test]
+    fn test_voting_power_arithmetic() {
+        let max = i64::MAX as u64;
+        test_voting_power_arithmetic_template(&[
+            (0, 0),
+            // Saturates at zero.
+            (0, 1),
+            (1, max - 1),
+            (max / 2, max / 2 + 1),
+            // Carries and borrows across the 32-bit limbs.
+            (u32::MAX as u64, 1),
+            (1 << 32, 1),
+            ((1 << 32) + 5, u32::MAX as u64),
+            (max - 1, 1),
+            (max, 0),
+            (max / 2 + 1, max / 2),
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Integer too large to fit in")]
+    fn test_add_voting_power_overflow() {
+        test_voting_power_arithmetic_template(&[(i64::MAX as u64, 1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Integer too large to fit in")]
+    fn test_voting_power_arithmetic_not_i64() {
+        test_voting_power_arithmetic_template(&[(1 << 63, 0)]);
+    }
+
+    fn test_adversarial_fixture_threshold_template(fixture: &BlockFixture) {
+        let powers = fixture
+            .validators
+            .iter()
+            .map(|v| v.power.value())
+            .collect::<Vec<_>>();
+        let signed = fixture
+            .signed_header
+            .commit
+            .signatures
+            .iter()
+            .map(|signature| matches!(signature, CommitSig::BlockIdFlagCommit { .. }))
+            .collect::<Vec<_>>();
+        test_assert_verified_signers_exceed_threshold_template(
+            &powers,
+            &signed,
+            &signed,
+            fixture.validators.len(),
+        );
+    }
+
+    #[test]
+    fn test_adversarial_fixture_threshold() {
+        test_adversarial_fixture_threshold_template(&BlockFixture::load(FIXTURE_PATH, 10000));
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_adversarial_fixture_threshold_insufficient_power() {
+        let fixture = BlockFixture::load(FIXTURE_PATH, 10000);
+        test_adversarial_fixture_threshold_template(&with_insufficient_power(&fixture));
+    }
+
+    #[test]
+    fn test_adversarial_fixture_validators_hash() {
+        let fixture = BlockFixture::load(FIXTURE_PATH, 10000);
+        test_assert_next_validators_power_positive_template(
+            H256::from_slice(fixture.signed_header.header.validators_hash.as_bytes()),
+            &fixture.validators,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_adversarial_fixture_validators_hash_wrong_validator_hash() {
+        let fixture = with_wrong_validator_hash(&BlockFixture::load(FIXTURE_PATH, 10000));
+        test_assert_next_validators_power_positive_template(
+            H256::from_slice(fixture.signed_header.header.validators_hash.as_bytes()),
+            &fixture.validators,
+        );
+    }
+
+    #[test]
+    fn test_select_minimal_signing_set() {
+        let powers = [10u32, 20, 5, 40, 25];
+        let validators = powers
+            .iter()
+            .map(|p| make_test_validator(*p as u64))
+            .collect::<Vec<_>>();
+        // The validator with power 20 did not sign.
+        let signed = [true, false, true, true, true];
+
+        let selected = select_minimal_signing_set(&validators, &signed, 2, 3);
+        assert_eq!(selected, vec![0, 3, 4]);
+
+        // The selected set exceeds 2/3 of the total voting power.
+        let total_voting_power = powers.iter().sum::<u32>();
+        let selected_voting_power = selected.iter().map(|&i| powers[i]).sum::<u32>();
+        assert!(selected_voting_power * 3 > total_voting_power * 2);
+
+        // The selected set is minimal: removing any selected signer drops below the threshold.
+        for &i in selected.iter() {
+            assert!((selected_voting_power - powers[i]) * 3 <= total_voting_power * 2);
+        }
+    }
+}
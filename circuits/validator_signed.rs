@@ -0,0 +1,256 @@
+use plonky2x::backend::circuit::Circuit;
+use plonky2x::frontend::merkle::tree::MerkleInclusionProofVariable;
+use plonky2x::frontend::uint::uint64::U64Variable;
+use plonky2x::prelude::{
+    ArrayVariable, BoolVariable, Bytes32Variable, CircuitBuilder, PlonkParameters, Variable,
+};
+
+use crate::consts::*;
+use crate::header::HeaderBuilder;
+use crate::signature::SignatureBuilder;
+use crate::validator::ValidatorBuilder;
+use crate::vars::{CommitSignatureVariable, ValidatorSignerVariable, ValidatorVariable};
+use crate::vote::VoteBuilder;
+
+/// Proves a validator (by pubkey) signed the commit of a header: the validator is in the validator
+/// set the header's validators_hash commits to, and its signature of the precommit for the header
+/// at height verifies. All inputs are public inputs of the proof, including the validator set and
+/// the signature. If VERIFY_SIGNATURES is false, the signature is not verified, so tests of the
+/// validator set and message checks don't pay for Ed25519 verification. Such a circuit doesn't
+/// prove the validator signed anything, so only tests should disable it.
+#[derive(Debug, Clone)]
+pub struct ValidatorSignedCircuit<
+    const MAX_VALIDATOR_SET_SIZE: usize,
+    const VERIFY_SIGNATURES: bool = true,
+> {
+    _config: usize,
+}
+
+impl<const MAX_VALIDATOR_SET_SIZE: usize, const VERIFY_SIGNATURES: bool> Circuit
+    for ValidatorSignedCircuit<MAX_VALIDATOR_SET_SIZE, VERIFY_SIGNATURES>
+{
+    fn define<L: PlonkParameters<D>, const D: usize>(builder: &mut CircuitBuilder<L, D>) {
+        let header = builder.read::<Bytes32Variable>();
+        let height = builder.read::<U64Variable>();
+        let round_present = builder.read::<BoolVariable>();
+        let validators_hash_proof = builder
+            .read::<MerkleInclusionProofVariable<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES>>();
+        let nb_validators = builder.read::<Variable>();
+        let validators = builder.read::<ArrayVariable<ValidatorVariable, MAX_VALIDATOR_SET_SIZE>>();
+        let target_pubkey = builder.read::<Bytes32Variable>();
+        let target_signature = builder.read::<CommitSignatureVariable>();
+
+        // The validator set is the one the header commits to.
+        let validators_hash = Bytes32Variable::from(&validators_hash_proof.leaf[2..2 + HASH_SIZE]);
+        builder.assert_validators_hash_equals(&header, &validators_hash_proof, &validators_hash);
+        builder.verify_validators_hash::<MAX_VALIDATOR_SET_SIZE>(
+            &validators_hash,
+            &validators,
+            &nb_validators,
+        );
+
+        let true_var = builder._true();
+        let members = validators
+            .as_vec()
+            .iter()
+            .map(|v| ValidatorSignerVariable {
+                pubkey: v.pubkey,
+                signed: v.enabled,
+            })
+            .collect::<Vec<_>>();
+        let is_member = builder.prove_validator_signed(&target_pubkey, &members);
+        builder.assert_is_equal(is_member, true_var);
+
+        builder.verify_vote_round(&target_signature.message.0, &round_present);
+        builder.verify_commit_vote(
+            &target_signature.message.0,
+            &target_signature.message_byte_length,
+            &height,
+            &header,
+            &round_present,
+            &true_var,
+        );
+        if VERIFY_SIGNATURES {
+            builder.verify_signatures::<1>(&[true_var], &[target_pubkey], &[target_signature]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_consensus::SigningKey;
+    use ethers::types::H256;
+    use plonky2x::backend::circuit::DefaultParameters;
+    use plonky2x::prelude::plonky2::field::types::Field;
+    use plonky2x::prelude::DefaultBuilder;
+    use tendermint::Signature;
+    use tendermint_proto::Protobuf;
+    use tendermintx::input::InputDataFetcher;
+
+    use super::*;
+    use crate::fixture::adversarial::{with_corrupted_signature, BlockFixture};
+    use crate::fixture::tests::FIXTURE_PATH;
+    use crate::fixture::{get_signed_block, get_signed_block_validators};
+    use crate::input::commit_signature;
+    use crate::signature::to_commit_signature;
+    use crate::validator::to_validator_values;
+
+    type L = DefaultParameters;
+    type F = <L as PlonkParameters<D>>::Field;
+    const D: usize = 2;
+    const MAX_VALIDATOR_SET_SIZE: usize = 4;
+
+    /// Tampering with the inputs of a proof that a validator signed a commit.
+    #[derive(Default)]
+    struct Tamper {
+        /// Sign the validator's message with this key instead, which claims to be the signer.
+        outsider: Option<SigningKey>,
+        /// Supply the validator's signature of this block's commit instead.
+        signature_block: Option<u64>,
+        /// Corrupt the validator's signature with with_corrupted_signature.
+        corrupted_signature: bool,
+        /// Claim this height for the header instead.
+        height: Option<u64>,
+        /// Claim this length for the signed message instead.
+        message_byte_length: Option<u32>,
+        /// Claim the validator set has this many validators instead.
+        nb_validators: Option<usize>,
+    }
+
+    /// Prove the validator at validator_index of the fixture block signed its commit, with the
+    /// inputs tampered as described by tamper. The signature is only verified if
+    /// VERIFY_SIGNATURES.
+    fn prove_validator_signed<const VERIFY_SIGNATURES: bool>(
+        block_number: u64,
+        validator_index: usize,
+        tamper: Tamper,
+    ) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = DefaultBuilder::new();
+        ValidatorSignedCircuit::<MAX_VALIDATOR_SET_SIZE, VERIFY_SIGNATURES>::define(&mut builder);
+        let circuit = builder.build();
+
+        let signed_header = get_signed_block(FIXTURE_PATH, block_number);
+        let header = &signed_header.header;
+        let validators = get_signed_block_validators(FIXTURE_PATH, block_number);
+        let validators_hash_proof = InputDataFetcher::default()
+            .get_inclusion_proof::<PROTOBUF_HASH_SIZE_BYTES, F>(
+                header,
+                VALIDATORS_HASH_INDEX as u64,
+                header.validators_hash.encode_vec(),
+            );
+        let mut signature_fixture =
+            BlockFixture::load(FIXTURE_PATH, tamper.signature_block.unwrap_or(block_number));
+        if tamper.corrupted_signature {
+            signature_fixture = with_corrupted_signature(&signature_fixture, validator_index);
+        }
+        let (mut signature, message) =
+            commit_signature(&signature_fixture.signed_header, validator_index).unwrap();
+        let mut target_pubkey = H256::from_slice(&validators[validator_index].pub_key.to_bytes());
+        if let Some(outsider) = tamper.outsider {
+            signature = Signature::try_from(outsider.sign(&message).to_bytes().as_slice()).unwrap();
+            target_pubkey = H256::from_slice(outsider.verification_key().as_bytes());
+        }
+
+        let mut input = circuit.input();
+        input.write::<Bytes32Variable>(H256::from_slice(header.hash().as_bytes()));
+        input.write::<U64Variable>(tamper.height.unwrap_or(header.height.value()));
+        input.write::<BoolVariable>(signed_header.commit.round.value() != 0);
+        input.write::<MerkleInclusionProofVariable<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES>>(
+            validators_hash_proof,
+        );
+        input.write::<Variable>(F::from_canonical_usize(
+            tamper.nb_validators.unwrap_or(validators.len()),
+        ));
+        input.write::<ArrayVariable<ValidatorVariable, MAX_VALIDATOR_SET_SIZE>>(
+            to_validator_values::<F, MAX_VALIDATOR_SET_SIZE>(&validators),
+        );
+        input.write::<Bytes32Variable>(target_pubkey);
+        let mut target_signature = to_commit_signature::<F>(&signature, &message);
+        if let Some(message_byte_length) = tamper.message_byte_length {
+            target_signature.message_byte_length = message_byte_length;
+        }
+        input.write::<CommitSignatureVariable>(target_signature);
+
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_validator_signed() {
+        // Both fixture validators signed the commit.
+        prove_validator_signed::<true>(10000, 0, Tamper::default());
+        prove_validator_signed::<true>(10000, 1, Tamper::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_validator_signed_not_in_validator_set() {
+        // A valid signature of the commit's message, by a key outside the validator set.
+        let outsider = SigningKey::new(rand::thread_rng());
+        let tamper = Tamper {
+            outsider: Some(outsider),
+            ..Default::default()
+        };
+        prove_validator_signed::<false>(10000, 0, tamper);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_validator_signed_corrupted_signature() {
+        // The validator's signature of the commit, with a bit of its S scalar flipped.
+        let tamper = Tamper {
+            corrupted_signature: true,
+            ..Default::default()
+        };
+        prove_validator_signed::<true>(10000, 0, tamper);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_validator_signed_other_block() {
+        // A valid signature of the validator, but of the next block's commit.
+        let tamper = Tamper {
+            signature_block: Some(10001),
+            ..Default::default()
+        };
+        prove_validator_signed::<false>(10000, 0, tamper);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_validator_signed_wrong_height() {
+        // The validator's signature of the header's commit, at another height than the commit's.
+        let tamper = Tamper {
+            height: Some(10001),
+            ..Default::default()
+        };
+        prove_validator_signed::<false>(10000, 0, tamper);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_validator_signed_truncated_message() {
+        // The validator's message without its last byte, the end of the chain ID.
+        let signed_header = get_signed_block(FIXTURE_PATH, 10000);
+        let (_, message) = commit_signature(&signed_header, 0).unwrap();
+        let tamper = Tamper {
+            message_byte_length: Some(message.len() as u32 - 1),
+            ..Default::default()
+        };
+        prove_validator_signed::<false>(10000, 0, tamper);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_validator_signed_wrong_validator_count() {
+        // The fixture's validator set has 2 validators, so a count of 1 doesn't match its enabled
+        // validators.
+        let tamper = Tamper {
+            nb_validators: Some(1),
+            ..Default::default()
+        };
+        prove_validator_signed::<false>(10000, 0, tamper);
+    }
+}
@@ -1,8 +1,9 @@
+use plonky2x::frontend::ecc::curve25519::ed25519::eddsa::EDDSASignatureVariable;
 use plonky2x::frontend::merkle::tree::MerkleInclusionProofVariable;
 use plonky2x::frontend::uint::uint64::U64Variable;
 use plonky2x::prelude::{
-    ArrayVariable, BoolVariable, Bytes32Variable, CircuitBuilder, CircuitVariable, PlonkParameters,
-    RichField, Variable,
+    ArrayVariable, BoolVariable, Bytes32Variable, BytesVariable, CircuitBuilder, CircuitVariable,
+    PlonkParameters, RichField, U32Variable, Variable,
 };
 
 use crate::consts::*;
@@ -34,3 +35,61 @@ pub struct MapReduceSubchainVariable {
     pub end_header: Bytes32Variable,
     pub data_merkle_root: Bytes32Variable,
 }
+
+/// A validator's Ed25519 public key and whether it signed the commit.
+#[derive(Clone, Debug, CircuitVariable)]
+#[value_name(ValidatorSigner)]
+pub struct ValidatorSignerVariable {
+    pub pubkey: Bytes32Variable,
+    pub signed: BoolVariable,
+}
+
+/// A validator of a validator set: its Ed25519 public key, voting power, and whether it is in the
+/// set, as the set is padded to a fixed size.
+#[derive(Clone, Debug, CircuitVariable)]
+#[value_name(Validator)]
+pub struct ValidatorVariable {
+    pub pubkey: Bytes32Variable,
+    pub voting_power: U64Variable,
+    pub enabled: BoolVariable,
+}
+
+/// A validator's signature on the commit of a block, and the message it signed: a length-prefixed
+/// CanonicalVote of message_byte_length bytes, padded to VOTE_MESSAGE_MAX_SIZE_BYTES.
+#[derive(Clone, Debug, CircuitVariable)]
+#[value_name(CommitSignature)]
+pub struct CommitSignatureVariable {
+    pub signature: EDDSASignatureVariable,
+    pub message: BytesVariable<VOTE_MESSAGE_MAX_SIZE_BYTES>,
+    pub message_byte_length: U32Variable,
+}
+
+/// A validator's Ed25519 public key, voting power, and whether it is in the set, as the set is
+/// padded to a fixed size. For each of two conflicting commits at the same height, whether it
+/// signed the commit, and its signature.
+#[derive(Clone, Debug, CircuitVariable)]
+#[value_name(EquivocationValidator)]
+pub struct EquivocationValidatorVariable {
+    pub pubkey: Bytes32Variable,
+    pub voting_power: U64Variable,
+    pub enabled: BoolVariable,
+    pub signed_a: BoolVariable,
+    pub signature_a: CommitSignatureVariable,
+    pub signed_b: BoolVariable,
+    pub signature_b: CommitSignatureVariable,
+}
+
+/// The inputs of the header checks of a skip: the proofs of the versions of the trusted and target
+/// headers, the proof of the target header's next_validators_hash, and the next validator set,
+/// padded to a fixed size.
+#[derive(Clone, Debug, CircuitVariable)]
+#[value_name(SkipHeaderInputs)]
+pub struct SkipHeaderInputsVariable<const MAX_VALIDATOR_SET_SIZE: usize> {
+    pub trusted_version_proof:
+        MerkleInclusionProofVariable<HEADER_PROOF_DEPTH, PROTOBUF_VERSION_SIZE_BYTES>,
+    pub target_version_proof:
+        MerkleInclusionProofVariable<HEADER_PROOF_DEPTH, PROTOBUF_VERSION_SIZE_BYTES>,
+    pub next_validators_hash_proof:
+        MerkleInclusionProofVariable<HEADER_PROOF_DEPTH, PROTOBUF_HASH_SIZE_BYTES>,
+    pub next_validators: ArrayVariable<ValidatorVariable, MAX_VALIDATOR_SET_SIZE>,
+}
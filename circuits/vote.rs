@@ -0,0 +1,561 @@
+use plonky2x::prelude::*;
+use tendermint::block::{self, Height, Round};
+use tendermint::{chain, vote, Time};
+use tendermint_proto::Protobuf;
+
+use crate::consts::*;
+
+/// A CanonicalVote, the message a validator signs, with conversions to and from its signed bytes
+/// so tests can construct and inspect signed messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanonicalVote {
+    pub vote_type: vote::Type,
+    pub height: Height,
+    pub round: Round,
+    pub block_id: Option<block::Id>,
+    pub timestamp: Option<Time>,
+    pub chain_id: chain::Id,
+}
+
+impl CanonicalVote {
+    /// Encode the vote as a length-prefixed protobuf, the bytes a validator signs.
+    pub fn to_sign_bytes(&self) -> Vec<u8> {
+        vote::CanonicalVote {
+            vote_type: self.vote_type,
+            height: self.height,
+            round: self.round,
+            block_id: self.block_id,
+            timestamp: self.timestamp,
+            chain_id: self.chain_id.clone(),
+        }
+        .encode_length_delimited_vec()
+    }
+
+    /// Decode a vote from the bytes a validator signs.
+    pub fn from_sign_bytes(bytes: &[u8]) -> Result<Self, tendermint_proto::Error> {
+        let vote = vote::CanonicalVote::decode_length_delimited_vec(bytes)?;
+        Ok(Self {
+            vote_type: vote.vote_type,
+            height: vote.height,
+            round: vote.round,
+            block_id: vote.block_id,
+            timestamp: vote.timestamp,
+            chain_id: vote.chain_id,
+        })
+    }
+}
+
+/// Gadgets over the signed messages of a commit. Each signed message is a length-prefixed,
+/// protobuf-encoded CanonicalVote.
+/// Spec: https://github.com/cometbft/cometbft/blob/v0.34.x/proto/tendermint/types/canonical.proto
+pub trait VoteBuilder<L: PlonkParameters<D>, const D: usize> {
+    /// Verify the signed message is a precommit. A commit is made of precommits, so a signature
+    /// over a prevote for the same block must not be counted towards it.
+    fn verify_vote_type_precommit(&mut self, message: &[ByteVariable]);
+
+    /// Whether the signed message is a precommit, as checked by verify_vote_type_precommit.
+    fn is_vote_type_precommit(&mut self, message: &[ByteVariable]) -> BoolVariable;
+
+    /// Verify the height encoded in the signed message equals the height of the header it
+    /// certifies.
+    fn verify_vote_height(&mut self, message: &[ByteVariable], height: &U64Variable);
+
+    /// Whether the height encoded in the signed message equals height, as checked by
+    /// verify_vote_height.
+    fn is_vote_height(&mut self, message: &[ByteVariable], height: &U64Variable) -> BoolVariable;
+
+    /// Verify message_bit_length, the length of the signed message the signature is verified
+    /// over, is the length of the length-prefixed message: the length prefix plus the number of
+    /// bytes it encodes. Otherwise a signature could be verified over a truncated message.
+    fn verify_vote_message_bit_length(
+        &mut self,
+        message: &[ByteVariable],
+        message_bit_length: &U32Variable,
+    );
+
+    /// Whether message_bit_length is the length of the length-prefixed message, as checked by
+    /// verify_vote_message_bit_length.
+    fn is_vote_message_bit_length(
+        &mut self,
+        message: &[ByteVariable],
+        message_bit_length: &U32Variable,
+    ) -> BoolVariable;
+
+    /// Decode the round from the signed message, and verify it is encoded iff round_present.
+    /// Protobuf omits a zero round, so the round is zero iff round_present is false, which binds
+    /// round_present to the commit round.
+    fn verify_vote_round(
+        &mut self,
+        message: &[ByteVariable],
+        round_present: &BoolVariable,
+    ) -> U64Variable;
+
+    /// Return the offset of the block hash in a signed message, which follows the round if
+    /// round_present.
+    fn get_vote_block_hash_offset(&mut self, round_present: &BoolVariable) -> Variable;
+
+    /// Verify whether the round is encoded in each signed message matches round_present, which is
+    /// shared by all messages of the commit. Messages of validators which did not sign are not
+    /// checked.
+    fn verify_votes_round_present(
+        &mut self,
+        messages: &[Vec<ByteVariable>],
+        signed: &[BoolVariable],
+        round_present: &BoolVariable,
+    );
+
+    /// Verify message is a validator's signed message for the commit of the block with block_hash
+    /// at height: a precommit for the height and block hash, whose length is message_byte_length.
+    /// The message is padded to a fixed size, so the signature must only be verified over
+    /// message_byte_length bytes. The block hash is read after the round if round_present, so
+    /// round_present must be bound to the message by verify_vote_round or
+    /// verify_votes_round_present. The message is only checked if signed, as a validator which
+    /// did not sign the block has no precommit for it.
+    fn verify_commit_vote(
+        &mut self,
+        message: &[ByteVariable],
+        message_byte_length: &U32Variable,
+        height: &U64Variable,
+        block_hash: &Bytes32Variable,
+        round_present: &BoolVariable,
+        signed: &BoolVariable,
+    );
+}
+
+impl<L: PlonkParameters<D>, const D: usize> VoteBuilder<L, D> for CircuitBuilder<L, D> {
+    fn verify_vote_type_precommit(&mut self, message: &[ByteVariable]) {
+        let true_var = self._true();
+        let is_precommit = self.is_vote_type_precommit(message);
+        self.assert_is_equal(is_precommit, true_var);
+    }
+
+    fn is_vote_type_precommit(&mut self, message: &[ByteVariable]) -> BoolVariable {
+        assert!(message.len() > VOTE_TYPE_INDEX);
+
+        let type_tag = ByteVariable::constant(self, VOTE_TYPE_TAG);
+        let is_type_tag = self.is_equal(message[VOTE_TYPE_TAG_INDEX], type_tag);
+        let precommit = ByteVariable::constant(self, VOTE_TYPE_PRECOMMIT);
+        let is_precommit = self.is_equal(message[VOTE_TYPE_INDEX], precommit);
+        self.and(is_type_tag, is_precommit)
+    }
+
+    fn verify_vote_height(&mut self, message: &[ByteVariable], height: &U64Variable) {
+        let true_var = self._true();
+        let is_height = self.is_vote_height(message, height);
+        self.assert_is_equal(is_height, true_var);
+    }
+
+    fn is_vote_height(&mut self, message: &[ByteVariable], height: &U64Variable) -> BoolVariable {
+        assert!(message.len() >= VOTE_HEIGHT_INDEX + VOTE_HEIGHT_SIZE_BYTES);
+
+        // Check the height field is at the expected offset.
+        let height_tag = ByteVariable::constant(self, VOTE_HEIGHT_TAG);
+        let mut is_height = self.is_equal(message[VOTE_HEIGHT_TAG_INDEX], height_tag);
+
+        // The vote encodes the height little-endian, while U64Variable encodes big-endian.
+        let encoded_height = height.encode(self);
+        for i in 0..VOTE_HEIGHT_SIZE_BYTES {
+            let byte_matches = self.is_equal(
+                message[VOTE_HEIGHT_INDEX + i],
+                encoded_height[VOTE_HEIGHT_SIZE_BYTES - 1 - i],
+            );
+            is_height = self.and(is_height, byte_matches);
+        }
+        is_height
+    }
+
+    fn verify_vote_message_bit_length(
+        &mut self,
+        message: &[ByteVariable],
+        message_bit_length: &U32Variable,
+    ) {
+        let true_var = self._true();
+        let is_bit_length = self.is_vote_message_bit_length(message, message_bit_length);
+        self.assert_is_equal(is_bit_length, true_var);
+    }
+
+    fn is_vote_message_bit_length(
+        &mut self,
+        message: &[ByteVariable],
+        message_bit_length: &U32Variable,
+    ) -> BoolVariable {
+        // The length prefix is a single-byte varint, so its continuation bit is unset.
+        let length_prefix = message[VOTE_LENGTH_PREFIX_INDEX];
+        let is_single_byte = self.not(length_prefix.as_be_bits()[0]);
+
+        // (VOTE_LENGTH_PREFIX_SIZE_BYTES + length_prefix) * 8
+        let length_prefix = length_prefix.to_variable(self);
+        let prefix_size = self.constant::<Variable>(L::Field::from_canonical_usize(
+            VOTE_LENGTH_PREFIX_SIZE_BYTES,
+        ));
+        let byte_length = self.add(prefix_size, length_prefix);
+        let eight = self.constant::<Variable>(L::Field::from_canonical_u64(8));
+        let bit_length = self.mul(byte_length, eight);
+        let is_bit_length = self.is_equal(bit_length, message_bit_length.variable);
+        self.and(is_single_byte, is_bit_length)
+    }
+
+    fn verify_vote_round(
+        &mut self,
+        message: &[ByteVariable],
+        round_present: &BoolVariable,
+    ) -> U64Variable {
+        assert!(message.len() >= VOTE_ROUND_INDEX + VOTE_ROUND_SIZE_BYTES);
+
+        // The round is present iff the field after the height is the round.
+        let round_tag = ByteVariable::constant(self, VOTE_ROUND_TAG);
+        let message_round_present = self.is_equal(message[VOTE_ROUND_TAG_INDEX], round_tag);
+        self.assert_is_equal(message_round_present, *round_present);
+
+        // The vote encodes the round little-endian, while U64Variable decodes big-endian. The
+        // bytes after the height aren't the round if it's omitted.
+        let mut round_bytes =
+            message[VOTE_ROUND_INDEX..VOTE_ROUND_INDEX + VOTE_ROUND_SIZE_BYTES].to_vec();
+        round_bytes.reverse();
+        let encoded_round = U64Variable::decode(self, &round_bytes);
+        let zero = self.constant::<U64Variable>(0);
+        let round = self.select(*round_present, encoded_round, zero);
+
+        // An encoded round of zero is not canonical.
+        let is_zero_round = self.is_equal(round, zero);
+        let round_absent = self.not(*round_present);
+        self.assert_is_equal(is_zero_round, round_absent);
+        round
+    }
+
+    fn get_vote_block_hash_offset(&mut self, round_present: &BoolVariable) -> Variable {
+        let offset_with_round = self.constant::<Variable>(L::Field::from_canonical_usize(
+            VOTE_BLOCK_HASH_INDEX_WITH_ROUND,
+        ));
+        let offset =
+            self.constant::<Variable>(L::Field::from_canonical_usize(VOTE_BLOCK_HASH_INDEX));
+        self.select(*round_present, offset_with_round, offset)
+    }
+
+    fn verify_votes_round_present(
+        &mut self,
+        messages: &[Vec<ByteVariable>],
+        signed: &[BoolVariable],
+        round_present: &BoolVariable,
+    ) {
+        assert_eq!(messages.len(), signed.len());
+
+        let true_var = self._true();
+        let round_tag = ByteVariable::constant(self, VOTE_ROUND_TAG);
+        for (message, signed) in messages.iter().zip(signed.iter()) {
+            assert!(message.len() > VOTE_ROUND_TAG_INDEX);
+
+            // The round is present iff the field after the height is the round.
+            let message_round_present = self.is_equal(message[VOTE_ROUND_TAG_INDEX], round_tag);
+            let matches = self.is_equal(message_round_present, *round_present);
+            let not_signed = self.not(*signed);
+            let consistent = self.or(not_signed, matches);
+            self.assert_is_equal(consistent, true_var);
+        }
+    }
+
+    fn verify_commit_vote(
+        &mut self,
+        message: &[ByteVariable],
+        message_byte_length: &U32Variable,
+        height: &U64Variable,
+        block_hash: &Bytes32Variable,
+        round_present: &BoolVariable,
+        signed: &BoolVariable,
+    ) {
+        let true_var = self._true();
+        let max_byte_length = self.constant::<U32Variable>(message.len() as u32);
+        let mut is_commit_vote = self.lte(*message_byte_length, max_byte_length);
+
+        // message_byte_length is below 2^32, so its bit length doesn't wrap around the field.
+        let eight = self.constant::<Variable>(L::Field::from_canonical_u64(8));
+        let message_bit_length = self.mul(message_byte_length.variable, eight);
+        let message_bit_length = U32Variable::from_variables_unsafe(&[message_bit_length]);
+        let is_bit_length = self.is_vote_message_bit_length(message, &message_bit_length);
+        is_commit_vote = self.and(is_commit_vote, is_bit_length);
+
+        let is_precommit = self.is_vote_type_precommit(message);
+        is_commit_vote = self.and(is_commit_vote, is_precommit);
+        let is_height = self.is_vote_height(message, height);
+        is_commit_vote = self.and(is_commit_vote, is_height);
+
+        let block_hash_offset = self.get_vote_block_hash_offset(round_present);
+        for (i, block_hash_byte) in block_hash.as_bytes().iter().enumerate() {
+            let i = self.constant::<Variable>(L::Field::from_canonical_usize(i));
+            let index = self.add(block_hash_offset, i);
+            let message_byte = self.select_array(message, index);
+            let byte_matches = self.is_equal(message_byte, *block_hash_byte);
+            is_commit_vote = self.and(is_commit_vote, byte_matches);
+        }
+
+        let not_signed = self.not(*signed);
+        let consistent = self.or(not_signed, is_commit_vote);
+        self.assert_is_equal(consistent, true_var);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use plonky2x::prelude::plonky2::field::types::PrimeField64;
+    use subtle_encoding::hex;
+    use tendermint::block::parts;
+    use tendermint::Hash;
+
+    use super::*;
+
+    type L = DefaultParameters;
+    const D: usize = 2;
+
+    /// Encode the prefix of a signed precommit message up to and including the height.
+    pub(crate) fn encode_vote_prefix(height: u64) -> Vec<u8> {
+        // Length prefix (unused by the gadgets), vote type (precommit), height tag.
+        let mut message = vec![0x6e, VOTE_TYPE_TAG, VOTE_TYPE_PRECOMMIT, VOTE_HEIGHT_TAG];
+        message.extend(height.to_le_bytes());
+        message
+    }
+
+    fn test_verify_vote_type_precommit_template(vote_type: u8) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+
+        let mut message = encode_vote_prefix(144094);
+        message[VOTE_TYPE_INDEX] = vote_type;
+        let message = message
+            .iter()
+            .map(|b| builder.constant::<ByteVariable>(*b))
+            .collect::<Vec<_>>();
+        builder.verify_vote_type_precommit(&message);
+
+        let circuit = builder.build();
+        let input = circuit.input();
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_verify_vote_type_precommit() {
+        test_verify_vote_type_precommit_template(VOTE_TYPE_PRECOMMIT);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_verify_vote_type_prevote() {
+        test_verify_vote_type_precommit_template(0x01);
+    }
+
+    fn test_verify_vote_message_bit_length_template(message_bit_length: u32) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+
+        // The length prefix of the message is 0x6e, so the message is 111 bytes.
+        let message = encode_vote_prefix(144094)
+            .iter()
+            .map(|b| builder.constant::<ByteVariable>(*b))
+            .collect::<Vec<_>>();
+        let message_bit_length_var = builder.constant::<U32Variable>(message_bit_length);
+        builder.verify_vote_message_bit_length(&message, &message_bit_length_var);
+
+        let circuit = builder.build();
+        let input = circuit.input();
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_verify_vote_message_bit_length() {
+        test_verify_vote_message_bit_length_template(111 * 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_verify_vote_message_bit_length_truncated() {
+        test_verify_vote_message_bit_length_template(110 * 8);
+    }
+
+    /// Encode the prefix of a signed precommit message up to the field after the height, which is
+    /// the round if round_present and the block ID otherwise.
+    fn encode_vote_prefix_with_round(height: u64, round_present: bool) -> Vec<u8> {
+        let mut message = encode_vote_prefix(height);
+        if round_present {
+            message.push(VOTE_ROUND_TAG);
+            message.extend(1u64.to_le_bytes());
+        } else {
+            // Tag of the block ID: (4 << 3) | 2 (length-delimited wire type).
+            message.push(0x22);
+        }
+        message
+    }
+
+    fn test_verify_vote_height_template(message_height: u64, header_height: u64) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+
+        let message = encode_vote_prefix(message_height)
+            .iter()
+            .map(|b| builder.constant::<ByteVariable>(*b))
+            .collect::<Vec<_>>();
+        let height = builder.constant::<U64Variable>(header_height);
+        builder.verify_vote_height(&message, &height);
+
+        let circuit = builder.build();
+        let input = circuit.input();
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_verify_vote_height() {
+        test_verify_vote_height_template(144094, 144094);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_verify_vote_height_altered() {
+        test_verify_vote_height_template(144095, 144094);
+    }
+
+    fn test_verify_votes_round_present_template(
+        messages_round_present: &[bool],
+        signed: &[bool],
+        round_present: bool,
+    ) {
+        env_logger::try_init().unwrap_or_default();
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+
+        let messages = messages_round_present
+            .iter()
+            .map(|present| {
+                encode_vote_prefix_with_round(144094, *present)
+                    .iter()
+                    .map(|b| builder.constant::<ByteVariable>(*b))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let signed = signed
+            .iter()
+            .map(|s| builder.constant::<BoolVariable>(*s))
+            .collect::<Vec<_>>();
+        let round_present = builder.constant::<BoolVariable>(round_present);
+        builder.verify_votes_round_present(&messages, &signed, &round_present);
+
+        let circuit = builder.build();
+        let input = circuit.input();
+        let (proof, output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+    }
+
+    #[test]
+    fn test_verify_votes_round_present() {
+        test_verify_votes_round_present_template(&[true, true], &[true, true], true);
+        test_verify_votes_round_present_template(&[false, false], &[true, true], false);
+        // The message of a validator which did not sign is not checked.
+        test_verify_votes_round_present_template(&[false, true], &[true, false], false);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_verify_votes_round_present_inconsistent() {
+        test_verify_votes_round_present_template(&[false, true], &[true, true], false);
+    }
+
+    fn test_verify_vote_round_template(round: Option<u64>, round_present: bool) {
+        env_logger::try_init().unwrap_or_default();
+
+        let block_hash = [0xab; HASH_SIZE];
+        let mut message = encode_vote_prefix(144094);
+        if let Some(round) = round {
+            message.push(VOTE_ROUND_TAG);
+            message.extend(round.to_le_bytes());
+        }
+        // The block ID tag and length, then the tag and length of the block hash.
+        message.extend([0x22, 0x48, 0x0a, HASH_SIZE as u8]);
+        message.extend(block_hash);
+
+        let mut builder = CircuitBuilder::<L, D>::new();
+        let message_var = message
+            .iter()
+            .map(|b| builder.constant::<ByteVariable>(*b))
+            .collect::<Vec<_>>();
+        let round_present_var = builder.constant::<BoolVariable>(round_present);
+        let round_var = builder.verify_vote_round(&message_var, &round_present_var);
+        builder.write(round_var);
+        let offset = builder.get_vote_block_hash_offset(&round_present_var);
+        builder.write(offset);
+
+        let circuit = builder.build();
+        let input = circuit.input();
+        let (proof, mut output) = circuit.prove(&input);
+        circuit.verify(&proof, &input, &output);
+
+        assert_eq!(output.read::<U64Variable>(), round.unwrap_or(0));
+        let offset = output.read::<Variable>().to_canonical_u64() as usize;
+        assert_eq!(message[offset..offset + HASH_SIZE], block_hash);
+    }
+
+    #[test]
+    fn test_verify_vote_round() {
+        test_verify_vote_round_template(None, false);
+        test_verify_vote_round_template(Some(1), true);
+        assert_eq!(VOTE_BLOCK_HASH_INDEX_WITH_ROUND, 25);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_verify_vote_round_not_present() {
+        test_verify_vote_round_template(Some(1), false);
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn test_verify_vote_round_encoded_zero() {
+        test_verify_vote_round_template(Some(0), true);
+    }
+
+    #[test]
+    fn test_canonical_vote_sign_bytes() {
+        // The signed message of the last validator of block 144094 of Celestia's Mocha 3 testnet,
+        // from test_verify_hash_in_message in tendermintx. The round is 0, so it is omitted.
+        let signed_message = hex::decode(
+            "6b080211de3202000000000022480a208909e1b73b7d987e95a7541d96ed484c17a4b0411e98ee4b7c890ad21302ff8c12240801122061263df4855e55fcab7aab0a53ee32cf4f29a1101b56de4a9d249d44e4cf96282a0b089dce84a60610ebb7a81932076d6f6368612d33",
+        )
+        .unwrap();
+        let hash = |hex_hash: &str| {
+            Hash::from_hex_upper(
+                tendermint::hash::Algorithm::Sha256,
+                &hex_hash.to_uppercase(),
+            )
+            .unwrap()
+        };
+        let vote = CanonicalVote {
+            vote_type: vote::Type::Precommit,
+            height: Height::from(144094u32),
+            round: Round::from(0u16),
+            block_id: Some(block::Id {
+                hash: hash("8909e1b73b7d987e95a7541d96ed484c17a4b0411e98ee4b7c890ad21302ff8c"),
+                part_set_header: parts::Header::new(
+                    1,
+                    hash("61263df4855e55fcab7aab0a53ee32cf4f29a1101b56de4a9d249d44e4cf9628"),
+                )
+                .unwrap(),
+            }),
+            timestamp: Some(Time::from_unix_timestamp(1690380061, 53091307).unwrap()),
+            chain_id: chain::Id::try_from("mocha-3").unwrap(),
+        };
+
+        assert_eq!(vote.to_sign_bytes(), signed_message);
+        assert_eq!(
+            CanonicalVote::from_sign_bytes(&signed_message).unwrap(),
+            vote
+        );
+
+        // The height is at the offset the gadgets read it from.
+        assert_eq!(
+            signed_message[VOTE_HEIGHT_INDEX..VOTE_HEIGHT_INDEX + VOTE_HEIGHT_SIZE_BYTES],
+            144094u64.to_le_bytes()
+        );
+    }
+}